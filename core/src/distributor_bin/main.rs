@@ -1,4 +1,5 @@
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::fs::canonicalize;
 use std::env::{
     // current_exe,
     args_os
@@ -26,7 +27,8 @@ fn main() -> Result<(), String> {
         return Err(String::from("Argument required | try `sigrs --help`"));
     };
 
-    match sub_cmd.to_string_lossy().as_ref() {
+    let sub_name = sub_cmd.to_string_lossy().into_owned();
+    match sub_name.as_str() {
         // continue
         "set-config-path" => {},
         _ => {
@@ -42,13 +44,17 @@ fn main() -> Result<(), String> {
             if !res.success() {
                 return Err(String::from(""));
             }
+            if sub_name == "generate-config" || sub_name == "--generate-config" {
+                // template was created by sigrs_function, save its path if asked to
+                return register_generated_config();
+            }
             return Ok(());
         }
     }
 
     let m = command().get_matches();
     let Some(("set-config-path", sub_m)) = m.subcommand() else {
-        panic!("unreachable");
+        return Err(String::from("Expected `sigrs set-config-path`"));
     };
 
     // cfg_path is the path to set
@@ -56,6 +62,35 @@ fn main() -> Result<(), String> {
         return Err(String::from("--path required"));
     };
 
+    save_config_path(cfg_path, sub_m.get_flag("no-generate"))
+}
+
+/// `sigrs generate-config --path=... --register`
+/// Must run here rather than in sigrs_function, since sigrs_modifier can't
+/// rewrite the sigrs_function binary while it is running
+fn register_generated_config() -> Result<(), String> {
+    // sigrs_function already validated these args && wrote the template, so an
+    // error here must not exit without saying what went wrong
+    let m = command().try_get_matches()
+        .map_err(|e| format!("Template created, but could not read --register: {}", e))?;
+    let Some(("generate-config", sub_m)) = m.subcommand() else {
+        return Err(String::from("Expected `sigrs generate-config`"));
+    };
+    if !sub_m.get_flag("register") {
+        return Ok(());
+    }
+    let Some(cfg_path) = sub_m.get_one::<OsString>("path") else {
+        return Err(String::from("--path required"));
+    };
+
+    // saved path must not depend on where sigrs was run from
+    let cfg_path = canonicalize(cfg_path)
+        .map_err(|e| e.to_string())?;
+    save_config_path(cfg_path.as_os_str(), true)
+}
+
+/// Saves `cfg_path` at the end of the sigrs_function binary via sigrs_modifier
+fn save_config_path(cfg_path: &OsStr, no_generate: bool) -> Result<(), String> {
     // call `sigrs_functionality get-bin-path`, read output from stdout
     let bin_path_res = ProcessCommand::new("sigrs_function")
         .arg("get-bin-path")
@@ -87,7 +122,7 @@ fn main() -> Result<(), String> {
         .arg("--new-cfg-path")
         .arg(cfg_path);
 
-    if sub_m.get_flag("no-generate") {
+    if no_generate {
         cmd.arg("--no-generate");
     }
 
@@ -116,6 +151,12 @@ fn command() -> Command {
     Command::new("sigrs")
         .about("sigrs")
         .color(clap::ColorChoice::Always)
+        // global in sigrs_function, accepted so it can be passed to generate-config
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .global(true)
+        )
         .subcommand(
             Command::new("set-config-path")
                 .arg_required_else_help(true)
@@ -132,4 +173,21 @@ fn command() -> Command {
                         .action(ArgAction::SetTrue)
                 )
         )
+        // validated && run by sigrs_function first, only --register is handled here
+        .subcommand(
+            Command::new("generate-config")
+                .long_flag("generate-config")
+                .arg(
+                    Arg::new("path")
+                        .required(true)
+                        .long("path")
+                        .short('p')
+                        .value_parser(value_parser!(OsString))
+                )
+                .arg(
+                    Arg::new("register")
+                        .long("register")
+                        .action(ArgAction::SetTrue)
+                )
+        )
 }
@@ -140,7 +140,8 @@ fn main() -> Result<(), String> {
             .write(true).create_new(true)
             .open(new_cfg_path)
             .map_err(|e| e.to_string())?;
-        let emp_ct = "# File generated by signatu_rs. Do not manually edit this file. #\n";
+        // must match `las_header()` in sigrs_bin/las/version.rs
+        let emp_ct = "# File generated by signatu_rs. Do not manually edit this file. #\n#!las-version:3\n";
         empty_cfg_handle
             .write_all(emp_ct.as_bytes())
             .map_err(|e| e.to_string())?;
@@ -1,5 +1,6 @@
 #![allow(non_snake_case, dead_code)]
 use anyhow::Result;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 
@@ -18,10 +19,36 @@ use std::fmt::Debug;
 // - This WILL cause UD if there's a write to the file while I hold a reference
 // --- to the memmapped file
 // - So I need to ensure that all references are dropped before writing
+// --- other sigrs processes are kept out by the shared lock `memmap::LasMmap`
+// --- holds while mapped (see `las/lock.rs`), editors etc. still are not
+//
+// Values are only copied out of the memmap (Cow::Owned) when they contained
+// escape sequences, see `las/format.rs`
 #[derive(Clone, Debug)]
 pub struct IdentityPerf<'LASMemoryMap> {
-    pub usernames: Vec<&'LASMemoryMap [u8]>,
-    pub emails: Vec<&'LASMemoryMap [u8]>
+    pub usernames: Vec<Cow<'LASMemoryMap, [u8]>>,
+    pub emails: Vec<Cow<'LASMemoryMap, [u8]>>,
+    /// (field, value) pairs from the optional `M:` line
+    pub metadata: Vec<MetadataPair<'LASMemoryMap>>,
+    /// (kind, pattern) pairs from the optional `R:` line, see `las/rules.rs`
+    pub rules: Vec<MetadataPair<'LASMemoryMap>>
+}
+
+pub type MetadataPair<'LASMemoryMap> = (Cow<'LASMemoryMap, [u8]>, Cow<'LASMemoryMap, [u8]>);
+
+/// Optional fields an identity can store besides usernames/emails,
+/// in the order they are written to the `M:` line
+pub const METADATA_FIELDS: [&str; 4] = ["url", "role", "organization", "fingerprint"];
+
+/// Owned copy of an identity's metadata, handed to the signers
+/// - package.json contributors use `url`
+/// - composer.json authors use `url` (as `homepage`) && `role`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuthorMeta {
+    pub url: Option<String>,
+    pub role: Option<String>,
+    pub organization: Option<String>,
+    pub fingerprint: Option<String>,
 }
 
 impl<'LASMemoryMap> IdentityPerf<'LASMemoryMap> {
@@ -31,17 +58,75 @@ impl<'LASMemoryMap> IdentityPerf<'LASMemoryMap> {
     pub fn new() -> Self {
         Self {
             usernames: Vec::new(),
-            emails: Vec::new()
+            emails: Vec::new(),
+            metadata: Vec::new(),
+            rules: Vec::new()
         }
     }
 
-    pub fn add_username(&mut self, username: &'LASMemoryMap [u8]) -> () {
+    pub fn add_username(&mut self, username: Cow<'LASMemoryMap, [u8]>) -> () {
         self.usernames.push(username);
     }
 
-    pub fn add_email(&mut self, email: &'LASMemoryMap [u8]) -> () {
+    pub fn add_email(&mut self, email: Cow<'LASMemoryMap, [u8]>) -> () {
         self.emails.push(email);
     }
+
+    pub fn add_metadata(
+        &mut self,
+        field: Cow<'LASMemoryMap, [u8]>,
+        value: Cow<'LASMemoryMap, [u8]>
+    ) {
+        self.metadata.push((field, value));
+    }
+
+    pub fn add_rule(
+        &mut self,
+        kind: Cow<'LASMemoryMap, [u8]>,
+        pattern: Cow<'LASMemoryMap, [u8]>
+    ) {
+        self.rules.push((kind, pattern));
+    }
+
+    /// Value of a metadata field, None if not set
+    pub fn get_metadata(&self, field: &str) -> Option<&[u8]> {
+        self.metadata
+            .iter()
+            .find(|(f, _)| f.as_ref() == field.as_bytes())
+            .map(|(_, v)| v.as_ref())
+    }
+
+    pub fn author_meta(&self) -> AuthorMeta {
+        let get = |field: &str| {
+            self.get_metadata(field).map(|v| String::from_utf8_lossy(v).into_owned())
+        };
+        AuthorMeta {
+            url: get("url"),
+            role: get("role"),
+            organization: get("organization"),
+            fingerprint: get("fingerprint"),
+        }
+    }
+
+    /// Default username is always the first one stored
+    /// Change it via `sigrs update --set-default-username`
+    pub fn default_username(&self) -> Option<&[u8]> {
+        self.usernames.first().map(|u| u.as_ref())
+    }
+
+    /// Default email is always the first one stored
+    /// Change it via `sigrs update --set-default-email`
+    pub fn default_email(&self) -> Option<&[u8]> {
+        self.emails.first().map(|e| e.as_ref())
+    }
+}
+
+/// `"value"`, or `none` if there is no default
+fn display_default(value: Option<&[u8]>) -> String {
+    match value {
+        Some(v) => format!("\"{}\"", String::from_utf8_lossy(v)),
+        None => String::from("none"),
+    }
 }
 
 
@@ -50,25 +135,31 @@ pub struct LocalAuthorStoragePerf<'LASMemoryMap> {
     pub memmap: &'LASMemoryMap [u8],
     // Boxed Identity contains only references to Memmap, not re-allocating
     // each portion of Memmap on heap
-    pub identities: BTreeMap<&'LASMemoryMap [u8], Box<IdentityPerf<'LASMemoryMap>>>
+    pub identities: BTreeMap<Cow<'LASMemoryMap, [u8]>, Box<IdentityPerf<'LASMemoryMap>>>,
+    /// Keys of identities with `R:` rules, in file order since the first matching rule wins
+    pub rule_order: Vec<Cow<'LASMemoryMap, [u8]>>
 }
 
 impl<'LASMemoryMap> LocalAuthorStoragePerf<'LASMemoryMap> {
     pub fn new(memmap: &'LASMemoryMap [u8]) -> Self {
         Self {
             memmap,
-            identities: BTreeMap::new()
+            identities: BTreeMap::new(),
+            rule_order: Vec::new()
         }
     }
 
     pub fn add_identity(
         &mut self,
-        key: &'LASMemoryMap [u8], 
+        key: Cow<'LASMemoryMap, [u8]>,
         identity: &IdentityPerf<'LASMemoryMap>
     ) -> Result<(), String> {
-        if self.identities.contains_key(key) {
+        if self.identities.contains_key(&key) {
             Err(String::from("Key already exists"))
         } else {
+            if !identity.rules.is_empty() {
+                self.rule_order.push(key.clone());
+            }
             // Allocating IdentityPerf on heap via box, but allocation
             // only contains references to Memmap
             self.identities.insert(key, Box::new(identity.clone()));
@@ -76,35 +167,20 @@ impl<'LASMemoryMap> LocalAuthorStoragePerf<'LASMemoryMap> {
         }
     }
 
-    pub fn lookup_id(&self, key: &String) -> Option<(&&[u8], &Box<IdentityPerf>)> {
+    pub fn lookup_id(&self, key: &String) -> Option<(&Cow<[u8]>, &Box<IdentityPerf>)> {
         self.identities.get_key_value(key.as_bytes())
     }
 
-    pub fn print_any_match(&self, usernames: &Vec<String>, emails: &Vec<String>) {
-
-        let mut count = 0;
-
-        for (k, v) in &self.identities {
-            // if v.usernames contains any value in emails print
-            for uname in usernames.iter() {
-                if v.usernames.contains(&uname.as_bytes()) {
-                    self.pretty_print(k, v, true);
-                    count += 1;
-                }
-            }
-            // If v.emails contains any email in emails
-            // print. TODO fix dupes
-            for email in emails.iter() {
-                if v.emails.contains(&email.as_bytes()) {
-                    self.pretty_print(k, v, true);
-                    count += 1;
-                }
-            }
-        }
-
-        if count == 0 {
-            println!("No matching identities found");
-        }
+    /// Identities `is_match` accepts, in key order
+    pub fn find_matches<F>(&self, is_match: F) -> Vec<(&[u8], &IdentityPerf<'LASMemoryMap>)>
+    where
+        F: Fn(&[u8], &IdentityPerf) -> bool
+    {
+        self.identities
+            .iter()
+            .map(|(k, v)| (k.as_ref(), v.as_ref()))
+            .filter(|(k, v)| is_match(k, v))
+            .collect()
     }
 
     pub fn pretty_print_all(&self, verbose: bool) {
@@ -122,7 +198,7 @@ impl<'LASMemoryMap> LocalAuthorStoragePerf<'LASMemoryMap> {
         }
     }
 
-    pub fn pretty_print(&self, k: &[u8], v: &Box<IdentityPerf>, verbose: bool) {
+    pub fn pretty_print(&self, k: &[u8], v: &IdentityPerf, verbose: bool) {
         if verbose == true {
             // Print identity key
             println!("================================");
@@ -131,18 +207,27 @@ impl<'LASMemoryMap> LocalAuthorStoragePerf<'LASMemoryMap> {
 
             // Print usernames
             println!("Usernames:");
-            println!("  Default: \"{}\"", String::from_utf8_lossy(v.usernames[0]));
+            println!("  Default: {}", display_default(v.default_username()));
             // if v.usernames.len() > 1 {
             println!("  All:");
-            for uname in &v.usernames {
-                println!("    - \"{}\"", String::from_utf8_lossy(uname));
+            for (idx, uname) in v.usernames.iter().enumerate() {
+                let marker = if idx == 0 { " (default)" } else { "" };
+                println!("    - \"{}\"{}", String::from_utf8_lossy(uname), marker);
             }
 
             println!("\nEmails:");
-            println!("  Default: \"{}\"", String::from_utf8_lossy(v.emails[0]));
+            println!("  Default: {}", display_default(v.default_email()));
             println!("  All:");
-            for email in &v.emails {
-                println!("    - \"{}\"", String::from_utf8_lossy(email));
+            for (idx, email) in v.emails.iter().enumerate() {
+                let marker = if idx == 0 { " (default)" } else { "" };
+                println!("    - \"{}\"{}", String::from_utf8_lossy(email), marker);
+            }
+
+            if !v.metadata.is_empty() {
+                println!("\nMetadata:");
+                for (field, value) in &v.metadata {
+                    println!("  {}: \"{}\"", String::from_utf8_lossy(field), String::from_utf8_lossy(value));
+                }
             }
 
             println!("================================\n");
@@ -152,22 +237,34 @@ impl<'LASMemoryMap> LocalAuthorStoragePerf<'LASMemoryMap> {
 
             // Identity: some name
             // Usernames: | Default: "fluxdiv" | "name2" "name3"
-            let mut usernames = format!("| Default: \"{}\" | ", String::from_utf8_lossy(v.usernames[0]));
-            for uname in &v.usernames[1..] {
+            let mut usernames = format!("| Default: {} | ", display_default(v.default_username()));
+            for uname in v.usernames.iter().skip(1) {
                 usernames.push_str("\"");
                 usernames.push_str(&String::from_utf8_lossy(uname));
                 usernames.push_str("\"");
             }
             println!("Usernames: {}", usernames);
 
-            let mut emails = format!("| Default: \"{}\" | ", String::from_utf8_lossy(v.emails[0]));
-            for email in &v.emails[1..] {
+            let mut emails = format!("| Default: {} | ", display_default(v.default_email()));
+            for email in v.emails.iter().skip(1) {
                 emails.push_str("\"");
                 emails.push_str(&String::from_utf8_lossy(email));
                 emails.push_str("\"");
             }
             println!("Emails: {} \n", emails);
 
+            if !v.metadata.is_empty() {
+                let mut metadata = String::from("| ");
+                for (field, value) in &v.metadata {
+                    metadata.push_str(&format!(
+                        "{}: \"{}\" | ",
+                        String::from_utf8_lossy(field),
+                        String::from_utf8_lossy(value)
+                    ));
+                }
+                println!("Metadata: {}", metadata);
+            }
+
             println!();
         }
     }
@@ -0,0 +1,68 @@
+use clap::ArgMatches;
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use crate::utils::extract_config_path;
+use super::lock::lock_las_for_write;
+use super::version::{backup_path, las_header};
+
+// ========== RESET-CONFIG (archive config.sigrs && start over with an empty one)
+// sigrs reset-config
+// sigrs reset-config --config-path ./some/config.sigrs
+// ========== GENERATE-CONFIG (create an empty config.sigrs somewhere else)
+// sigrs generate-config --path ./some/config.sigrs
+// sigrs generate-config --path ./some/config.sigrs --register
+// == --register also saves the path in the sigrs_function binary, the same
+// == as `sigrs set-config-path --path=... --no-generate` (see distributor)
+
+/// Creates an empty template config at `path`, errors if something already exists there
+pub fn write_template(path: &Path) -> Result<(), String> {
+    let mut handle = File::options()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| format!("Problem creating {}: {:?}", path.display(), e))?;
+    handle.write_all(las_header().as_bytes())
+        .map_err(|e| format!("Problem writing {}: {:?}", path.display(), e))?;
+    handle.sync_all()
+        .map_err(|e| format!("Problem writing {}: {:?}", path.display(), e))
+}
+
+pub fn handle_reset_config(args: &ArgMatches) -> Result<(), String> {
+    let config_path = PathBuf::from(extract_config_path(args)?);
+    let _lock = lock_las_for_write(args, &config_path)?;
+
+    if config_path.exists() {
+        let now = UNIX_EPOCH.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+        let archive = backup_path(&config_path, &format!("reset-{}", now));
+        fs::rename(&config_path, &archive)
+            .map_err(|e| format!("Problem archiving {}: {:?}", config_path.display(), e))?;
+        println!("Previous config archived at {}", archive.display());
+    }
+
+    write_template(&config_path)?;
+    println!("Empty config created at {}", config_path.display());
+    Ok(())
+}
+
+pub fn handle_generate_config(args: &ArgMatches) -> Result<(), String> {
+    let path = args.get_one::<OsString>("path")
+        .map(PathBuf::from)
+        .ok_or_else(|| String::from("--path required"))?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Problem creating {}: {:?}", parent.display(), e))?;
+    }
+
+    write_template(&path)?;
+    println!("Empty template config created at: {}", path.display());
+
+    // --register is handled by the distributor once this returns, since
+    // sigrs_modifier can't rewrite this binary while it is running
+    Ok(())
+}
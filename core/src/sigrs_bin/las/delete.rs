@@ -1,9 +1,12 @@
 use clap::ArgMatches;
-use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom, Write},
-};
-use crate::utils::extract_config_path;
+use std::fs;
+use crate::utils::{extract_config_path, write_atomic};
+use super::format::parse_key;
+use super::history::record_change_or_warn;
+use super::layers::missing_identity_error;
+use super::lock::lock_las_for_write;
+use super::storage::read_store;
+use super::version::ensure_current_version;
 
 /// Delete an entire identity
 pub fn handle_delete(args: &ArgMatches) -> Result<(), String> {
@@ -12,20 +15,19 @@ pub fn handle_delete(args: &ArgMatches) -> Result<(), String> {
         .ok_or_else(|| String::from("--identity required"))?;
 
     let config_path = extract_config_path(args)?;
+    // held until the new file is written
+    let _lock = lock_las_for_write(args, config_path.as_ref())?;
+    ensure_current_version(config_path.as_ref())?;
 
-    // Get file handle, do not create if didn't exist - nothing ot update
-    let mut config_handle = File::options()
-        .read(true)
-        .write(true)
-        .open(&config_path)
+    // do not create if didn't exist - nothing ot update
+    let file_content = fs::read_to_string(&config_path)
         .map_err(|e| format!("Problem opening config file: {:?}", e))?;
+    // the line walk below relies on a well formed file
+    read_store(&file_content)
+        .map_err(|e| format!("{}\nRun `sigrs doctor` to repair config.sigrs first", e))?;
 
-    let mut file_content = String::new();
-    config_handle.read_to_string(&mut file_content).unwrap();
-    assert!(!file_content.is_empty());
-
-    // Find matching key, delete that line && following 2 lines
-    let mut line_iter = file_content.lines();
+    // Find matching key, delete that line && following 2 lines (plus optional `M:`/`R:`)
+    let mut line_iter = file_content.lines().peekable();
     let mut new_file_content = String::new();
     let mut found = false;
 
@@ -39,19 +41,27 @@ pub fn handle_delete(args: &ArgMatches) -> Result<(), String> {
 
         // If key line, check identity matches
         if line.starts_with("K:") {
-            // unwrappable unless file format wrong
-            let uname_line = line_iter.next().unwrap();
-            let email_line = line_iter.next().unwrap();
+            // checked above, an error rather than a panic if that ever changes
+            let missing = || format!("Identity line \"{}\" is missing its `U:`/`E:` lines", line);
+            let uname_line = line_iter.next().ok_or_else(missing)?;
+            let email_line = line_iter.next().ok_or_else(missing)?;
+            // optional
+            let meta_line = line_iter.next_if(|l| l.starts_with("M:"));
+            let rules_line = line_iter.next_if(|l| l.starts_with("R:"));
 
             // If identity doesn't match add identity & continue
-            let id_key = line.trim_matches(&['K', ':', '"']);
-            if id_key != identity {
+            let id_key = parse_key(line).unwrap_or_default();
+            if &id_key != identity {
                 new_file_content.push_str(line);
                 new_file_content.push('\n');
                 new_file_content.push_str(uname_line);
                 new_file_content.push('\n');
                 new_file_content.push_str(email_line);
                 new_file_content.push('\n');
+                for optional_line in [meta_line, rules_line].into_iter().flatten() {
+                    new_file_content.push_str(optional_line);
+                    new_file_content.push('\n');
+                }
                 continue;
             }
 
@@ -62,16 +72,18 @@ pub fn handle_delete(args: &ArgMatches) -> Result<(), String> {
     }
 
     if !found {
-        return Err(String::from("Identity does not exist"));
+        return Err(missing_identity_error(identity));
     }
 
     // Update file
-    config_handle.set_len(0)
-        .map_err(|e| format!("Cannot alter file size: {:?}", e))?;
-    config_handle.seek(SeekFrom::Start(0))
-        .map_err(|e| format!("Cannot seek start of file: {:?}", e))?;
-    config_handle.write_all(new_file_content.as_bytes())
-        .map_err(|e| format!("Cannot update file: {:?}", e))?;
+    write_atomic(config_path.as_ref(), new_file_content.as_bytes())
+        .map_err(|e| format!("Cannot update file: {}", e))?;
+    record_change_or_warn(
+        config_path.as_ref(),
+        file_content.as_bytes(),
+        new_file_content.as_bytes(),
+        &format!("delete \"{}\"", identity)
+    );
 
     Ok(())
 }
@@ -0,0 +1,468 @@
+use clap::ArgMatches;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use crate::memmap::parse_values;
+use crate::utils::{extract_config_path, get_config_path, write_atomic};
+use super::format::{identity_entry, split_values};
+use super::lock::{extract_lock_timeout, lock_las, LockKind};
+use super::rules::RuleKind;
+use super::version::{
+    LAS_HEADER_COMMENT,
+    LAS_VERSION,
+    LAS_VERSION_PREFIX,
+    backup_path,
+    check_version,
+    las_header,
+    migrate_content,
+    read_version,
+};
+
+// ========== DOCTOR (validate && optionally repair config.sigrs)
+// sigrs doctor
+// sigrs doctor --fix
+// Unlike `process_las` which stops at the first problem, doctor reads the whole
+// file leniently, reports everything it finds, && can rebuild a clean file from
+// whatever it could recover
+
+/// A single problem found in config.sigrs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// 1-based line number, None for whole-file problems
+    pub line: Option<usize>,
+    pub message: String,
+    /// Whether `--fix` repairs this problem
+    pub fixable: bool,
+}
+
+/// An identity recovered from config.sigrs
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredIdentity {
+    pub line: usize,
+    pub key: String,
+    pub usernames: Vec<String>,
+    pub emails: Vec<String>,
+    pub metadata: Vec<(String, String)>,
+    pub rules: Vec<(String, String)>,
+}
+
+/// Result of reading a config.sigrs leniently
+#[derive(Debug, Default)]
+pub struct Diagnosis {
+    pub diagnostics: Vec<Diagnostic>,
+    /// User comments, excluding the generated header
+    pub comments: Vec<String>,
+    /// Recovered identities, duplicates already merged
+    pub identities: Vec<RecoveredIdentity>,
+}
+
+impl Diagnosis {
+    fn report(&mut self, line: Option<usize>, fixable: bool, message: String) {
+        self.diagnostics.push(Diagnostic { line, message, fixable });
+    }
+
+    /// Rebuilds config.sigrs content from everything that was recovered
+    pub fn repaired_content(&self) -> String {
+        let mut content = las_header();
+        for comment in &self.comments {
+            content.push_str(comment);
+            content.push('\n');
+        }
+        for identity in &self.identities {
+            content.push_str(&identity_entry(
+                &identity.key,
+                &identity.usernames,
+                &identity.emails,
+                &identity.metadata,
+                &identity.rules
+            ));
+        }
+        content
+    }
+}
+
+/// Appends values not already present, keeping order
+fn merge_values<T: PartialEq>(into: &mut Vec<T>, from: Vec<T>) {
+    for value in from {
+        if !into.contains(&value) {
+            into.push(value);
+        }
+    }
+}
+
+/// Appends fields not already present, keeping order
+fn merge_metadata(into: &mut Vec<(String, String)>, from: Vec<(String, String)>) {
+    for (field, value) in from {
+        if !into.iter().any(|(f, _)| *f == field) {
+            into.push((field, value));
+        }
+    }
+}
+
+/// Values of a K:/U:/E:/M:/R: line. Reports strict parse errors, but still
+/// salvages whatever values can be read
+fn line_values(line: &str, line_no: usize, diagnosis: &mut Diagnosis) -> Vec<String> {
+    if let Err(e) = parse_values(line.as_bytes(), line_no) {
+        diagnosis.report(Some(line_no), true, e.reason.to_string());
+    }
+    split_values(line.get(2..).unwrap_or_default())
+}
+
+/// Identity currently being read && which of its lines have been seen
+struct Reading {
+    identity: RecoveredIdentity,
+    has_u: bool,
+    has_e: bool,
+    has_m: bool,
+    has_r: bool,
+}
+
+/// Reads `content` leniently, collecting every problem found
+/// `content` must already be on the current LAS version
+pub fn diagnose(content: &str) -> Diagnosis {
+    let mut diagnosis = Diagnosis::default();
+    let mut current: Option<Reading> = None;
+    let mut recovered: Vec<RecoveredIdentity> = Vec::new();
+
+    fn finish(
+        current: Option<Reading>,
+        recovered: &mut Vec<RecoveredIdentity>,
+        diagnosis: &mut Diagnosis
+    ) {
+        let Some(Reading { identity, has_u, has_e, .. }) = current else {
+            return;
+        };
+        for (seen, prefix) in [(has_u, 'U'), (has_e, 'E')] {
+            if !seen {
+                diagnosis.report(
+                    Some(identity.line),
+                    true,
+                    format!("identity \"{}\" is missing its `{}:` line", identity.key, prefix)
+                );
+            }
+        }
+        recovered.push(identity);
+    }
+
+    for (idx, line) in content.lines().enumerate() {
+        let line_no = idx + 1;
+
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('#') {
+            if line != LAS_HEADER_COMMENT && !line.starts_with(LAS_VERSION_PREFIX) {
+                diagnosis.comments.push(line.to_string());
+            }
+            continue;
+        }
+
+        let line_type = match line.get(0..2) {
+            Some("K:") => 'K',
+            Some("U:") => 'U',
+            Some("E:") => 'E',
+            Some("M:") => 'M',
+            Some("R:") => 'R',
+            _ => {
+                diagnosis.report(
+                    Some(line_no),
+                    true,
+                    format!("unrecognized line \"{}\" (removed by --fix)", line)
+                );
+                continue;
+            }
+        };
+
+        let values = line_values(line, line_no, &mut diagnosis);
+
+        match line_type {
+            'K' => {
+                finish(current.take(), &mut recovered, &mut diagnosis);
+                if values.len() != 1 {
+                    diagnosis.report(
+                        Some(line_no),
+                        true,
+                        format!("key line must contain exactly 1 value, found {}", values.len())
+                    );
+                }
+                let Some(key) = values.into_iter().next() else {
+                    // nothing to attach the following U:/E: lines to
+                    continue;
+                };
+                current = Some(Reading {
+                    identity: RecoveredIdentity {
+                        line: line_no,
+                        key,
+                        usernames: vec![],
+                        emails: vec![],
+                        metadata: vec![],
+                        rules: vec![],
+                    },
+                    has_u: false,
+                    has_e: false,
+                    has_m: false,
+                    has_r: false,
+                });
+            },
+            _ => {
+                let Some(Reading { identity, has_u, has_e, has_m, has_r }) = current.as_mut() else {
+                    diagnosis.report(
+                        Some(line_no),
+                        true,
+                        format!("`{}:` line without a `K:` line, values {:?} removed by --fix", line_type, values)
+                    );
+                    continue;
+                };
+                if line_type == 'R' {
+                    if *has_r {
+                        diagnosis.report(
+                            Some(line_no),
+                            true,
+                            format!("identity \"{}\" has more than one `R:` line", identity.key)
+                        );
+                    }
+                    if !values.len().is_multiple_of(2) {
+                        diagnosis.report(
+                            Some(line_no),
+                            true,
+                            format!(
+                                "rule line must contain kind/pattern pairs, found {} values. The last value {:?} is removed by --fix",
+                                values.len(), values.last().cloned().unwrap_or_default()
+                            )
+                        );
+                    }
+                    *has_r = true;
+                    let mut values = values.into_iter();
+                    let mut rules = Vec::new();
+                    while let (Some(kind), Some(pattern)) = (values.next(), values.next()) {
+                        if RuleKind::parse(&kind).is_none() {
+                            diagnosis.report(
+                                Some(line_no),
+                                true,
+                                format!("unknown rule kind \"{}\" on identity \"{}\", the rule is removed by --fix", kind, identity.key)
+                            );
+                            continue;
+                        }
+                        rules.push((kind, pattern));
+                    }
+                    merge_values(&mut identity.rules, rules);
+                    continue;
+                }
+                if *has_r {
+                    diagnosis.report(
+                        Some(line_no),
+                        true,
+                        format!("identity \"{}\" has its `{}:` line after its `R:` line", identity.key, line_type)
+                    );
+                }
+                if line_type == 'M' {
+                    if *has_m {
+                        diagnosis.report(
+                            Some(line_no),
+                            true,
+                            format!("identity \"{}\" has more than one `M:` line", identity.key)
+                        );
+                    }
+                    if !values.len().is_multiple_of(2) {
+                        diagnosis.report(
+                            Some(line_no),
+                            true,
+                            format!(
+                                "metadata line must contain field/value pairs, found {} values. The last value {:?} is removed by --fix",
+                                values.len(), values.last().cloned().unwrap_or_default()
+                            )
+                        );
+                    }
+                    *has_m = true;
+                    let mut values = values.into_iter();
+                    let mut pairs = Vec::new();
+                    while let (Some(field), Some(value)) = (values.next(), values.next()) {
+                        pairs.push((field, value));
+                    }
+                    merge_metadata(&mut identity.metadata, pairs);
+                    continue;
+                }
+                if *has_m {
+                    diagnosis.report(
+                        Some(line_no),
+                        true,
+                        format!("identity \"{}\" has its `{}:` line after its `M:` line", identity.key, line_type)
+                    );
+                }
+                if line_type == 'U' && *has_e && !*has_u {
+                    diagnosis.report(
+                        Some(line_no),
+                        true,
+                        format!("identity \"{}\" has its `U:` line after its `E:` line", identity.key)
+                    );
+                }
+                let (seen, list) = if line_type == 'U' {
+                    (has_u, &mut identity.usernames)
+                } else {
+                    (has_e, &mut identity.emails)
+                };
+                if *seen {
+                    diagnosis.report(
+                        Some(line_no),
+                        true,
+                        format!("identity \"{}\" has more than one `{}:` line", identity.key, line_type)
+                    );
+                }
+                *seen = true;
+                merge_values(list, values);
+            }
+        }
+    }
+    finish(current.take(), &mut recovered, &mut diagnosis);
+
+    // duplicate keys, merged into the first occurrence
+    let mut by_key: BTreeMap<String, usize> = BTreeMap::new();
+    for identity in recovered {
+        match by_key.get(&identity.key) {
+            Some(&first) => {
+                let existing = &mut diagnosis.identities[first];
+                let message = format!(
+                    "identity \"{}\" is defined more than once (lines {} and {}), merged by --fix",
+                    identity.key, existing.line, identity.line
+                );
+                merge_values(&mut existing.usernames, identity.usernames);
+                merge_values(&mut existing.emails, identity.emails);
+                merge_metadata(&mut existing.metadata, identity.metadata);
+                merge_values(&mut existing.rules, identity.rules);
+                diagnosis.report(Some(identity.line), true, message);
+            },
+            None => {
+                by_key.insert(identity.key.clone(), diagnosis.identities.len());
+                diagnosis.identities.push(identity);
+            }
+        }
+    }
+
+    // identities sigrs cannot sign or list with
+    let mut empty = Vec::new();
+    for identity in &diagnosis.identities {
+        if identity.usernames.is_empty() {
+            empty.push((identity.line, format!(
+                "identity \"{}\" has no usernames, add one with `sigrs update --identity \"{}\" --au <USERNAME>`",
+                identity.key, identity.key
+            )));
+        }
+        if identity.emails.is_empty() {
+            empty.push((identity.line, format!(
+                "identity \"{}\" has no emails, add one with `sigrs update --identity \"{}\" --ae <EMAIL>`",
+                identity.key, identity.key
+            )));
+        }
+    }
+    for (line, message) in empty {
+        diagnosis.report(Some(line), false, message);
+    }
+
+    diagnosis
+}
+
+/// Checks the config path trailer appended to the sigrs_function binary. Only a problem
+/// when `config_path` was found through it, if it came from `--config-path`, a profile
+/// or SIGRS_CONFIG_PATH (e.g. CI running an unmodified binary) it is only a warning
+fn check_trailer(diagnosis: &mut Diagnosis, config_path: &Path) {
+    let saved = get_config_path();
+    let through_trailer = saved.as_ref().is_ok_and(|path| Path::new(path) == config_path);
+    let problem = match saved {
+        Ok(path) if Path::new(&path).is_file() => return,
+        Ok(path) => format!(
+            "saved config path {:?} does not exist. Run `sigrs set-config-path --path=<PATH>`",
+            path
+        ),
+        Err(e) => format!(
+            "unable to read the saved config path from the sigrs_function binary ({}). Run `sigrs set-config-path --path=<PATH>`",
+            e
+        ),
+    };
+    if through_trailer {
+        diagnosis.report(None, false, problem);
+    } else {
+        println!("warning: {}", problem);
+    }
+}
+
+fn print_diagnostics(diagnostics: &[Diagnostic]) {
+    for d in diagnostics {
+        let fix = if d.fixable { "" } else { " [manual fix required]" };
+        match d.line {
+            Some(line) => println!("line {}: {}{}", line, d.message, fix),
+            None => println!("{}{}", d.message, fix),
+        }
+    }
+}
+
+pub fn handle_doctor(args: &ArgMatches) -> Result<(), String> {
+    let config_path = PathBuf::from(extract_config_path(args)?);
+    let fix = args.get_flag("fix");
+
+    // --fix rewrites the file, so must not race another writer
+    let kind = if fix { LockKind::Exclusive } else { LockKind::Shared };
+    let _lock = lock_las(&config_path, kind, extract_lock_timeout(args))?;
+
+    let mut diagnosis = Diagnosis::default();
+    check_trailer(&mut diagnosis, &config_path);
+
+    let raw = fs::read(&config_path)
+        .map_err(|e| format!("Problem reading {}: {:?}", config_path.display(), e))?;
+    let mut content = String::from_utf8(raw)
+        .map_err(|e| format!("{} is not valid UTF-8: {}", config_path.display(), e))?;
+
+    let version = read_version(content.as_bytes())?;
+    if version > LAS_VERSION {
+        // can't know what a newer schema looks like
+        check_version(version)?;
+    }
+    if version < LAS_VERSION {
+        diagnosis.report(None, true, format!(
+            "config.sigrs uses LAS version {}, current version is {} (line numbers below are after migrating)",
+            version, LAS_VERSION
+        ));
+        match migrate_content(&content, version) {
+            Ok(migrated) => content = migrated,
+            Err(e) => {
+                diagnosis.report(None, false, format!("unable to migrate: {}", e));
+                print_diagnostics(&diagnosis.diagnostics);
+                return Err(String::from("config.sigrs must be repaired manually"));
+            }
+        }
+    }
+
+    let file_diagnosis = diagnose(&content);
+    diagnosis.diagnostics.extend(file_diagnosis.diagnostics.iter().cloned());
+
+    if diagnosis.diagnostics.is_empty() {
+        println!("No problems found in {}", config_path.display());
+        return Ok(());
+    }
+
+    print_diagnostics(&diagnosis.diagnostics);
+    let fixable = diagnosis.diagnostics.iter().filter(|d| d.fixable).count();
+    let manual = diagnosis.diagnostics.len() - fixable;
+
+    if !fix {
+        return Err(format!(
+            "{} problem(s) found, {} can be repaired with `sigrs doctor --fix`",
+            diagnosis.diagnostics.len(), fixable
+        ));
+    }
+
+    if fixable > 0 {
+        let backup = backup_path(&config_path, "doctor");
+        fs::copy(&config_path, &backup)
+            .map_err(|e| format!("Problem backing up config: {:?}", e))?;
+        write_atomic(&config_path, file_diagnosis.repaired_content().as_bytes())
+            .map_err(|e| format!("Problem writing repaired config: {}", e))?;
+        println!("Repaired {} problem(s), backup saved at {}", fixable, backup.display());
+    }
+
+    if manual > 0 {
+        return Err(format!("{} problem(s) must be fixed manually", manual));
+    }
+    Ok(())
+}
@@ -0,0 +1,345 @@
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+use crate::memmap::get_memmap;
+use crate::utils::write_atomic;
+use super::rules::RuleKind;
+use super::storage::{read_store, rewrite_store, sort_metadata, StoredIdentity};
+use super::validate::{prepare_value, ValueKind};
+
+// ========== EXPORT (write every identity as JSON or TOML)
+// sigrs export
+// sigrs export --format toml --output ./identities.toml
+// ========== IMPORT (add identities from an export)
+// sigrs import ./identities.toml
+// == by default nothing is written if any key already exists, the conflicts are listed
+// sigrs import ./identities.json --merge
+// == conflicting identities gain the imported usernames/emails, existing defaults are kept
+// sigrs import ./identities.json --replace
+// == config.sigrs ends up with exactly the identities in the file
+// sigrs import ./identities.json --no-validate
+// == usernames/emails are stored as in the file, without the checks add-new applies
+//
+// {
+//   "version": 1,
+//   "identities": [
+//     { "key": "work", "usernames": ["bob"], "emails": ["bob@x.com"], "metadata": { "role": "Lead" } }
+//   ]
+// }
+
+/// Version of the export schema, independent of the LAS version
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LasExport {
+    pub version: u32,
+    #[serde(default)]
+    pub identities: Vec<ExportedIdentity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedIdentity {
+    pub key: String,
+    #[serde(default)]
+    pub usernames: Vec<String>,
+    #[serde(default)]
+    pub emails: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// In order, see `rules.rs`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<ExportedRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedRule {
+    pub kind: String,
+    pub pattern: String,
+}
+
+impl From<StoredIdentity> for ExportedIdentity {
+    fn from(identity: StoredIdentity) -> Self {
+        Self {
+            key: identity.key,
+            usernames: identity.usernames,
+            emails: identity.emails,
+            metadata: identity.metadata.into_iter().collect(),
+            rules: identity.rules.into_iter()
+                .map(|(kind, pattern)| ExportedRule { kind, pattern })
+                .collect(),
+        }
+    }
+}
+
+impl From<ExportedIdentity> for StoredIdentity {
+    fn from(identity: ExportedIdentity) -> Self {
+        let mut metadata: Vec<(String, String)> = identity.metadata.into_iter().collect();
+        sort_metadata(&mut metadata);
+        Self {
+            key: identity.key,
+            usernames: identity.usernames,
+            emails: identity.emails,
+            metadata,
+            rules: identity.rules.into_iter().map(|rule| (rule.kind, rule.pattern)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Toml,
+}
+
+impl ExportFormat {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            other => Err(format!("Unknown format \"{}\", expected json or toml", other)),
+        }
+    }
+
+    /// `--format` if passed, otherwise from the extension of `path`
+    fn resolve(args: &ArgMatches, path: Option<&Path>) -> Result<Self, String> {
+        if let Some(name) = args.get_one::<String>("format") {
+            return Self::from_name(name);
+        }
+        match path.and_then(|p| p.extension()).and_then(|e| e.to_str()) {
+            Some(ext) => Self::from_name(&ext.to_ascii_lowercase())
+                .map_err(|_| format!("Cannot tell the format from \".{}\", pass `--format json|toml`", ext)),
+            None => Err(String::from("Cannot tell the format from the file name, pass `--format json|toml`")),
+        }
+    }
+}
+
+pub fn serialize_export(export: &LasExport, format: ExportFormat) -> Result<String, String> {
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(export)
+            .map(|mut s| { s.push('\n'); s })
+            .map_err(|e| format!("Problem writing JSON: {}", e)),
+        ExportFormat::Toml => toml::to_string_pretty(export)
+            .map_err(|e| format!("Problem writing TOML: {}", e)),
+    }
+}
+
+pub fn deserialize_export(content: &str, format: ExportFormat) -> Result<LasExport, String> {
+    let export: LasExport = match format {
+        ExportFormat::Json => serde_json::from_str(content)
+            .map_err(|e| format!("Problem parsing JSON: {}", e))?,
+        ExportFormat::Toml => toml::from_str(content)
+            .map_err(|e| format!("Problem parsing TOML: {}", e))?,
+    };
+    if export.version > EXPORT_VERSION {
+        return Err(format!(
+            "Export version {} is newer than this sigrs supports ({}). Update sigrs to import it",
+            export.version, EXPORT_VERSION
+        ));
+    }
+
+    let mut seen = BTreeSet::new();
+    for identity in &export.identities {
+        if identity.key.is_empty() {
+            return Err(String::from("Identity keys cannot be empty"));
+        }
+        if !seen.insert(identity.key.as_str()) {
+            return Err(format!("Identity \"{}\" is defined more than once", identity.key));
+        }
+        // doctor flags these && sign can't use them
+        if identity.usernames.is_empty() || identity.emails.is_empty() {
+            return Err(format!("Identity \"{}\" needs at least one username and one email", identity.key));
+        }
+    }
+    Ok(export)
+}
+
+// See `memmap.rs` for explanations for why this function is unsafe
+pub unsafe fn handle_export(args: &ArgMatches) -> Result<(), String> {
+    let output = args.get_one::<OsString>("output").map(PathBuf::from);
+    let format = if output.is_some() || args.contains_id("format") {
+        ExportFormat::resolve(args, output.as_deref())?
+    } else {
+        ExportFormat::Json
+    };
+
+    let content = {
+        // Handles --config-path, shared lock until dropped
+        let memmap_las = get_memmap(args)?;
+        let content = std::str::from_utf8(&memmap_las)
+            .map_err(|e| format!("config.sigrs is not valid UTF-8: {}", e))?;
+        let (_comments, identities) = read_store(content)?;
+        let export = LasExport {
+            version: EXPORT_VERSION,
+            identities: identities.into_iter().map(ExportedIdentity::from).collect(),
+        };
+        serialize_export(&export, format)?
+    };
+
+    match output {
+        Some(path) => {
+            write_atomic(&path, content.as_bytes())?;
+            println!("Exported to {}", path.display());
+        },
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+/// Appends values not already present, keeping order
+fn merge_values<T: PartialEq>(into: &mut Vec<T>, from: Vec<T>) {
+    for value in from {
+        if !into.contains(&value) {
+            into.push(value);
+        }
+    }
+}
+
+/// Adds the usernames/emails of `from` that `into` lacks after its own, in order.
+/// Metadata already on `into` wins, `from` fills the gaps. Rules `into` lacks are
+/// added after its own. Keys are left alone
+pub fn merge_identity(into: &mut StoredIdentity, from: StoredIdentity) {
+    merge_values(&mut into.usernames, from.usernames);
+    merge_values(&mut into.emails, from.emails);
+    merge_values(&mut into.rules, from.rules);
+    for (field, value) in from.metadata {
+        if !into.metadata.iter().any(|(f, _)| *f == field) {
+            into.metadata.push((field, value));
+        }
+    }
+    sort_metadata(&mut into.metadata);
+}
+
+/// What importing `imported` into `existing` did
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub added: Vec<String>,
+    /// Keys that already existed
+    pub conflicts: Vec<String>,
+    /// Existing keys removed by --replace that were not in the import
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    /// Fail on any conflict
+    New,
+    Merge,
+    Replace,
+}
+
+/// Combines `existing` && `imported`, see `ImportMode`
+pub fn import_identities(
+    existing: Vec<StoredIdentity>,
+    imported: Vec<StoredIdentity>,
+    mode: ImportMode
+) -> Result<(Vec<StoredIdentity>, ImportReport), String> {
+    let mut report = ImportReport::default();
+
+    if mode == ImportMode::Replace {
+        let imported_keys: BTreeSet<&str> = imported.iter().map(|i| i.key.as_str()).collect();
+        for identity in &existing {
+            if imported_keys.contains(identity.key.as_str()) {
+                report.conflicts.push(identity.key.clone());
+            } else {
+                report.removed.push(identity.key.clone());
+            }
+        }
+        let existing_keys: BTreeSet<&str> = existing.iter().map(|i| i.key.as_str()).collect();
+        report.added = imported.iter()
+            .filter(|i| !existing_keys.contains(i.key.as_str()))
+            .map(|i| i.key.clone())
+            .collect();
+        return Ok((imported, report));
+    }
+
+    let mut result = existing;
+    for identity in imported {
+        match result.iter_mut().find(|e| e.key == identity.key) {
+            None => {
+                report.added.push(identity.key.clone());
+                result.push(identity);
+            },
+            Some(current) => {
+                report.conflicts.push(identity.key.clone());
+                if mode == ImportMode::Merge {
+                    merge_identity(current, identity);
+                }
+            }
+        }
+    }
+
+    if mode == ImportMode::New && !report.conflicts.is_empty() {
+        return Err(format!(
+            "Nothing imported, these identities already exist: {}\nUse `--merge` to combine them, or `--replace` to overwrite config.sigrs with the file",
+            report.conflicts.iter().map(|k| format!("\"{}\"", k)).collect::<Vec<_>>().join(", ")
+        ));
+    }
+    Ok((result, report))
+}
+
+pub fn handle_import(args: &ArgMatches) -> Result<(), String> {
+    let file = args.get_one::<OsString>("file")
+        .map(PathBuf::from)
+        .ok_or_else(|| String::from("File to import required"))?;
+    let format = ExportFormat::resolve(args, Some(&file))?;
+    let mode = if args.get_flag("merge") {
+        ImportMode::Merge
+    } else if args.get_flag("replace") {
+        ImportMode::Replace
+    } else {
+        ImportMode::New
+    };
+
+    let import_content = fs::read_to_string(&file)
+        .map_err(|e| format!("Problem reading {}: {:?}", file.display(), e))?;
+    let export = deserialize_export(&import_content, format)?;
+
+    let mut imported: Vec<StoredIdentity> = export.identities.into_iter().map(StoredIdentity::from).collect();
+    // would otherwise be written && make config.sigrs unreadable
+    for identity in &mut imported {
+        if let Some((kind, _)) = identity.rules.iter().find(|(kind, _)| RuleKind::parse(kind).is_none()) {
+            return Err(format!("Identity \"{}\" has an unknown rule kind \"{}\", expected `path` or `remote`", identity.key, kind));
+        }
+        // same checks as add-new unless --no-validate
+        let key = identity.key.clone();
+        let in_identity = |e: String| format!("Identity \"{}\": {}", key, e);
+        identity.usernames = identity.usernames.iter()
+            .map(|u| prepare_value(args, ValueKind::Username, u))
+            .collect::<Result<Vec<String>, String>>()
+            .map_err(in_identity)?;
+        identity.emails = identity.emails.iter()
+            .map(|e| prepare_value(args, ValueKind::Email, e))
+            .collect::<Result<Vec<String>, String>>()
+            .map_err(in_identity)?;
+    }
+    let mut report = ImportReport::default();
+    rewrite_store(args, &format!("import {}", file.display()), |existing| {
+        let (identities, import_report) = import_identities(existing, imported, mode)?;
+        report = import_report;
+        Ok(identities)
+    })?;
+
+    let conflict_action = match mode {
+        ImportMode::Replace => "replaced",
+        _ => "merged",
+    };
+    for key in &report.added {
+        println!("added    \"{}\"", key);
+    }
+    for key in &report.conflicts {
+        println!("{}   \"{}\" (already existed)", conflict_action, key);
+    }
+    for key in &report.removed {
+        println!("removed  \"{}\" (not in {})", key, file.display());
+    }
+    println!(
+        "Imported {} new, {} {}, {} removed",
+        report.added.len(), report.conflicts.len(), conflict_action, report.removed.len()
+    );
+    Ok(())
+}
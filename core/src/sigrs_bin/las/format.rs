@@ -0,0 +1,181 @@
+// ============================= config.sigrs (LAS) line format
+// Every identity is stored as 3 lines, plus an optional metadata line:
+//
+// K:"some key"
+// U:"username 1""username 2"
+// E:"email 1""email 2"
+// M:"url""https://x.com""role""Maintainer"
+//
+// `M:` values alternate field, value. Known fields are listed in
+// `identity::METADATA_FIELDS`
+//
+// Each value is wrapped in double quotes. Characters that would break the
+// format are escaped with a backslash inside the quotes:
+//
+//   `\`       ->  `\\`
+//   `"`       ->  `\"`
+//   newline   ->  `\n`
+//   CR        ->  `\r`
+//
+// Writers must go through `escape_value` / `quote_value`, and readers through
+// `find_value_end` + `unescape_value` (memmap) or `split_values` (owned)
+use std::borrow::Cow;
+
+pub const BACKSLASH_BITS: u8 = 92u8;
+pub const QUOTE_BITS: u8 = 34u8;
+
+/// Escapes a single value so it can be written between double quotes
+pub fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes `value` && wraps it in double quotes
+pub fn quote_value(value: &str) -> String {
+    format!("\"{}\"", escape_value(value))
+}
+
+/// Reverses `escape_value` on the raw bytes between a pair of quotes.
+/// Borrows `raw` if it contains no escapes, so memmapped values stay zero-copy
+pub fn unescape_value(raw: &[u8]) -> Cow<'_, [u8]> {
+    if !raw.contains(&BACKSLASH_BITS) {
+        return Cow::Borrowed(raw);
+    }
+
+    let mut unescaped = Vec::with_capacity(raw.len());
+    let mut bytes = raw.iter();
+    while let Some(&b) = bytes.next() {
+        if b != BACKSLASH_BITS {
+            unescaped.push(b);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'n') => unescaped.push(b'\n'),
+            Some(b'r') => unescaped.push(b'\r'),
+            // `\\` && `\"`
+            Some(&other) => unescaped.push(other),
+            // trailing lone backslash, keep it
+            None => unescaped.push(BACKSLASH_BITS),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Given `line[start]` is the first byte after an opening quote, returns
+/// the index of the matching (unescaped) closing quote
+pub fn find_value_end(line: &[u8], start: usize) -> Option<usize> {
+    let mut cursor = start;
+    while cursor < line.len() {
+        match line[cursor] {
+            BACKSLASH_BITS => cursor += 2,
+            QUOTE_BITS => return Some(cursor),
+            _ => cursor += 1,
+        }
+    }
+    None
+}
+
+/// Splits the body of a U:/E: line (everything after the prefix) into its
+/// unescaped values
+/// `"a""b\"c"` => ["a", "b\"c"]
+pub fn split_values(body: &str) -> Vec<String> {
+    let bytes = body.as_bytes();
+    let mut values = Vec::new();
+    let mut cursor = 0;
+
+    while cursor < bytes.len() {
+        // move to opening quote
+        let Some(open) = bytes[cursor..].iter().position(|&b| b == QUOTE_BITS) else {
+            break;
+        };
+        let start = cursor + open + 1;
+        let Some(end) = find_value_end(bytes, start) else {
+            break;
+        };
+        let value = unescape_value(&bytes[start..end]);
+        values.push(String::from_utf8_lossy(&value).into_owned());
+        // skip closing quote
+        cursor = end + 1;
+    }
+    values
+}
+
+/// Returns the unescaped key of a `K:"key"` line, or None if `line` is not a key line
+pub fn parse_key(line: &str) -> Option<String> {
+    let body = line.strip_prefix("K:")?;
+    split_values(body).into_iter().next()
+}
+
+/// `K:"key"`
+pub fn key_line(key: &str) -> String {
+    format!("K:{}", quote_value(key))
+}
+
+/// `U:"a""b"` or `E:"a""b"` depending on `prefix`
+pub fn values_line<S: AsRef<str>>(prefix: char, values: &[S]) -> String {
+    let mut line = format!("{}:", prefix);
+    for value in values {
+        line.push_str(&quote_value(value.as_ref()));
+    }
+    line
+}
+
+/// `M:"field""value"...`, or None if there is no metadata to write
+pub fn metadata_line(metadata: &[(String, String)]) -> Option<String> {
+    if metadata.is_empty() {
+        return None;
+    }
+    let flat: Vec<&str> = metadata
+        .iter()
+        .flat_map(|(field, value)| [field.as_str(), value.as_str()])
+        .collect();
+    Some(values_line('M', &flat))
+}
+
+/// `R:"kind""pattern"...`, or None if there are no rules to write
+pub fn rules_line(rules: &[(String, String)]) -> Option<String> {
+    if rules.is_empty() {
+        return None;
+    }
+    let flat: Vec<&str> = rules
+        .iter()
+        .flat_map(|(kind, pattern)| [kind.as_str(), pattern.as_str()])
+        .collect();
+    Some(values_line('R', &flat))
+}
+
+/// All lines for an identity, including trailing newline
+/// The `M:` && `R:` lines are only written if there is metadata/rules
+pub fn identity_entry<S: AsRef<str>>(
+    key: &str,
+    usernames: &[S],
+    emails: &[S],
+    metadata: &[(String, String)],
+    rules: &[(String, String)]
+) -> String {
+    let mut entry = String::new();
+    entry.push_str(&key_line(key));
+    entry.push('\n');
+    entry.push_str(&values_line('U', usernames));
+    entry.push('\n');
+    entry.push_str(&values_line('E', emails));
+    entry.push('\n');
+    if let Some(line) = metadata_line(metadata) {
+        entry.push_str(&line);
+        entry.push('\n');
+    }
+    if let Some(line) = rules_line(rules) {
+        entry.push_str(&line);
+        entry.push('\n');
+    }
+    entry
+}
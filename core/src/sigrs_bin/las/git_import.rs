@@ -0,0 +1,473 @@
+use clap::ArgMatches;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+use super::storage::{add_identities, read_store, read_store_unlocked, unique_key, StoredIdentity};
+
+// ========== IMPORT-GIT (propose identities from user.name/user.email in git config files)
+// sigrs import-git
+// == reads ~/.config/git/config, ~/.gitconfig && the .git/config of the repo containing
+// == the working dir, following [include] && [includeIf] paths. Only files are read,
+// == git itself is not needed
+// sigrs import-git --dry-run
+// == list what would be added without writing
+// sigrs import-git --repo ./some/repo --repo ./other/repo --file ./.gitconfig-work
+// sigrs import-git --no-global --repo ./some/repo
+//
+// Each scope (global, every repo, every --file) gives the name/email pair that
+// git would use there. An [includeIf] fragment gives its own pair, since it only
+// applies to some repos. Repos && --file inherit the global values they don't set,
+// the same as git. Every distinct, complete pair becomes one identity, unless an
+// identity already holds both values
+
+/// git stops following includes at this depth
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// user.name / user.email seen so far
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GitUser {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// A complete name/email pair, && the file it was found in
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitCandidate {
+    pub name: String,
+    pub email: String,
+    pub source: PathBuf,
+}
+
+/// One `key = value` from a git config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitConfigEntry {
+    /// lowercase, as section names are case insensitive
+    pub section: String,
+    /// case sensitive
+    pub subsection: Option<String>,
+    /// lowercase, as key names are case insensitive
+    pub key: String,
+    /// None for `key` without `=`, a boolean true
+    pub value: Option<String>,
+}
+
+// ============================= Parsing
+// https://git-scm.com/docs/git-config#_syntax
+
+/// Parses the value after `=`, pulling continuation lines (trailing `\`) from `lines`
+fn parse_value<'a>(
+    first: &'a str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>
+) -> Result<String, String> {
+    let mut value = String::new();
+    // whitespace is only kept if something follows it
+    let mut pending_ws = String::new();
+    let mut in_quotes = false;
+    let mut text = first;
+
+    loop {
+        let mut chars = text.chars();
+        let mut continued = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    value.push_str(&pending_ws);
+                    pending_ws.clear();
+                    in_quotes = !in_quotes;
+                },
+                '\\' => {
+                    let escaped = match chars.next() {
+                        None => {
+                            continued = true;
+                            break;
+                        },
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some(c @ ('"' | '\\')) => c,
+                        Some(c) => return Err(format!("invalid escape `\\{}`", c)),
+                    };
+                    value.push_str(&pending_ws);
+                    pending_ws.clear();
+                    value.push(escaped);
+                },
+                '#' | ';' if !in_quotes => break,
+                c if c.is_whitespace() && !in_quotes => pending_ws.push(c),
+                c => {
+                    value.push_str(&pending_ws);
+                    pending_ws.clear();
+                    value.push(c);
+                },
+            }
+        }
+
+        if !continued {
+            break;
+        }
+        text = match lines.next() {
+            Some((_, next)) => next,
+            None => return Err(String::from("value continues past the end of the file")),
+        };
+    }
+
+    if in_quotes {
+        return Err(String::from("missing closing quote"));
+    }
+    Ok(value)
+}
+
+/// Parses `name "subsection"`, `name.subsection` or `name` from inside `[...]`
+fn parse_section(header: &str) -> Result<(String, Option<String>), String> {
+    let header = header.trim();
+    let Some((name, rest)) = header.split_once(char::is_whitespace) else {
+        // deprecated [section.subsection] form
+        return Ok(match header.split_once('.') {
+            Some((name, sub)) => (name.to_ascii_lowercase(), Some(sub.to_ascii_lowercase())),
+            None => (header.to_ascii_lowercase(), None),
+        });
+    };
+
+    let rest = rest.trim();
+    let quoted = rest.strip_prefix('"')
+        .and_then(|r| r.strip_suffix('"'))
+        .ok_or_else(|| format!("invalid section header `[{}]`", header))?;
+    let mut subsection = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        // only \" and \\ mean anything, other backslashes are dropped
+        subsection.push(if c == '\\' { chars.next().unwrap_or('\\') } else { c });
+    }
+    Ok((name.to_ascii_lowercase(), Some(subsection)))
+}
+
+/// Every `key = value` in a git config file, in order
+pub fn parse_git_config(content: &str) -> Result<Vec<GitConfigEntry>, String> {
+    let mut entries = Vec::new();
+    let mut section: Option<(String, Option<String>)> = None;
+    let mut lines = content.lines().enumerate();
+
+    while let Some((i, line)) = lines.next() {
+        let err = |e: String| format!("line {}: {}", i + 1, e);
+        let mut line = line.trim_start();
+
+        if let Some(header) = line.strip_prefix('[') {
+            let end = header.find(']')
+                .ok_or_else(|| err(String::from("section header is missing `]`")))?;
+            section = Some(parse_section(&header[..end]).map_err(err)?);
+            // a variable may follow on the same line
+            line = header[end + 1..].trim_start();
+        }
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        let Some((section, subsection)) = &section else {
+            return Err(err(String::from("variable outside of a section")));
+        };
+        let key_end = line
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .unwrap_or(line.len());
+        let key = &line[..key_end];
+        if !key.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Err(err(format!("invalid variable name `{}`", line)));
+        }
+
+        let rest = line[key_end..].trim_start();
+        let value = if let Some(raw) = rest.strip_prefix('=') {
+            Some(parse_value(raw.trim_start(), &mut lines).map_err(err)?)
+        } else if rest.is_empty() || rest.starts_with('#') || rest.starts_with(';') {
+            None
+        } else {
+            return Err(err(format!("expected `=` after `{}`", key)));
+        };
+
+        entries.push(GitConfigEntry {
+            section: section.clone(),
+            subsection: subsection.clone(),
+            key: key.to_ascii_lowercase(),
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+// ============================= Collecting identities
+
+/// Resolves an include path the way git does: `~/` is the home dir,
+/// relative paths are relative to the including file
+fn resolve_include(path: &str, including: &Path) -> Option<PathBuf> {
+    if let Some(rest) = path.strip_prefix("~/") {
+        return dirs::home_dir().map(|home| home.join(rest));
+    }
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return Some(path.to_path_buf());
+    }
+    Some(including.parent().unwrap_or(Path::new(".")).join(path))
+}
+
+/// Walks config files && their includes, collecting candidates
+#[derive(Debug, Default)]
+pub struct GitCollector {
+    pub candidates: Vec<GitCandidate>,
+    /// Files that could not be read or parsed, the rest are still used
+    pub warnings: Vec<String>,
+    /// Files currently being read, to stop include cycles
+    stack: Vec<PathBuf>,
+}
+
+impl GitCollector {
+    fn push_candidate(&mut self, user: &GitUser, source: &Path) {
+        let (Some(name), Some(email)) = (&user.name, &user.email) else {
+            return;
+        };
+        if name.is_empty() || email.is_empty() {
+            return;
+        }
+        if !self.candidates.iter().any(|c| &c.name == name && &c.email == email) {
+            self.candidates.push(GitCandidate {
+                name: name.clone(),
+                email: email.clone(),
+                source: source.to_path_buf(),
+            });
+        }
+    }
+
+    /// Applies `path` on top of `user`, as if it was part of the including file.
+    /// Missing files are skipped, as git does
+    fn read_file(&mut self, path: &Path, user: &mut GitUser) {
+        if self.stack.len() >= MAX_INCLUDE_DEPTH || self.stack.iter().any(|p| p == path) {
+            self.warnings.push(format!("{}: include cycle or too deeply nested, skipped", path.display()));
+            return;
+        }
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                self.warnings.push(format!("{}: {:?}", path.display(), e));
+                return;
+            }
+        };
+        let entries = match parse_git_config(&content) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.warnings.push(format!("{}: {}", path.display(), e));
+                return;
+            }
+        };
+
+        self.stack.push(path.to_path_buf());
+        for entry in entries {
+            match (entry.section.as_str(), entry.key.as_str(), entry.value) {
+                ("user", "name", Some(value)) => user.name = Some(value),
+                ("user", "email", Some(value)) => user.email = Some(value),
+                ("include", "path", Some(include)) => {
+                    if let Some(include) = resolve_include(&include, path) {
+                        self.read_file(&include, user);
+                    }
+                },
+                // conditions are not checked, every fragment is a possible identity
+                ("includeif", "path", Some(include)) => {
+                    if let Some(include) = resolve_include(&include, path) {
+                        let mut conditional = user.clone();
+                        self.read_file(&include, &mut conditional);
+                        if conditional != *user {
+                            self.push_candidate(&conditional, &include);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        self.stack.pop();
+    }
+
+    /// Reads `files` in order as one scope starting from `inherited`,
+    /// && records the pair git would use in that scope
+    pub fn read_scope(&mut self, files: &[PathBuf], inherited: &GitUser) -> GitUser {
+        let mut user = inherited.clone();
+        for file in files {
+            self.read_file(file, &mut user);
+        }
+        if let Some(source) = files.iter().rev().find(|f| f.exists()) {
+            self.push_candidate(&user, source);
+        }
+        user
+    }
+}
+
+/// `$XDG_CONFIG_HOME/git/config` (or `~/.config/git/config`) then `~/.gitconfig`
+fn global_config_files() -> Vec<PathBuf> {
+    let home = dirs::home_dir();
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".config")));
+
+    let mut files = Vec::new();
+    if let Some(xdg) = xdg {
+        files.push(xdg.join("git").join("config"));
+    }
+    if let Some(home) = home {
+        files.push(home.join(".gitconfig"));
+    }
+    files
+}
+
+/// The config file of the repo at `dir`, following `.git` files (worktrees, submodules)
+pub fn repo_config_file(dir: &Path) -> Result<PathBuf, String> {
+    let dot_git = dir.join(".git");
+    let git_dir = if dot_git.is_file() {
+        let content = fs::read_to_string(&dot_git)
+            .map_err(|e| format!("Problem reading {}: {:?}", dot_git.display(), e))?;
+        let target = content.trim().strip_prefix("gitdir:")
+            .ok_or_else(|| format!("{} does not point to a git dir", dot_git.display()))?;
+        dir.join(target.trim())
+    } else if dot_git.is_dir() {
+        dot_git
+    } else {
+        return Err(format!("{} is not a git repository", dir.display()));
+    };
+
+    // worktrees keep config in the main repo's git dir
+    let git_dir = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => git_dir.join(common.trim()),
+        Err(_) => git_dir,
+    };
+    Ok(git_dir.join("config"))
+}
+
+/// The repo containing the working dir, if any
+fn current_repo() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors().find(|dir| dir.join(".git").exists()).map(Path::to_path_buf)
+}
+
+/// What `sigrs import-git` would do with each candidate
+#[derive(Debug, Clone, PartialEq)]
+pub enum GitProposal {
+    Add(StoredIdentity, PathBuf),
+    /// Key of the identity already holding both values
+    AlreadyStored(GitCandidate, String),
+}
+
+/// New identities for the candidates not already stored in `existing`
+pub fn propose_identities(candidates: &[GitCandidate], existing: &[StoredIdentity]) -> Vec<GitProposal> {
+    let mut taken: Vec<String> = existing.iter().map(|i| i.key.clone()).collect();
+    let mut proposals = Vec::new();
+
+    for candidate in candidates {
+        let stored = existing.iter().find(|i| {
+            i.usernames.contains(&candidate.name) && i.emails.contains(&candidate.email)
+        });
+        if let Some(stored) = stored {
+            proposals.push(GitProposal::AlreadyStored(candidate.clone(), stored.key.clone()));
+            continue;
+        }
+
+        let key = unique_key(&candidate.name, &candidate.email, &taken);
+        taken.push(key.clone());
+        proposals.push(GitProposal::Add(
+            StoredIdentity {
+                key,
+                usernames: vec![candidate.name.clone()],
+                emails: vec![candidate.email.clone()],
+                metadata: vec![],
+                rules: vec![],
+            },
+            candidate.source.clone(),
+        ));
+    }
+    proposals
+}
+
+/// Candidates from the scopes selected by `args`
+pub fn collect_candidates(args: &ArgMatches) -> Result<GitCollector, String> {
+    let mut collector = GitCollector::default();
+    let global = if args.get_flag("no-global") {
+        GitUser::default()
+    } else {
+        collector.read_scope(&global_config_files(), &GitUser::default())
+    };
+
+    let mut repos: Vec<PathBuf> = args.get_many::<OsString>("repo")
+        .map(|repos| repos.map(PathBuf::from).collect())
+        .unwrap_or_default();
+    let files: Vec<PathBuf> = args.get_many::<OsString>("file")
+        .map(|files| files.map(PathBuf::from).collect())
+        .unwrap_or_default();
+    // only look around the working dir if nothing was picked explicitly
+    if repos.is_empty() && files.is_empty() {
+        repos.extend(current_repo());
+    }
+
+    for repo in repos {
+        let config = repo_config_file(&repo)?;
+        collector.read_scope(&[config], &global);
+    }
+    for file in files {
+        if !file.is_file() {
+            return Err(format!("{} does not exist", file.display()));
+        }
+        collector.read_scope(&[file], &global);
+    }
+    Ok(collector)
+}
+
+/// Prints what happens to each candidate && returns the identities to add
+fn report_proposals(candidates: &[GitCandidate], existing: &[StoredIdentity], dry_run: bool) -> Vec<StoredIdentity> {
+    let mut identities = Vec::new();
+    for proposal in propose_identities(candidates, existing) {
+        match proposal {
+            GitProposal::Add(identity, source) => {
+                println!(
+                    "{} \"{}\": {} <{}> (from {})",
+                    if dry_run { "would add" } else { "add" },
+                    identity.key, identity.usernames[0], identity.emails[0], source.display()
+                );
+                identities.push(identity);
+            },
+            GitProposal::AlreadyStored(candidate, key) => {
+                println!("skip {} <{}>, already stored in \"{}\"", candidate.name, candidate.email, key);
+            },
+        }
+    }
+    identities
+}
+
+pub fn handle_import_git(args: &ArgMatches) -> Result<(), String> {
+    let collector = collect_candidates(args)?;
+    for warning in &collector.warnings {
+        eprintln!("Warning: skipped {}", warning);
+    }
+    if collector.candidates.is_empty() {
+        println!("No user.name/user.email pairs found in git config files");
+        return Ok(());
+    }
+
+    let dry_run = args.get_flag("dry-run");
+    let mut added = 0;
+    if dry_run {
+        // read-only: no lock, no migration on disk
+        let (_comments, existing) = read_store_unlocked(args)?;
+        added = report_proposals(&collector.candidates, &existing, dry_run).len();
+    } else {
+        add_identities(args, "import-git", |file_content| {
+            let (_, existing) = read_store(file_content)
+                .map_err(|e| format!("{}\nRun `sigrs doctor` to repair config.sigrs before importing", e))?;
+            let identities = report_proposals(&collector.candidates, &existing, dry_run);
+            added = identities.len();
+            Ok(identities)
+        })?;
+    }
+
+    if dry_run {
+        println!("Dry run, nothing written. {} identities would be added", added);
+    } else {
+        println!("Added {} identities", added);
+    }
+    Ok(())
+}
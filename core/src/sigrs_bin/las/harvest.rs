@@ -0,0 +1,229 @@
+use clap::ArgMatches;
+use std::path::{Path, PathBuf};
+use crate::identity::AuthorMeta;
+use crate::signing::{handle::{find_config_paths, Discovery}, signing_utils::FoundAuthor};
+use super::export::{import_identities, ImportMode};
+use super::storage::{read_store_unlocked, rewrite_store, sort_metadata, unique_key, StoredIdentity};
+
+// ========== HARVEST (create/merge identities from authors already in config files)
+// sigrs harvest --dir ./some/monorepo
+// == finds config files the same way as `sigrs sign` (node_modules, vendor, target && .git are skipped)
+// sigrs harvest --dir ./a --dir ./b --only-include package.json::composer.json
+// sigrs harvest --dir-with-depth 2::./some/monorepo -f ./other/Cargo.toml
+// sigrs harvest --dir ./some/monorepo --dry-run
+//
+// Reads `Name <email>` strings (Cargo.toml, poetry, npm) && `{ name, email }`
+// objects (package.json, composer.json, PEP 621) from:
+// - Cargo.toml      every `authors` array
+// - package.json    `author` && `contributors`
+// - composer.json   `authors`
+// - pyproject.toml  every `authors` array
+//
+// Authors with the same email (case insensitive) are one person, their names are
+// all kept as usernames. Authors with only a name join the person using that
+// name, or are skipped. A person is merged into the stored identity holding one
+// of their emails, otherwise becomes a new identity
+
+/// One person found across the harvested files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HarvestedPerson {
+    pub usernames: Vec<String>,
+    pub emails: Vec<String>,
+    pub meta: AuthorMeta,
+    pub sources: Vec<PathBuf>,
+}
+
+impl HarvestedPerson {
+    fn add_source(&mut self, source: &Path) {
+        if !self.sources.iter().any(|s| s == source) {
+            self.sources.push(source.to_path_buf());
+        }
+    }
+
+    fn metadata(&self) -> Vec<(String, String)> {
+        let mut metadata: Vec<(String, String)> = [("url", &self.meta.url), ("role", &self.meta.role)]
+            .into_iter()
+            .filter_map(|(field, value)| value.clone().map(|v| (field.to_string(), v)))
+            .collect();
+        sort_metadata(&mut metadata);
+        metadata
+    }
+}
+
+fn contains_ignore_case(values: &[String], value: &str) -> bool {
+    values.iter().any(|v| v.eq_ignore_ascii_case(value))
+}
+
+/// Groups authors into people, see above. Returns (people, skipped authors)
+pub fn group_authors(found: &[(PathBuf, FoundAuthor)]) -> (Vec<HarvestedPerson>, Vec<String>) {
+    let mut people: Vec<HarvestedPerson> = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (source, author) in found {
+        let Some(email) = &author.email else {
+            continue;
+        };
+        let index = match people.iter().position(|p| contains_ignore_case(&p.emails, email)) {
+            Some(index) => index,
+            None => {
+                people.push(HarvestedPerson { emails: vec![email.clone()], ..HarvestedPerson::default() });
+                people.len() - 1
+            }
+        };
+        let person = &mut people[index];
+        if let Some(name) = author.name.as_ref().filter(|name| !person.usernames.contains(name)) {
+            person.usernames.push(name.clone());
+        }
+        // first seen wins
+        person.meta.url = person.meta.url.take().or_else(|| author.meta.url.clone());
+        person.meta.role = person.meta.role.take().or_else(|| author.meta.role.clone());
+        person.add_source(source);
+    }
+
+    // name only, attach to whoever uses that name
+    for (source, author) in found.iter().filter(|(_, a)| a.email.is_none()) {
+        let Some(name) = &author.name else {
+            continue;
+        };
+        match people.iter_mut().find(|p| p.usernames.contains(name)) {
+            Some(person) => person.add_source(source),
+            None => skipped.push(format!("{} (no email, in {})", name, source.display())),
+        }
+    }
+
+    // email only
+    people.retain(|person| {
+        if person.usernames.is_empty() {
+            skipped.push(format!("<{}> (no name, in {})", person.emails[0], person.sources[0].display()));
+        }
+        !person.usernames.is_empty()
+    });
+    skipped.dedup();
+    (people, skipped)
+}
+
+/// Assigns each person the key of the stored identity holding one of their
+/// emails, or a new unique key
+pub fn harvested_identities(people: &[HarvestedPerson], existing: &[StoredIdentity]) -> Vec<StoredIdentity> {
+    let mut taken: Vec<String> = existing.iter().map(|i| i.key.clone()).collect();
+    people.iter()
+        .map(|person| {
+            let stored = existing.iter()
+                .find(|i| person.emails.iter().any(|e| contains_ignore_case(&i.emails, e)));
+            let key = match stored {
+                Some(stored) => stored.key.clone(),
+                None => {
+                    let key = unique_key(&person.usernames[0], &person.emails[0], &taken);
+                    taken.push(key.clone());
+                    key
+                }
+            };
+            StoredIdentity {
+                key,
+                usernames: person.usernames.clone(),
+                emails: person.emails.clone(),
+                metadata: person.metadata(),
+                rules: vec![],
+            }
+        })
+        .collect()
+}
+
+pub fn handle_harvest(args: &ArgMatches) -> Result<(), String> {
+    let (paths, errs) = find_config_paths(args, Discovery::ConfigFiles);
+    for e in errs {
+        eprintln!("{e}");
+    }
+
+    let mut found: Vec<(PathBuf, FoundAuthor)> = Vec::new();
+    for path in &paths {
+        match path.authors() {
+            Ok(authors) => {
+                found.extend(authors.into_iter().map(|a| (path.get_path().to_path_buf(), a)));
+            },
+            Err(e) => eprintln!("Skipped {}: {}", path.get_path().display(), e),
+        }
+    }
+
+    let (people, skipped) = group_authors(&found);
+    for skip in &skipped {
+        println!("skip   {}", skip);
+    }
+    if people.is_empty() {
+        println!("No authors with both a name and an email found in {} config files", paths.len());
+        return Ok(());
+    }
+
+    let dry_run = args.get_flag("dry-run");
+    let (added, merged) = merge_people(args, "harvest", &people, dry_run)?;
+
+    let summary = format!("{} new, {} merged, from {} config files", added, merged, paths.len());
+    if dry_run {
+        println!("Dry run, nothing written. Would add {}", summary);
+    } else {
+        println!("Harvested {}", summary);
+    }
+    Ok(())
+}
+
+/// Adds/merges `people` into config.sigrs (see `harvested_identities`), printing
+/// each change. Also used by `sigrs import-mailmap`. Returns (added, merged)
+/// A dry run only reads config.sigrs, it isn't locked, migrated or created
+pub fn merge_people(
+    args: &ArgMatches,
+    description: &str,
+    people: &[HarvestedPerson],
+    dry_run: bool
+) -> Result<(usize, usize), String> {
+    if dry_run {
+        let (_comments, existing) = read_store_unlocked(args)?;
+        let (_, added, merged) = plan_merge(people, &existing)?;
+        return Ok((added, merged));
+    }
+    let (mut added, mut merged) = (0, 0);
+    rewrite_store(args, description, |existing| {
+        let (identities, plan_added, plan_merged) = plan_merge(people, &existing)?;
+        (added, merged) = (plan_added, plan_merged);
+        Ok(identities)
+    })?;
+    Ok((added, merged))
+}
+
+/// `existing` with `people` merged in, printing each change. Returns (identities, added, merged)
+fn plan_merge(
+    people: &[HarvestedPerson],
+    existing: &[StoredIdentity]
+) -> Result<(Vec<StoredIdentity>, usize, usize), String> {
+    let (mut added, mut merged) = (0, 0);
+    let harvested = harvested_identities(people, existing);
+    let (identities, _) = import_identities(existing.to_vec(), harvested, ImportMode::Merge)?;
+
+    for identity in &identities {
+        let before = existing.iter().find(|e| e.key == identity.key);
+        let new_values = |values: &[String], old: Option<&Vec<String>>| {
+            values.iter()
+                .filter(|v| old.is_none_or(|old| !old.contains(v)))
+                .cloned()
+                .collect::<Vec<String>>()
+        };
+        let usernames = new_values(&identity.usernames, before.map(|b| &b.usernames));
+        let emails = new_values(&identity.emails, before.map(|b| &b.emails));
+
+        match before {
+            None => {
+                added += 1;
+                println!("add    \"{}\": {} / {}", identity.key, usernames.join(", "), emails.join(", "));
+            },
+            Some(before) if before != identity => {
+                merged += 1;
+                let metadata = identity.metadata.iter()
+                    .filter(|m| !before.metadata.contains(m))
+                    .map(|(field, value)| format!("{}={}", field, value));
+                let changes: Vec<String> = usernames.into_iter().chain(emails).chain(metadata).collect();
+                println!("merge  \"{}\": + {}", identity.key, changes.join(", "));
+            },
+            Some(_) => {},
+        }
+    }
+    Ok((identities, added, merged))
+}
@@ -0,0 +1,254 @@
+// ============================= config.sigrs (LAS) history
+// Every add-new/update/delete records a snapshot of config.sigrs before && after
+// the change in `<config path>.history/`, so mistakes can be walked back
+//
+// <config path>.history/
+//   HEAD              id of the last applied change, 0 if everything is undone
+//   000001.before     config.sigrs before change 1
+//   000001.after      config.sigrs after change 1
+//   000001.info       `<unix secs>\n<description>`
+//
+// - undo restores `<HEAD>.before` && moves HEAD to the previous change
+// - redo restores `<next>.after` && moves HEAD forward
+// - recording a new change drops anything that was undone (no redo past it)
+// - only the newest `HISTORY_LIMIT` changes are kept
+//
+// ========== UNDO / REDO / HISTORY
+// sigrs undo
+// sigrs redo
+// sigrs history
+use clap::ArgMatches;
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use crate::utils::{extract_config_path, write_atomic};
+use super::lock::{extract_lock_timeout, lock_las, lock_las_for_write, LockKind};
+
+/// Number of changes kept, oldest are dropped first
+pub const HISTORY_LIMIT: usize = 50;
+
+/// A recorded change
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// unix secs
+    pub time: u64,
+    pub description: String,
+}
+
+/// `<config path>.history`
+pub fn history_dir(config_path: &Path) -> PathBuf {
+    let mut path = config_path.as_os_str().to_owned();
+    path.push(".history");
+    PathBuf::from(path)
+}
+
+fn entry_path(dir: &Path, id: u64, ext: &str) -> PathBuf {
+    dir.join(format!("{:06}.{}", id, ext))
+}
+
+fn read_head(dir: &Path) -> Result<u64, String> {
+    match fs::read_to_string(dir.join("HEAD")) {
+        Ok(head) => head.trim().parse::<u64>()
+            .map_err(|_| format!("Invalid history HEAD in {}", dir.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(format!("Problem reading history: {:?}", e)),
+    }
+}
+
+fn write_head(dir: &Path, id: u64) -> Result<(), String> {
+    write_atomic(&dir.join("HEAD"), id.to_string().as_bytes())
+}
+
+/// Ids of every recorded change, oldest first
+fn entry_ids(dir: &Path) -> Result<Vec<u64>, String> {
+    let read = match fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("Problem reading history: {:?}", e)),
+    };
+    let mut ids: Vec<u64> = read
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let name = name.to_str()?;
+            name.strip_suffix(".info")?.parse::<u64>().ok()
+        })
+        .collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+fn remove_entry(dir: &Path, id: u64) -> Result<(), String> {
+    for ext in ["before", "after", "info"] {
+        match fs::remove_file(entry_path(dir, id, ext)) {
+            Ok(()) => {},
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(format!("Problem removing history entry {}: {:?}", id, e)),
+        }
+    }
+    Ok(())
+}
+
+fn read_entry(dir: &Path, id: u64) -> Result<HistoryEntry, String> {
+    let info = fs::read_to_string(entry_path(dir, id, "info"))
+        .map_err(|e| format!("Problem reading history entry {}: {:?}", id, e))?;
+    let (time, description) = info.split_once('\n').unwrap_or((info.as_str(), ""));
+    Ok(HistoryEntry {
+        id,
+        time: time.trim().parse().unwrap_or(0),
+        description: description.to_string(),
+    })
+}
+
+/// Every recorded change oldest first, && the id of the last applied one
+pub fn read_history(config_path: &Path) -> Result<(Vec<HistoryEntry>, u64), String> {
+    let dir = history_dir(config_path);
+    let entries = entry_ids(&dir)?
+        .into_iter()
+        .map(|id| read_entry(&dir, id))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((entries, read_head(&dir)?))
+}
+
+/// Records a change to config.sigrs. Caller must hold the exclusive lock
+pub fn record_change(
+    config_path: &Path,
+    before: &[u8],
+    after: &[u8],
+    description: &str
+) -> Result<(), String> {
+    let dir = history_dir(config_path);
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Problem creating {}: {:?}", dir.display(), e))?;
+
+    let head = read_head(&dir)?;
+    let mut ids = entry_ids(&dir)?;
+
+    // a new change replaces anything that was undone
+    for &id in ids.iter().filter(|&&id| id > head) {
+        remove_entry(&dir, id)?;
+    }
+    ids.retain(|&id| id <= head);
+
+    let id = ids.last().map_or(head, |&last| last.max(head)) + 1;
+    let now = UNIX_EPOCH.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+
+    let write = |ext: &str, content: &[u8]| {
+        fs::write(entry_path(&dir, id, ext), content)
+            .map_err(|e| format!("Problem writing history entry {}: {:?}", id, e))
+    };
+    write("before", before)?;
+    write("after", after)?;
+    // info last, entries are only listed once it exists
+    write("info", format!("{}\n{}", now, description).as_bytes())?;
+    write_head(&dir, id)?;
+
+    ids.push(id);
+    while ids.len() > HISTORY_LIMIT {
+        remove_entry(&dir, ids.remove(0))?;
+    }
+    Ok(())
+}
+
+/// `record_change`, but only warns on failure since the change itself already succeeded
+pub fn record_change_or_warn(config_path: &Path, before: &[u8], after: &[u8], description: &str) {
+    if let Err(e) = record_change(config_path, before, after, description) {
+        eprintln!("Warning: change saved, but could not be recorded for `sigrs undo`: {}", e);
+    }
+}
+
+/// Errors if config.sigrs no longer matches what history expects,
+/// so undo/redo never silently discard changes made outside sigrs history
+fn check_unchanged(config_path: &Path, expected: &[u8], args: &ArgMatches) -> Result<(), String> {
+    if args.get_flag("force") {
+        return Ok(());
+    }
+    let current = match fs::read(config_path) {
+        Ok(current) => current,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(format!("Problem reading config file: {:?}", e)),
+    };
+    if current != expected {
+        return Err(String::from(
+            "config.sigrs was changed since sigrs last recorded it (e.g. doctor --fix, or a manual edit). Use `--force` to discard those changes"
+        ));
+    }
+    Ok(())
+}
+
+pub fn handle_undo(args: &ArgMatches) -> Result<(), String> {
+    let config_path = PathBuf::from(extract_config_path(args)?);
+    let _lock = lock_las_for_write(args, &config_path)?;
+    let dir = history_dir(&config_path);
+
+    let head = read_head(&dir)?;
+    let Some(&id) = entry_ids(&dir)?.iter().rev().find(|&&id| id <= head) else {
+        return Err(String::from("Nothing to undo"));
+    };
+    let entry = read_entry(&dir, id)?;
+
+    let after = fs::read(entry_path(&dir, id, "after"))
+        .map_err(|e| format!("Problem reading history entry {}: {:?}", id, e))?;
+    check_unchanged(&config_path, &after, args)?;
+
+    let before = fs::read(entry_path(&dir, id, "before"))
+        .map_err(|e| format!("Problem reading history entry {}: {:?}", id, e))?;
+    write_atomic(&config_path, &before)?;
+
+    let previous = entry_ids(&dir)?.into_iter().filter(|&prev| prev < id).max().unwrap_or(0);
+    write_head(&dir, previous)?;
+
+    println!("Undid: {}", entry.description);
+    Ok(())
+}
+
+pub fn handle_redo(args: &ArgMatches) -> Result<(), String> {
+    let config_path = PathBuf::from(extract_config_path(args)?);
+    let _lock = lock_las_for_write(args, &config_path)?;
+    let dir = history_dir(&config_path);
+
+    let head = read_head(&dir)?;
+    let Some(&id) = entry_ids(&dir)?.iter().find(|&&id| id > head) else {
+        return Err(String::from("Nothing to redo"));
+    };
+    let entry = read_entry(&dir, id)?;
+
+    let before = fs::read(entry_path(&dir, id, "before"))
+        .map_err(|e| format!("Problem reading history entry {}: {:?}", id, e))?;
+    check_unchanged(&config_path, &before, args)?;
+
+    let after = fs::read(entry_path(&dir, id, "after"))
+        .map_err(|e| format!("Problem reading history entry {}: {:?}", id, e))?;
+    write_atomic(&config_path, &after)?;
+    write_head(&dir, id)?;
+
+    println!("Redid: {}", entry.description);
+    Ok(())
+}
+
+pub fn handle_history(args: &ArgMatches) -> Result<(), String> {
+    let config_path = PathBuf::from(extract_config_path(args)?);
+    let _lock = lock_las(&config_path, LockKind::Shared, extract_lock_timeout(args))?;
+    let (entries, head) = read_history(&config_path)?;
+
+    if entries.is_empty() {
+        println!("No changes recorded for {}", config_path.display());
+        return Ok(());
+    }
+
+    // newest first, * marks the current state
+    println!("    id  unix time   change");
+    for entry in entries.iter().rev() {
+        let marker = if entry.id == head { "* " } else { "  " };
+        let state = if entry.id > head { " (undone)" } else { "" };
+        println!("{}{:>4}  {:<10}  {}{}", marker, entry.id, entry.time, entry.description, state);
+    }
+    if head == 0 {
+        println!("* start of recorded history, everything above is undone");
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+#[cfg(test)]
+pub mod test_format;
+#[cfg(test)]
+pub mod test_version;
+#[cfg(test)]
+pub mod test_parse;
+#[cfg(test)]
+pub mod test_doctor;
+#[cfg(test)]
+pub mod test_config;
+#[cfg(test)]
+pub mod test_storage;
+#[cfg(test)]
+pub mod test_lock;
+#[cfg(test)]
+pub mod test_history;
+#[cfg(test)]
+pub mod test_export;
+#[cfg(test)]
+pub mod test_git_import;
+#[cfg(test)]
+pub mod test_harvest;
+#[cfg(test)]
+pub mod test_mailmap;
+#[cfg(test)]
+pub mod test_validate;
+#[cfg(test)]
+pub mod test_search;
+#[cfg(test)]
+pub mod test_output;
+#[cfg(test)]
+pub mod test_merge;
+#[cfg(test)]
+pub mod test_profile;
+#[cfg(test)]
+pub mod test_layers;
+#[cfg(test)]
+pub mod test_rules;
@@ -0,0 +1,71 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::config::{handle_generate_config, handle_reset_config};
+
+#[cfg(test)]
+use crate::las::lock::lock_path;
+
+#[cfg(test)]
+use crate::las::version::las_header;
+
+#[test]
+fn test_reset_config_archives_old_store() {
+    let path = "./TestResetConfig.sigrs";
+    let old = format!("{}K:\"a\"\nU:\"u\"\nE:\"e\"\n", las_header());
+    fs::write(path, &old).unwrap();
+
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "reset-config", "--config-path", path])
+        .unwrap();
+    let Some(("reset-config", sub_matches)) = matches.subcommand() else {
+        panic!("reset-config not matched");
+    };
+    let res = handle_reset_config(sub_matches);
+
+    let archives: Vec<_> = fs::read_dir(".").unwrap()
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("TestResetConfig.sigrs.reset-"))
+        .collect();
+    let archived = archives.first().map(|name| fs::read_to_string(name).unwrap());
+    let fresh = fs::read_to_string(path).unwrap();
+
+    for name in &archives {
+        fs::remove_file(name).unwrap();
+    }
+    fs::remove_file(path).unwrap();
+    fs::remove_file(lock_path(path.as_ref())).unwrap();
+
+    assert!(res.is_ok(), "{:?}", res);
+    assert_eq!(archives.len(), 1);
+    assert_eq!(archived.as_deref(), Some(old.as_str()));
+    assert_eq!(fresh, las_header());
+}
+
+#[test]
+fn test_generate_config() {
+    let dir = "./TestGenerateConfig";
+    let path = "./TestGenerateConfig/nested/config.sigrs";
+
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "generate-config", "--path", path])
+        .unwrap();
+    let Some(("generate-config", sub_matches)) = matches.subcommand() else {
+        panic!("generate-config not matched");
+    };
+    let first = handle_generate_config(sub_matches);
+    let content = fs::read_to_string(path);
+    // never overwrites an existing config
+    let second = handle_generate_config(sub_matches);
+
+    fs::remove_dir_all(dir).unwrap();
+
+    assert!(first.is_ok(), "{:?}", first);
+    assert_eq!(content.unwrap(), las_header());
+    assert!(second.is_err());
+}
@@ -0,0 +1,126 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::doctor::{diagnose, handle_doctor};
+
+#[cfg(test)]
+use crate::las::lock::lock_path;
+
+#[cfg(test)]
+use crate::las::version::las_header;
+
+#[cfg(test)]
+use crate::memmap::process_las;
+
+#[cfg(test)]
+fn with_header(body: &str) -> String {
+    format!("{}{}", las_header(), body)
+}
+
+#[test]
+fn test_doctor_clean_file() {
+    let content = with_header("# my comment\nK:\"a\"\nU:\"u\"\nE:\"e\"\n");
+    let diagnosis = diagnose(&content);
+    assert!(diagnosis.diagnostics.is_empty(), "{:?}", diagnosis.diagnostics);
+    // a clean file is rewritten unchanged
+    assert_eq!(diagnosis.repaired_content(), content);
+}
+
+#[test]
+fn test_doctor_duplicate_keys_merged() {
+    let content = with_header("K:\"a\"\nU:\"u1\"\nE:\"e1\"\nK:\"a\"\nU:\"u2\"\"u1\"\nE:\"e2\"\n");
+    let diagnosis = diagnose(&content);
+
+    assert_eq!(diagnosis.diagnostics.len(), 1);
+    assert_eq!(diagnosis.diagnostics[0].line, Some(6));
+    assert!(diagnosis.diagnostics[0].fixable);
+
+    assert_eq!(diagnosis.identities.len(), 1);
+    assert_eq!(diagnosis.identities[0].usernames, vec!["u1", "u2"]);
+    assert_eq!(diagnosis.identities[0].emails, vec!["e1", "e2"]);
+}
+
+#[test]
+fn test_doctor_ordering() {
+    // E before U, orphan U, missing E
+    let content = with_header("K:\"a\"\nE:\"e\"\nU:\"u\"\nK:\"b\"\nU:\"u\"\n");
+    let diagnosis = diagnose(&content);
+    let messages: Vec<&str> = diagnosis.diagnostics.iter().map(|d| d.message.as_str()).collect();
+
+    assert!(messages.iter().any(|m| m.contains("`U:` line after its `E:` line")), "{:?}", messages);
+    assert!(messages.iter().any(|m| m.contains("\"b\" is missing its `E:` line")), "{:?}", messages);
+
+    let orphan = with_header("U:\"u\"\nK:\"a\"\nU:\"u\"\nE:\"e\"\n");
+    let diagnosis = diagnose(&orphan);
+    assert_eq!(diagnosis.diagnostics.len(), 1);
+    assert_eq!(diagnosis.diagnostics[0].line, Some(3));
+}
+
+#[test]
+fn test_doctor_empty_identities_not_fixable() {
+    let content = with_header("K:\"a\"\nU:\nE:\"e\"\nK:\"b\"\nU:\"u\"\nE:\n");
+    let diagnosis = diagnose(&content);
+    assert_eq!(diagnosis.diagnostics.len(), 2);
+    assert!(diagnosis.diagnostics.iter().all(|d| !d.fixable));
+}
+
+#[test]
+fn test_doctor_repair_parses() {
+    let content = with_header(
+        "garbage line\nK:\"a\"\nU:\"u1\" \"u2\"\nE:\"e\"\nK:\"b\"\nU:\"u\"\nE:\"unterminated\nK:\"a\"\nU:\"u3\"\nE:\"e\"\nK:\"c\"\n"
+    );
+    assert!(process_las(content.as_bytes()).is_err());
+
+    let diagnosis = diagnose(&content);
+    assert!(diagnosis.diagnostics.len() >= 4, "{:?}", diagnosis.diagnostics);
+
+    let repaired = diagnosis.repaired_content();
+    let las = process_las(repaired.as_bytes()).expect("repaired content does not parse");
+    assert_eq!(las.identities.len(), 3);
+
+    let (_k, a) = las.lookup_id(&String::from("a")).unwrap();
+    let unames: Vec<&[u8]> = a.usernames.iter().map(|u| u.as_ref()).collect();
+    assert_eq!(unames, vec![&b"u1"[..], b"u2", b"u3"]);
+
+    // running doctor on repaired content only leaves the manual problems
+    let again = diagnose(&repaired);
+    assert!(again.diagnostics.iter().all(|d| !d.fixable), "{:?}", again.diagnostics);
+}
+
+#[test]
+fn test_doctor_metadata() {
+    // clean metadata survives a rewrite unchanged
+    let clean = with_header("K:\"a\"\nU:\"u\"\nE:\"e\"\nM:\"url\"\"https://x.com\"\n");
+    let diagnosis = diagnose(&clean);
+    assert!(diagnosis.diagnostics.is_empty(), "{:?}", diagnosis.diagnostics);
+    assert_eq!(diagnosis.repaired_content(), clean);
+
+    // misplaced `M:` line && dangling field are repaired
+    let content = with_header("K:\"a\"\nM:\"role\"\"Dev\"\"url\"\nU:\"u\"\nE:\"e\"\n");
+    let diagnosis = diagnose(&content);
+    assert_eq!(diagnosis.diagnostics.len(), 3, "{:?}", diagnosis.diagnostics);
+    assert!(diagnosis.diagnostics.iter().all(|d| d.fixable));
+
+    let repaired = diagnosis.repaired_content();
+    assert_eq!(repaired, with_header("K:\"a\"\nU:\"u\"\nE:\"e\"\nM:\"role\"\"Dev\"\n"));
+    assert!(process_las(repaired.as_bytes()).is_ok());
+}
+
+#[test]
+fn test_doctor_config_path_without_trailer() {
+    // the test binary has no saved config path, like an unmodified binary in CI
+    let path = "./TestDoctorNoTrailer.sigrs";
+    fs::write(path, with_header("K:\"a\"\nU:\"u\"\nE:\"e\"\n")).unwrap();
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "doctor", "--config-path", path])
+        .unwrap();
+    let result = handle_doctor(matches.subcommand().unwrap().1);
+    fs::remove_file(path).unwrap();
+    let _ = fs::remove_file(lock_path(path.as_ref()));
+
+    assert!(result.is_ok(), "{:?}", result);
+}
@@ -0,0 +1,161 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    export::{deserialize_export, handle_export, handle_import, ExportFormat},
+    las_tests::test_storage::cleanup,
+    storage::read_store,
+    version::las_header,
+};
+
+#[cfg(test)]
+fn run_import(path: &str, extra: &[&str]) -> Result<(), String> {
+    let mut argv = vec!["sigrs", "import", "--config-path", path];
+    argv.extend_from_slice(extra);
+    let matches = sigrs_command().try_get_matches_from(argv).unwrap();
+    handle_import(matches.subcommand_matches("import").unwrap())
+}
+
+#[cfg(test)]
+fn run_export(path: &str, output: &str) -> Result<(), String> {
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "export", "--config-path", path, "--output", output])
+        .unwrap();
+    unsafe { handle_export(matches.subcommand_matches("export").unwrap()) }
+}
+
+#[test]
+fn test_export_round_trip() {
+    let path = "./TestLasExport.sigrs";
+    let content = format!(
+        "{}K:\"work\"\nU:\"bob\"\"rob\"\nE:\"bob@x.com\"\nM:\"url\"\"https://x.com\"\"role\"\"Lead\"\nK:\"home \\\"q\\\"\"\nU:\"b\"\nE:\"b@y.com\"\n",
+        las_header()
+    );
+    fs::write(path, &content).unwrap();
+
+    let mut results = vec![];
+    for output in ["./TestLasExport.json", "./TestLasExport.toml"] {
+        let export_res = run_export(path, output);
+        let exported = fs::read_to_string(output).unwrap_or_default();
+        // importing what was just exported into an empty store gives the same file
+        fs::write(path, las_header()).unwrap();
+        let import_res = run_import(path, &[output]);
+        results.push((export_res, exported, import_res, fs::read_to_string(path).unwrap()));
+        fs::write(path, &content).unwrap();
+        let _ = fs::remove_file(output);
+    }
+    cleanup(path);
+
+    for (export_res, exported, import_res, reimported) in results {
+        assert_eq!(export_res, Ok(()));
+        assert!(exported.contains("https://x.com"), "{}", exported);
+        assert_eq!(import_res, Ok(()));
+        assert_eq!(reimported, content);
+    }
+}
+
+#[test]
+fn test_import_conflicts() {
+    let path = "./TestLasImport.sigrs";
+    let file = "./TestLasImport.json";
+    let existing = format!(
+        "{}# keep me\nK:\"a\"\nU:\"u1\"\nE:\"e1@x.com\"\nM:\"role\"\"Old\"\nK:\"b\"\nU:\"u\"\nE:\"e\"\n",
+        las_header()
+    );
+    fs::write(path, &existing).unwrap();
+    fs::write(file, r#"{
+        "version": 1,
+        "identities": [
+            { "key": "a", "usernames": ["u2", "u1"], "emails": ["e1@x.com"], "metadata": { "role": "New", "url": "x.com" } },
+            { "key": "c", "usernames": ["cu"], "emails": ["ce@x.com"] }
+        ]
+    }"#).unwrap();
+
+    let conflict = run_import(path, &[file]);
+    let after_conflict = fs::read_to_string(path).unwrap();
+
+    let merge = run_import(path, &[file, "--merge"]);
+    let after_merge = fs::read_to_string(path).unwrap();
+
+    fs::write(path, &existing).unwrap();
+    let replace = run_import(path, &[file, "--replace"]);
+    let after_replace = fs::read_to_string(path).unwrap();
+
+    cleanup(path);
+    let _ = fs::remove_file(file);
+
+    // default mode writes nothing && names the conflict
+    assert!(conflict.unwrap_err().contains("\"a\""));
+    assert_eq!(after_conflict, existing);
+
+    assert_eq!(merge, Ok(()));
+    assert_eq!(after_merge, format!(
+        "{}# keep me\nK:\"a\"\nU:\"u1\"\"u2\"\nE:\"e1@x.com\"\nM:\"url\"\"x.com\"\"role\"\"Old\"\nK:\"b\"\nU:\"u\"\nE:\"e\"\nK:\"c\"\nU:\"cu\"\nE:\"ce@x.com\"\n",
+        las_header()
+    ));
+
+    assert_eq!(replace, Ok(()));
+    let (comments, identities) = read_store(&after_replace).unwrap();
+    assert_eq!(comments, vec!["# keep me".to_string()]);
+    assert_eq!(identities.iter().map(|i| i.key.as_str()).collect::<Vec<_>>(), vec!["a", "c"]);
+    assert_eq!(identities[0].usernames, vec!["u2", "u1"]);
+}
+
+#[test]
+fn test_deserialize_export_errors() {
+    let identity = "[[identities]]\nkey = \"a\"\nusernames = [\"u\"]\nemails = [\"e@x.com\"]\n";
+    let duplicate = format!("version = 1\n{}{}", identity, identity);
+    let newer = "{\"version\": 2, \"identities\": []}";
+    let empty_key = "{\"version\": 1, \"identities\": [{\"key\": \"\"}]}";
+
+    assert!(deserialize_export(&duplicate, ExportFormat::Toml).unwrap_err().contains("more than once"));
+    assert!(deserialize_export(newer, ExportFormat::Json).unwrap_err().contains("newer"));
+    assert!(deserialize_export(empty_key, ExportFormat::Json).is_err());
+    assert!(deserialize_export("not json", ExportFormat::Json).is_err());
+    // imported as is, doctor would flag them && sign can't use them
+    let neither = "{\"version\": 1, \"identities\": [{\"key\": \"ghost\"}]}";
+    let no_usernames = "{\"version\": 1, \"identities\": [{\"key\": \"ghost\", \"emails\": [\"g@x.com\"]}]}";
+    let no_emails = "{\"version\": 1, \"identities\": [{\"key\": \"ghost\", \"usernames\": [\"g\"]}]}";
+    assert!(deserialize_export(neither, ExportFormat::Json).unwrap_err().contains("\"ghost\""));
+    assert!(deserialize_export(no_usernames, ExportFormat::Json).is_err());
+    assert!(deserialize_export(no_emails, ExportFormat::Json).is_err());
+}
+
+#[test]
+fn test_import_validates() {
+    let path = "./TestLasImportValidate.sigrs";
+    let file = "./TestLasImportValidate.json";
+    fs::write(path, las_header()).unwrap();
+    fs::write(file, r#"{
+        "version": 1,
+        "identities": [
+            { "key": "a", "usernames": ["  Bob "], "emails": ["bob@x.com"] },
+            { "key": "b", "usernames": ["b"], "emails": ["not an email"] }
+        ]
+    }"#).unwrap();
+
+    let checked = run_import(path, &[file]);
+    let after_checked = fs::read_to_string(path).unwrap();
+    let unchecked = run_import(path, &[file, "--no-validate"]);
+    let after_unchecked = fs::read_to_string(path).unwrap();
+
+    fs::write(file, r#"{ "version": 1, "identities": [{ "key": "a", "usernames": ["  Bob "], "emails": ["bob@x.com"] }] }"#).unwrap();
+    fs::write(path, las_header()).unwrap();
+    let trimmed = run_import(path, &[file]);
+    let after_trimmed = fs::read_to_string(path).unwrap();
+
+    cleanup(path);
+    let _ = fs::remove_file(file);
+
+    // same checks as add-new, nothing written on failure
+    assert!(checked.unwrap_err().contains("\"b\""));
+    assert_eq!(after_checked, las_header());
+    assert_eq!(unchecked, Ok(()));
+    assert!(after_unchecked.contains("E:\"not an email\""));
+    assert_eq!(trimmed, Ok(()));
+    assert_eq!(after_trimmed, format!("{}K:\"a\"\nU:\"Bob\"\nE:\"bob@x.com\"\n", las_header()));
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+use std::borrow::Cow;
+
+#[cfg(test)]
+use crate::las::format::{
+    escape_value,
+    unescape_value,
+    split_values,
+    parse_key,
+    key_line,
+    values_line,
+    identity_entry,
+};
+
+#[cfg(test)]
+use crate::las::version::las_header;
+
+#[cfg(test)]
+use crate::memmap::process_las;
+
+/// Values that broke the un-escaped format
+#[cfg(test)]
+const TRICKY_VALUES: &[&str] = &[
+    "plain name",
+    "Dwayne \"The Rock\" Johnson",
+    "Conan O'Brien",
+    "back\\slash",
+    "trailing backslash \\",
+    "\\\"",
+    "multi\nline",
+    "carriage\r\nreturn",
+    "\"",
+    "",
+    "Zoë Ünïcødé 名前",
+    "K:\"not a key\"",
+];
+
+#[test]
+fn test_escape_unescape_roundtrip() {
+    for value in TRICKY_VALUES {
+        let escaped = escape_value(value);
+        assert!(!escaped.contains('\n'), "escaped value contains a raw newline: {:?}", escaped);
+        let unescaped = unescape_value(escaped.as_bytes());
+        assert_eq!(
+            unescaped.as_ref(),
+            value.as_bytes(),
+            "escape -> unescape changed value {:?}", value
+        );
+    }
+}
+
+#[test]
+fn test_unescape_borrows_plain_values() {
+    let res = unescape_value(b"no escapes here");
+    assert!(matches!(res, Cow::Borrowed(_)), "plain value was copied");
+
+    let res = unescape_value(b"has \\\" escape");
+    assert!(matches!(res, Cow::Owned(_)));
+    assert_eq!(res.as_ref(), b"has \" escape");
+}
+
+#[test]
+fn test_split_values_roundtrip() {
+    let line = values_line('U', TRICKY_VALUES);
+    let body = line.strip_prefix("U:").unwrap();
+    let values = split_values(body);
+    assert_eq!(values, TRICKY_VALUES.to_vec());
+}
+
+#[test]
+fn test_parse_key_roundtrip() {
+    for value in TRICKY_VALUES {
+        let line = key_line(value);
+        assert_eq!(parse_key(&line).as_deref(), Some(*value));
+    }
+    assert_eq!(parse_key("U:\"not a key\""), None);
+}
+
+#[test]
+fn test_process_las_roundtrip() {
+    let mut content = las_header();
+    // one identity per tricky value, key/username/email all use the value
+    for (idx, value) in TRICKY_VALUES.iter().enumerate() {
+        let key = format!("{}{}", value, idx);
+        content.push_str(&identity_entry(&key, &[*value, "second"], &["x@y.com", *value], &[], &[]));
+    }
+
+    let las = process_las(content.as_bytes()).unwrap();
+    assert_eq!(las.identities.len(), TRICKY_VALUES.len());
+
+    for (idx, value) in TRICKY_VALUES.iter().enumerate() {
+        let key = format!("{}{}", value, idx);
+        let (_k, identity) = las.lookup_id(&key)
+            .unwrap_or_else(|| panic!("key {:?} not found after round trip", key));
+
+        assert_eq!(identity.usernames.len(), 2);
+        assert_eq!(identity.usernames[0].as_ref(), value.as_bytes());
+        assert_eq!(identity.usernames[1].as_ref(), b"second");
+
+        assert_eq!(identity.emails.len(), 2);
+        assert_eq!(identity.emails[0].as_ref(), b"x@y.com");
+        assert_eq!(identity.emails[1].as_ref(), value.as_bytes());
+    }
+}
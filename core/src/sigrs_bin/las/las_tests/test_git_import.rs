@@ -0,0 +1,156 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    git_import::{handle_import_git, parse_git_config, propose_identities, GitCandidate, GitProposal},
+    las_tests::test_storage::cleanup,
+    lock::lock_path,
+    storage::{read_store, StoredIdentity},
+    version::{backup_path, las_header},
+};
+
+#[test]
+fn test_parse_git_config() {
+    let content = "\
+# comment
+[user]
+\tname = \"Jane  Doe\" ; trailing comment
+\temail=jane@x.com   # another
+[includeIf \"gitdir:~/work/\"]
+    path = ~/.gitconfig-work
+[Section.Sub] Flag
+[core]
+    editor = vim \\
+        -u NONE
+    quoted = \"a\\\"b\\\\c\\t\"
+";
+    let entries = parse_git_config(content).unwrap();
+    let find = |section: &str, key: &str| {
+        entries.iter().find(|e| e.section == section && e.key == key).cloned().unwrap()
+    };
+
+    assert_eq!(find("user", "name").value.as_deref(), Some("Jane  Doe"));
+    assert_eq!(find("user", "email").value.as_deref(), Some("jane@x.com"));
+    let include = find("includeif", "path");
+    assert_eq!(include.subsection.as_deref(), Some("gitdir:~/work/"));
+    assert_eq!(include.value.as_deref(), Some("~/.gitconfig-work"));
+    let flag = find("section", "flag");
+    assert_eq!((flag.subsection.as_deref(), flag.value), (Some("sub"), None));
+    assert_eq!(find("core", "editor").value.as_deref(), Some("vim         -u NONE"));
+    assert_eq!(find("core", "quoted").value.as_deref(), Some("a\"b\\c\t"));
+
+    assert!(parse_git_config("name = x").is_err());
+    assert!(parse_git_config("[user]\nname = \"open").is_err());
+    assert!(parse_git_config("[user\nname = x").is_err());
+}
+
+#[test]
+fn test_propose_identities() {
+    let candidate = |name: &str, email: &str| GitCandidate {
+        name: name.to_string(),
+        email: email.to_string(),
+        source: "x".into(),
+    };
+    let existing = vec![StoredIdentity {
+        key: String::from("jane-doe"),
+        usernames: vec![String::from("Jane Doe")],
+        emails: vec![String::from("jane@home.com")],
+        metadata: vec![],
+        rules: vec![],
+    }];
+    let candidates = [
+        candidate("Jane Doe", "jane@home.com"),
+        candidate("Jane Doe", "jane@acme.com"),
+        candidate("Jane Doe", "j@acme.org"),
+        candidate("", "bob@x.com"),
+    ];
+
+    let keys: Vec<String> = propose_identities(&candidates, &existing)
+        .into_iter()
+        .map(|p| match p {
+            GitProposal::Add(identity, _) => identity.key,
+            GitProposal::AlreadyStored(_, key) => format!("stored:{}", key),
+        })
+        .collect();
+    assert_eq!(keys, vec!["stored:jane-doe", "jane-doe-acme", "jane-doe-2", "bob"]);
+}
+
+#[test]
+fn test_import_git() {
+    let path = "./TestGitImport.sigrs";
+    let root = "./TestGitImport.gitconfig";
+    let work = "./TestGitImport.gitconfig-work";
+    let nested = "./TestGitImport.gitconfig-nested";
+    fs::write(path, las_header()).unwrap();
+    fs::write(root, "[user]\n\tname = Jane Doe\n\temail = jane@home.com\n[includeIf \"gitdir:~/work/\"]\n\tpath = TestGitImport.gitconfig-work\n").unwrap();
+    // only overrides the email, the name comes from the including file
+    fs::write(work, "[user]\n\temail = jane@acme.com\n[include]\n\tpath = TestGitImport.gitconfig-nested\n").unwrap();
+    // cycle back to root is skipped, not followed forever
+    fs::write(nested, "[user]\n\tname = J. Doe\n[include]\n\tpath = TestGitImport.gitconfig\n").unwrap();
+
+    let run = |extra: &[&str]| {
+        let mut argv = vec!["sigrs", "import-git", "--config-path", path, "--no-global", "--file", root];
+        argv.extend_from_slice(extra);
+        let matches = sigrs_command().try_get_matches_from(argv).unwrap();
+        handle_import_git(matches.subcommand_matches("import-git").unwrap())
+    };
+
+    let dry_run = run(&["--dry-run"]);
+    let after_dry_run = fs::read_to_string(path).unwrap();
+    let first = run(&[]);
+    let after_first = fs::read_to_string(path).unwrap();
+    // everything is already stored the second time
+    let second = run(&[]);
+    let after_second = fs::read_to_string(path).unwrap();
+
+    cleanup(path);
+    for file in [root, work, nested] {
+        let _ = fs::remove_file(file);
+    }
+
+    assert_eq!(dry_run, Ok(()));
+    assert_eq!(after_dry_run, las_header());
+    assert_eq!(first, Ok(()));
+    let (_, identities) = read_store(&after_first).unwrap();
+    let pairs: Vec<(&str, &str, &str)> = identities.iter()
+        .map(|i| (i.key.as_str(), i.usernames[0].as_str(), i.emails[0].as_str()))
+        .collect();
+    assert_eq!(pairs, vec![
+        ("j-doe", "J. Doe", "jane@acme.com"),
+        ("jane-doe", "Jane Doe", "jane@home.com"),
+    ]);
+    assert_eq!(second, Ok(()));
+    assert_eq!(after_second, after_first);
+}
+
+#[test]
+fn test_import_git_dry_run_read_only() {
+    let path = "./TestGitImportDryRun.sigrs";
+    let gitconfig = "./TestGitImportDryRun.gitconfig";
+    // written before versioning, a real import would migrate it
+    let v0 = "# File generated by signatu_rs. Do not manually edit this file. #\nK:\"a\"\nU:\"u\"\nE:\"e\"\n";
+    fs::write(path, v0).unwrap();
+    fs::write(gitconfig, "[user]\n\tname = Jane Doe\n\temail = jane@home.com\n").unwrap();
+
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "import-git", "--config-path", path, "--no-global", "--file", gitconfig, "--dry-run"])
+        .unwrap();
+    let dry_run = handle_import_git(matches.subcommand_matches("import-git").unwrap());
+    let after = fs::read_to_string(path).unwrap();
+    let side_files = [lock_path(path.as_ref()), backup_path(path.as_ref(), "v0")]
+        .iter()
+        .filter(|p| p.exists())
+        .count();
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(gitconfig).unwrap();
+
+    assert_eq!(dry_run, Ok(()));
+    // no migration, no lock or backup files
+    assert_eq!(after, v0);
+    assert_eq!(side_files, 0);
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    harvest::handle_harvest,
+    las_tests::test_storage::cleanup,
+    lock::lock_path,
+    version::backup_path,
+    storage::read_store,
+    version::las_header,
+};
+
+#[test]
+fn test_harvest() {
+    let path = "./TestLasHarvest.sigrs";
+    let dir = "./TestLasHarvestDir";
+    let _ = fs::remove_dir_all(dir);
+    for sub in ["a", "b", "b/node_modules/dep"] {
+        fs::create_dir_all(format!("{}/{}", dir, sub)).unwrap();
+    }
+    fs::write(format!("{}/a/Cargo.toml", dir), "[package]\nauthors = [\"Jane Doe <jane@x.com>\", \"Bob\", \"Ann <ann@x.com>\"]\n").unwrap();
+    fs::write(
+        format!("{}/b/package.json", dir),
+        r#"{ "author": { "name": "J. Doe", "email": "JANE@x.com", "url": "jane.dev" }, "contributors": ["<nameless@x.com>"] }"#
+    ).unwrap();
+    // dependencies are never harvested
+    fs::write(format!("{}/b/node_modules/dep/package.json", dir), r#"{ "author": "Dep <dep@x.com>" }"#).unwrap();
+    fs::write(format!("{}/b/README.md", dir), "not a config").unwrap();
+    // ann is already stored, her identity is merged into
+    fs::write(path, format!("{}K:\"ann\"\nU:\"Ann B\"\nE:\"ann@x.com\"\n", las_header())).unwrap();
+
+    let run = |extra: &[&str]| {
+        let mut argv = vec!["sigrs", "harvest", "--config-path", path, "--dir", dir];
+        argv.extend_from_slice(extra);
+        let matches = sigrs_command().try_get_matches_from(argv).unwrap();
+        handle_harvest(matches.subcommand_matches("harvest").unwrap())
+    };
+
+    let before = fs::read_to_string(path).unwrap();
+    let dry_run = run(&["--dry-run"]);
+    let after_dry_run = fs::read_to_string(path).unwrap();
+    let harvest = run(&[]);
+    let after = fs::read_to_string(path).unwrap();
+    let again = run(&[]);
+    let after_again = fs::read_to_string(path).unwrap();
+
+    cleanup(path);
+    let _ = fs::remove_dir_all(dir);
+
+    assert_eq!(dry_run, Ok(()));
+    assert_eq!(after_dry_run, before);
+    assert_eq!(harvest, Ok(()));
+    let (_, identities) = read_store(&after).unwrap();
+    let summary: Vec<(&str, Vec<String>, Vec<String>)> = identities.iter()
+        .map(|i| (i.key.as_str(), i.usernames.clone(), i.emails.clone()))
+        .collect();
+    assert_eq!(summary, vec![
+        ("ann", vec!["Ann B".to_string(), "Ann".to_string()], vec!["ann@x.com".to_string()]),
+        ("jane-doe", vec!["Jane Doe".to_string(), "J. Doe".to_string()], vec!["jane@x.com".to_string()]),
+    ]);
+    assert_eq!(identities[1].metadata, vec![("url".to_string(), "jane.dev".to_string())]);
+    // nothing new the second time
+    assert_eq!(again, Ok(()));
+    assert_eq!(after_again, after);
+}
+
+#[test]
+fn test_harvest_dry_run_read_only() {
+    let missing = "./TestLasHarvestDryMissing.sigrs";
+    let old = "./TestLasHarvestDryOld.sigrs";
+    let dir = "./TestLasHarvestDryDir";
+    fs::create_dir_all(dir).unwrap();
+    fs::write(format!("{}/Cargo.toml", dir), "[package]\nauthors = [\"Jane Doe <jane@x.com>\"]\n").unwrap();
+    let v2 = "# File generated by signatu_rs. Do not manually edit this file. #\n#!las-version:2\nK:\"a\"\nU:\"u\"\nE:\"e\"\n";
+    fs::write(old, v2).unwrap();
+
+    let run = |path: &str| {
+        let matches = sigrs_command()
+            .try_get_matches_from(["sigrs", "harvest", "--config-path", path, "--dir", dir, "--dry-run"])
+            .unwrap();
+        handle_harvest(matches.subcommand_matches("harvest").unwrap())
+    };
+
+    let on_missing = run(missing);
+    let on_old = run(old);
+    let missing_created = fs::exists(missing).unwrap();
+    let old_after = fs::read_to_string(old).unwrap();
+    let side_files = [lock_path(missing.as_ref()), lock_path(old.as_ref()), backup_path(old.as_ref(), "v2")]
+        .iter()
+        .filter(|p| p.exists())
+        .count();
+
+    fs::remove_file(old).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(on_missing, Ok(()));
+    assert_eq!(on_old, Ok(()));
+    // no new config, no migration, no lock files
+    assert!(!missing_created);
+    assert_eq!(old_after, v2);
+    assert_eq!(side_files, 0);
+}
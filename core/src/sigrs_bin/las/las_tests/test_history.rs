@@ -0,0 +1,81 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    delete::handle_delete,
+    history::{handle_redo, handle_undo, read_history},
+    las_tests::test_storage::cleanup,
+    storage::{handle_add_new, handle_update},
+    version::las_header,
+};
+
+/// Runs `sigrs <subcommand> --config-path <path> <extra>`
+#[cfg(test)]
+fn run(path: &str, subcommand: &str, extra: &[&str]) -> Result<(), String> {
+    let mut argv = vec!["sigrs", subcommand, "--config-path", path];
+    argv.extend_from_slice(extra);
+    let matches = sigrs_command().try_get_matches_from(argv).unwrap();
+    match matches.subcommand() {
+        Some(("add-new", m)) => handle_add_new(m),
+        Some(("update", m)) => handle_update(m),
+        Some(("delete", m)) => handle_delete(m),
+        Some(("undo", m)) => handle_undo(m),
+        Some(("redo", m)) => handle_redo(m),
+        _ => panic!("unexpected subcommand {}", subcommand),
+    }
+}
+
+#[test]
+fn test_undo_redo() {
+    let path = "./TestLasHistory.sigrs";
+    let empty = las_header();
+    fs::write(path, &empty).unwrap();
+    let read = || fs::read_to_string(path).unwrap();
+
+    run(path, "add-new", &["--identity", "a", "-U", "u", "-E", "e@x.com"]).unwrap();
+    let added = read();
+    run(path, "update", &["--identity", "a", "--remove-all-emails"]).unwrap();
+    let updated = read();
+    run(path, "delete", &["--identity", "a"]).unwrap();
+
+    let mut states = vec![];
+    // walk all the way back, then too far
+    for _ in 0..3 {
+        run(path, "undo", &[]).unwrap();
+        states.push(read());
+    }
+    let undo_past_start = run(path, "undo", &[]);
+
+    // forward again
+    run(path, "redo", &[]).unwrap();
+    states.push(read());
+
+    // a new change drops the rest of the redo history
+    run(path, "add-new", &["--identity", "b", "-U", "u", "-E", "e@x.com"]).unwrap();
+    let redo_after_change = run(path, "redo", &[]);
+    let (entries, head) = read_history(path.as_ref()).unwrap();
+
+    // changes made outside history are not silently discarded
+    fs::write(path, &empty).unwrap();
+    let undo_after_edit = run(path, "undo", &[]);
+    let forced = run(path, "undo", &["--force"]);
+    let after_forced = read();
+
+    cleanup(path);
+
+    assert_eq!(states, vec![updated, added.clone(), empty.clone(), added.clone()]);
+    assert!(undo_past_start.is_err());
+    assert!(redo_after_change.is_err());
+
+    let descriptions: Vec<&str> = entries.iter().map(|e| e.description.as_str()).collect();
+    assert_eq!(descriptions, vec!["add-new \"a\"", "add-new \"b\""]);
+    assert_eq!(head, entries[1].id);
+
+    assert!(undo_after_edit.is_err());
+    assert!(forced.is_ok(), "{:?}", forced);
+    assert_eq!(after_forced, added);
+}
@@ -0,0 +1,144 @@
+#[cfg(test)]
+use std::{fs, path::{Path, PathBuf}};
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    layers::LayeredLas,
+    output::{render, ListedIdentity, OutputFormat},
+    profile::{expand_shared, registry_path, run_profile, ProfileRegistry},
+    search::LasQuery,
+    version::las_header,
+};
+
+#[cfg(test)]
+use crate::memmap::{map_shared, process_las};
+
+#[cfg(test)]
+fn store(identities: &str) -> String {
+    format!("{}{}", las_header(), identities)
+}
+
+#[test]
+fn test_layered_lookup() {
+    let personal = store("K:\"work\"\nU:\"bob\"\nE:\"bob@corp.com\"\n");
+    let team = store("K:\"work\"\nU:\"Team Bob\"\nE:\"team@corp.com\"\nK:\"deploy\"\nU:\"bot\"\nE:\"bot@corp.com\"\n");
+    let broken = String::from("K:\"no header\"\n");
+    let (personal_path, team_path, broken_path) = (Path::new("./me.sigrs"), Path::new("./team.sigrs"), Path::new("./broken.sigrs"));
+
+    let mut las = LayeredLas::new(personal_path, process_las(personal.as_bytes()).unwrap());
+    assert!(!las.is_layered());
+    las.push_shared(team_path, team.as_bytes());
+    las.push_shared(broken_path, broken.as_bytes());
+    // the personal store listed as shared too is only searched once
+    las.push_shared(personal_path, personal.as_bytes());
+    assert_eq!(las.layers.len(), 2);
+    assert!(las.is_layered());
+
+    // personal wins, shared fills in the rest
+    let work = las.lookup_id("work").unwrap();
+    assert_eq!(work.source, personal_path);
+    assert_eq!(work.identity.default_email(), Some(&b"bob@corp.com"[..]));
+    assert_eq!(las.lookup_id("deploy").unwrap().source, team_path);
+    assert!(las.lookup_id("nope").is_none());
+
+    let all: Vec<(&[u8], &Path)> = las.all().iter().map(|e| (e.key, e.source)).collect();
+    assert_eq!(all, vec![(&b"deploy"[..], team_path), (&b"work"[..], personal_path)]);
+
+    // shadowed identities can't be found through their values
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "list-find", "-E", "corp.com", "--contains", "--sort", "key"])
+        .unwrap();
+    let query = LasQuery::from_args(matches.subcommand().unwrap().1).unwrap();
+    let found: Vec<(&[u8], &Path)> = query.find(&las).iter().map(|f| (f.key, f.source)).collect();
+    assert_eq!(found, all);
+
+    let listed: Vec<ListedIdentity> = las.all().iter()
+        .map(|e| ListedIdentity::new(e.key, e.identity).with_store(e.source))
+        .collect();
+    let json: serde_json::Value = serde_json::from_str(&render(&OutputFormat::Json, &listed).unwrap()).unwrap();
+    assert_eq!(json[0]["store"], "./team.sigrs");
+    assert!(render(&OutputFormat::Csv, &listed).unwrap().starts_with("id,default_username,default_email,usernames,emails,url,role,organization,fingerprint,store\n"));
+    assert_eq!(
+        render(&OutputFormat::parse("{id} {store}").unwrap(), &listed).unwrap(),
+        "deploy ./team.sigrs\nwork ./me.sigrs\n"
+    );
+}
+
+#[test]
+fn test_shared_store_registry() {
+    let dir = "./TestLasShared";
+    fs::create_dir_all(format!("{}/team", dir)).unwrap();
+    fs::write(format!("{}/team/b.sigrs", dir), store("")).unwrap();
+    fs::write(format!("{}/team/a.sigrs", dir), store("")).unwrap();
+    fs::write(format!("{}/team/notes.txt", dir), "").unwrap();
+    fs::write(format!("{}/infra.sigrs", dir), store("")).unwrap();
+
+    let default_path = Path::new(dir).join("config.sigrs");
+    let registry = registry_path(&default_path);
+    let run = |argv: &[&str]| {
+        let matches = sigrs_command()
+            .try_get_matches_from(["sigrs", "profile"].iter().chain(argv))
+            .unwrap();
+        run_profile(matches.subcommand().unwrap().1, &registry, &default_path)
+    };
+
+    let add_dir = run(&["add-shared", "./TestLasShared/team"]);
+    let add_file = run(&["add-shared", "./TestLasShared/infra.sigrs"]);
+    let add_again = run(&["add-shared", "./TestLasShared/team"]);
+    let add_missing = run(&["add-shared", "./TestLasShared/nope"]);
+    let list = run(&["list"]);
+    let shared = ProfileRegistry::load(&registry).unwrap().shared;
+    let files = expand_shared(&shared);
+    let remove_dir = run(&["remove-shared", "./TestLasShared/team"]);
+    let remove_again = run(&["remove-shared", "./TestLasShared/team"]);
+    let after_remove = ProfileRegistry::load(&registry).unwrap().shared;
+
+    fs::remove_dir_all(dir).unwrap();
+
+    assert!(add_dir.is_ok(), "{:?}", add_dir);
+    assert!(add_file.is_ok(), "{:?}", add_file);
+    assert!(add_again.is_err());
+    assert!(add_missing.is_err());
+    assert!(list.is_ok());
+    assert_eq!(shared.len(), 2);
+    assert!(shared.iter().all(|p| p.is_absolute()));
+
+    // directories expand to their *.sigrs files by name, in registry order
+    let names: Vec<PathBuf> = files.iter().map(|p| PathBuf::from(p.file_name().unwrap())).collect();
+    assert_eq!(names, vec![PathBuf::from("a.sigrs"), PathBuf::from("b.sigrs"), PathBuf::from("infra.sigrs")]);
+
+    assert!(remove_dir.is_ok(), "{:?}", remove_dir);
+    assert!(remove_again.is_err());
+    assert_eq!(after_remove.len(), 1);
+}
+
+#[test]
+fn test_shared_store_older_version() {
+    let dir = "./TestLasSharedVersions";
+    fs::create_dir_all(dir).unwrap();
+    let v2 = "# File generated by signatu_rs. Do not manually edit this file. #\n#!las-version:2\nK:\"deploy\"\nU:\"bot\"\nE:\"bot@corp.com\"\n";
+    let newer = "# File generated by signatu_rs. Do not manually edit this file. #\n#!las-version:999\nK:\"x\"\nU:\"x\"\nE:\"x\"\n";
+    let (old_path, newer_path) = (Path::new(dir).join("old.sigrs"), Path::new(dir).join("newer.sigrs"));
+    fs::write(&old_path, v2).unwrap();
+    fs::write(&newer_path, newer).unwrap();
+
+    let old = unsafe { map_shared(old_path.clone()) };
+    let newer_err = unsafe { map_shared(newer_path) }.err();
+    let personal = store("K:\"work\"\nU:\"bob\"\nE:\"bob@corp.com\"\n");
+    let found = old.as_ref().ok().map(|old| {
+        let mut las = LayeredLas::new(Path::new("./me.sigrs"), process_las(personal.as_bytes()).unwrap());
+        las.push_shared(&old.source, old);
+        las.lookup_id("deploy").map(|e| e.source.to_path_buf())
+    });
+    let old_after = fs::read_to_string(&old_path).unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+
+    // readable without being rewritten
+    assert_eq!(found, Some(Some(old_path)));
+    assert_eq!(old_after, v2);
+    assert!(newer_err.is_some_and(|e| e.contains("Update sigrs")));
+}
@@ -0,0 +1,31 @@
+#[cfg(test)]
+use std::{fs, path::Path, time::Duration};
+
+#[cfg(test)]
+use crate::las::lock::{lock_las, lock_path, LockKind};
+
+#[test]
+fn test_lock_contention() {
+    let path = Path::new("./TestLasLock.sigrs");
+    let none = Duration::from_secs(0);
+
+    // readers share
+    let read_1 = lock_las(path, LockKind::Shared, none);
+    let read_2 = lock_las(path, LockKind::Shared, none);
+    let write_while_reading = lock_las(path, LockKind::Exclusive, Duration::from_millis(120));
+    drop(read_1);
+    drop(read_2);
+
+    // writers exclude everyone
+    let write = lock_las(path, LockKind::Exclusive, none);
+    let read_while_writing = lock_las(path, LockKind::Shared, none);
+    // released on drop
+    drop(write);
+    let read_after_writing = lock_las(path, LockKind::Shared, none).map(drop);
+
+    fs::remove_file(lock_path(path)).unwrap();
+
+    assert!(write_while_reading.is_err());
+    assert!(read_while_writing.unwrap_err().contains("--lock-timeout"));
+    assert!(read_after_writing.is_ok());
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    las_tests::test_storage::cleanup,
+    mailmap::{handle_export_mailmap, handle_import_mailmap, parse_mailmap, MailmapEntry},
+    storage::read_store,
+    version::las_header,
+};
+
+#[test]
+fn test_parse_mailmap() {
+    let content = "\
+# comment
+Jane Doe <jane@x.com>
+<jane@x.com> <JANE@old.com>   # trailing
+Jane Doe <jane@x.com> J <j@laptop>
+
+<bob@x.com> <bob@old.com>
+";
+    let entries = parse_mailmap(content).unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0], MailmapEntry {
+        proper_name: Some("Jane Doe".into()),
+        proper_email: None,
+        commit_name: None,
+        commit_email: "jane@x.com".into(),
+    });
+    assert_eq!(entries[1].commit_email, "JANE@old.com");
+    assert_eq!(entries[2].commit_name.as_deref(), Some("J"));
+    assert!(parse_mailmap("Jane Doe jane@x.com").is_err());
+}
+
+#[test]
+fn test_mailmap_round_trip() {
+    let path = "./TestLasMailmap.sigrs";
+    let mailmap = "./TestLasMailmap.mailmap";
+    let exported = "./TestLasMailmapExport.mailmap";
+    fs::write(path, las_header()).unwrap();
+    fs::write(mailmap, "\
+Jane Doe <jane@x.com>
+Jane Doe <jane@x.com> <jane@old.com>
+Jane Doe <jane@x.com> J <j@laptop>
+<bob@x.com> <bob@old.com>
+").unwrap();
+
+    let run = |argv: Vec<&str>| {
+        let matches = sigrs_command().try_get_matches_from(argv).unwrap();
+        match matches.subcommand() {
+            Some(("import-mailmap", m)) => handle_import_mailmap(m),
+            Some(("export-mailmap", m)) => unsafe { handle_export_mailmap(m) },
+            _ => unreachable!(),
+        }
+    };
+
+    let import = run(vec!["sigrs", "import-mailmap", mailmap, "--config-path", path]);
+    let imported = fs::read_to_string(path).unwrap();
+    let export = run(vec!["sigrs", "export-mailmap", "--config-path", path, "--output", exported]);
+    let exported_content = fs::read_to_string(exported).unwrap_or_default();
+    // importing the export again changes nothing
+    let reimport = run(vec!["sigrs", "import-mailmap", exported, "--config-path", path]);
+    let reimported = fs::read_to_string(path).unwrap();
+
+    cleanup(path);
+    let _ = fs::remove_file(mailmap);
+    let _ = fs::remove_file(exported);
+
+    assert_eq!(import, Ok(()));
+    let (_, identities) = read_store(&imported).unwrap();
+    assert_eq!(identities.len(), 1, "bob has no name && is skipped");
+    assert_eq!(identities[0].key, "jane-doe");
+    assert_eq!(identities[0].usernames, vec!["Jane Doe", "J"]);
+    assert_eq!(identities[0].emails, vec!["jane@x.com", "jane@old.com", "j@laptop"]);
+
+    assert_eq!(export, Ok(()));
+    assert_eq!(exported_content, "\
+# Generated by `sigrs export-mailmap`
+# jane-doe
+Jane Doe <jane@x.com>
+Jane Doe <jane@x.com> <jane@old.com>
+Jane Doe <jane@x.com> <j@laptop>
+Jane Doe <jane@x.com> J <jane@x.com>
+");
+    assert_eq!(reimport, Ok(()));
+    assert_eq!(reimported, imported);
+}
@@ -0,0 +1,85 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    las_tests::test_storage::cleanup,
+    merge::{handle_clone, handle_merge},
+    storage::{read_store, StoredIdentity},
+    version::las_header,
+};
+
+#[cfg(test)]
+fn run(path: &str, command: &str, extra: &[&str]) -> Result<(), String> {
+    let mut argv = vec!["sigrs", command, "--config-path", path];
+    argv.extend_from_slice(extra);
+    let matches = sigrs_command().try_get_matches_from(argv).unwrap();
+    match matches.subcommand() {
+        Some(("clone", m)) => handle_clone(m),
+        Some(("merge", m)) => handle_merge(m),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+fn identity(key: &str, usernames: &[&str], emails: &[&str], metadata: &[(&str, &str)]) -> StoredIdentity {
+    let owned = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+    StoredIdentity {
+        key: key.to_string(),
+        usernames: owned(usernames),
+        emails: owned(emails),
+        metadata: metadata.iter().map(|(f, v)| (f.to_string(), v.to_string())).collect(),
+        rules: vec![],
+    }
+}
+
+#[test]
+fn test_clone_merge() {
+    let path = "./TestLasMerge.sigrs";
+    fs::write(path, format!(
+        "{}# kept\nK:\"bob\"\nU:\"bob\"\nE:\"bob@x.com\"\nM:\"role\"\"Lead\"\nK:\"bob-2\"\nU:\"Bob Smith\"\"bob\"\nE:\"bob@y.com\"\"bob@x.com\"\nM:\"url\"\"https://bob.dev\"\"role\"\"Dev\"\nK:\"robert\"\nU:\"robert\"\nE:\"robert@z.com\"\n",
+        las_header()
+    )).unwrap();
+    let read = || fs::read_to_string(path).unwrap();
+    let identities = || read_store(&read()).unwrap().1;
+
+    let cloned = run(path, "clone", &["--identity", "robert", "--as", "rob"]);
+    let after_clone = identities();
+    let clone_taken = run(path, "clone", &["--identity", "bob", "--as", "rob"]);
+    let clone_missing = run(path, "clone", &["--identity", "nope", "--as", "x"]);
+
+    let merged = run(path, "merge", &["--into", "bob", "--from", "bob-2", "--from", "robert"]);
+    let after_merge = identities();
+    let content = read();
+
+    let into_itself = run(path, "merge", &["--into", "bob", "--from", "bob"]);
+    let from_missing = run(path, "merge", &["--into", "bob", "--from", "rob", "--from", "nope"]);
+    let after_errors = identities();
+
+    cleanup(path);
+
+    assert!(cloned.is_ok(), "{:?}", cloned);
+    assert_eq!(after_clone[3], identity("rob", &["robert"], &["robert@z.com"], &[]));
+    assert!(clone_taken.is_err());
+    assert!(clone_missing.is_err());
+
+    assert!(merged.is_ok(), "{:?}", merged);
+    assert_eq!(after_merge, vec![
+        identity(
+            "bob",
+            &["bob", "Bob Smith", "robert"],
+            &["bob@x.com", "bob@y.com", "robert@z.com"],
+            &[("url", "https://bob.dev"), ("role", "Lead")]
+        ),
+        identity("rob", &["robert"], &["robert@z.com"], &[]),
+    ]);
+    assert!(content.contains("# kept\n"));
+
+    assert!(into_itself.is_err());
+    // nothing is merged if any --from is missing
+    assert!(from_missing.is_err());
+    assert_eq!(after_errors, after_merge);
+}
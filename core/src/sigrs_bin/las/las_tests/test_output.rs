@@ -0,0 +1,96 @@
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    output::{render, ListedIdentity, OutputFormat},
+    version::las_header,
+};
+
+#[cfg(test)]
+use crate::memmap::process_las;
+
+/// Every identity in `content` rendered as `format`
+#[cfg(test)]
+fn render_all(content: &str, format: &str) -> Result<String, String> {
+    let format = OutputFormat::parse(format)?;
+    let las = process_las(content.as_bytes()).unwrap();
+    let identities = las.find_matches(|_, _| true);
+    let listed: Vec<ListedIdentity> = identities.iter().map(|(k, v)| ListedIdentity::new(k, v)).collect();
+    render(&format, &listed)
+}
+
+#[cfg(test)]
+fn content() -> String {
+    format!(
+        "{}K:\"work\"\nU:\"Bob, Jr\"\"bob\"\nE:\"bob@corp.com\"\"bob@x.com\"\nM:\"url\"\"https://x.com\"\nK:\"z\"\nU:\"\\\"Z\\\"\"\nE:\n",
+        las_header()
+    )
+}
+
+#[test]
+fn test_output_json_yaml() {
+    let json = render_all(&content(), "json").unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed[0]["id"], "work");
+    assert_eq!(parsed[0]["default_email"], "bob@corp.com");
+    assert_eq!(parsed[0]["usernames"][1], "bob");
+    assert_eq!(parsed[0]["metadata"]["url"], "https://x.com");
+    assert_eq!(parsed[1]["default_email"], serde_json::Value::Null);
+    assert!(parsed[1].get("metadata").is_none());
+
+    let yaml = render_all(&content(), "yaml").unwrap();
+    assert_eq!(yaml, "\
+- id: \"work\"
+  default_username: \"Bob, Jr\"
+  default_email: \"bob@corp.com\"
+  usernames:
+    - \"Bob, Jr\"
+    - \"bob\"
+  emails:
+    - \"bob@corp.com\"
+    - \"bob@x.com\"
+  metadata:
+    \"url\": \"https://x.com\"
+- id: \"z\"
+  default_username: \"\\\"Z\\\"\"
+  default_email: null
+  usernames:
+    - \"\\\"Z\\\"\"
+  emails: []
+");
+    assert_eq!(render_all(&las_header(), "yaml").unwrap(), "[]\n");
+}
+
+#[test]
+fn test_output_csv_tsv() {
+    let header = "id,default_username,default_email,usernames,emails,url,role,organization,fingerprint\n";
+    assert_eq!(render_all(&content(), "csv").unwrap(), format!(
+        "{}work,\"Bob, Jr\",bob@corp.com,\"Bob, Jr;bob\",bob@corp.com;bob@x.com,https://x.com,,,\nz,\"\"\"Z\"\"\",,\"\"\"Z\"\"\",,,,,\n",
+        header
+    ));
+
+    let tsv = render_all(&content(), "tsv").unwrap();
+    let lines: Vec<&str> = tsv.lines().collect();
+    assert_eq!(lines[0], header.trim_end().replace(',', "\t"));
+    assert_eq!(lines[1], "work\tBob, Jr\tbob@corp.com\tBob, Jr;bob\tbob@corp.com;bob@x.com\thttps://x.com\t\t\t");
+    assert_eq!(lines.len(), 3);
+}
+
+#[test]
+fn test_output_template() {
+    assert_eq!(
+        render_all(&content(), "{id}\\t{default_email} {{{usernames}}}").unwrap(),
+        "work\tbob@corp.com {Bob, Jr;bob}\nz\t {\"Z\"}\n"
+    );
+    assert!(OutputFormat::parse("{nope}").is_err());
+    assert!(OutputFormat::parse("{id").is_err());
+    assert!(OutputFormat::parse("id}").is_err());
+    assert!(OutputFormat::parse("xml").is_err());
+
+    // parsed by clap, && not allowed with the human readable --verbose
+    let parsed = sigrs_command().try_get_matches_from(["sigrs", "list-all", "--format", "{id}"]);
+    assert!(parsed.is_ok());
+    assert!(sigrs_command().try_get_matches_from(["sigrs", "list-all", "--format", "{x}"]).is_err());
+    assert!(sigrs_command().try_get_matches_from(["sigrs", "list-all", "--format", "csv", "--verbose"]).is_err());
+}
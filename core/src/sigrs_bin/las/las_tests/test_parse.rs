@@ -0,0 +1,137 @@
+#[cfg(test)]
+use crate::las::version::las_header;
+
+#[cfg(test)]
+use crate::memmap::{process_las, LasParseError, LasParseErrorKind};
+
+/// Prefixes `body` with the current LAS header (2 lines)
+#[cfg(test)]
+fn with_header(body: &str) -> String {
+    format!("{}{}", las_header(), body)
+}
+
+/// Parse `body` (after header) && return the error, panics if parsing succeeded
+#[cfg(test)]
+fn parse_err(body: &str) -> LasParseError {
+    let content = with_header(body);
+    match process_las(content.as_bytes()) {
+        Ok(las) => panic!("expected parse error, parsed {} identities", las.identities.len()),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn test_parse_valid() {
+    let content = with_header("K:\"a\"\nU:\"u1\"\"u2\"\nE:\"e1\"\n\n# comment\nK:\"b\"\nU:\"u\"\nE:\"e\"\n");
+    let las = process_las(content.as_bytes()).unwrap();
+    assert_eq!(las.identities.len(), 2);
+}
+
+#[test]
+fn test_parse_no_trailing_newline() {
+    let content = with_header("K:\"a\"\nU:\"u\"\nE:\"e1\"\"e2\"");
+    let las = process_las(content.as_bytes()).unwrap();
+    let (_k, identity) = las.lookup_id(&String::from("a")).expect("last line was dropped");
+    assert_eq!(identity.emails.len(), 2);
+}
+
+#[test]
+fn test_parse_crlf() {
+    let content = with_header("K:\"a\"\r\nU:\"u\"\r\nE:\"e\"\r\n");
+    let las = process_las(content.as_bytes()).unwrap();
+    let (_k, identity) = las.lookup_id(&String::from("a")).unwrap();
+    assert_eq!(identity.emails[0].as_ref(), b"e");
+}
+
+#[test]
+fn test_parse_unknown_line_type() {
+    let e = parse_err("K:\"a\"\nU:\"u\"\nE:\"e\"\nX:\"oops\"\n");
+    // 2 header lines
+    assert_eq!(e, LasParseError::new(6, 1, LasParseErrorKind::UnknownLineType('X')));
+}
+
+#[test]
+fn test_parse_missing_colon() {
+    let e = parse_err("K\"a\"\n");
+    assert_eq!(e, LasParseError::new(3, 2, LasParseErrorKind::MissingColon('K')));
+}
+
+#[test]
+fn test_parse_unterminated_value() {
+    // used to loop past the end of the line
+    let e = parse_err("K:\"a\"\nU:\"u1\"\"u2\nE:\"e\"\n");
+    assert_eq!(e, LasParseError::new(4, 7, LasParseErrorKind::UnterminatedValue));
+
+    let e = parse_err("K:\"a");
+    assert_eq!(e, LasParseError::new(3, 3, LasParseErrorKind::UnterminatedValue));
+}
+
+#[test]
+fn test_parse_garbage_between_values() {
+    let e = parse_err("K:\"a\"\nU:\"u1\" \"u2\"\nE:\"e\"\n");
+    assert_eq!(e, LasParseError::new(4, 7, LasParseErrorKind::ExpectedQuote(' ')));
+}
+
+#[test]
+fn test_parse_key_value_count() {
+    let e = parse_err("K:\"a\"\"b\"\nU:\"u\"\nE:\"e\"\n");
+    assert_eq!(e, LasParseError::new(3, 3, LasParseErrorKind::KeyValueCount(2)));
+}
+
+#[test]
+fn test_parse_duplicate_key() {
+    let e = parse_err("K:\"a\"\nU:\"u\"\nE:\"e\"\nK:\"a\"\nU:\"u\"\nE:\"e\"\n");
+    assert_eq!(e, LasParseError::new(6, 3, LasParseErrorKind::DuplicateKey(String::from("a"))));
+}
+
+#[test]
+fn test_parse_out_of_order() {
+    let e = parse_err("U:\"u\"\nE:\"e\"\n");
+    assert_eq!(e, LasParseError::new(3, 1, LasParseErrorKind::UnexpectedLine { expected: 'K', found: 'U' }));
+
+    let e = parse_err("K:\"a\"\nE:\"e\"\n");
+    assert_eq!(e, LasParseError::new(4, 1, LasParseErrorKind::UnexpectedLine { expected: 'U', found: 'E' }));
+}
+
+#[test]
+fn test_parse_truncated_identity() {
+    let e = parse_err("K:\"a\"\nU:\"u\"\n");
+    assert_eq!(e, LasParseError::new(5, 1, LasParseErrorKind::UnexpectedEof('E')));
+}
+
+#[test]
+fn test_parse_error_message() {
+    let e = parse_err("K:\"a\"\nU:\"u\"\nE:\"e\"\nK:\"a\"\nU:\"u\"\nE:\"e\"\n");
+    assert_eq!(
+        e.to_string(),
+        "config.sigrs line 6, column 3: identity \"a\" is defined more than once"
+    );
+}
+
+#[test]
+fn test_parse_metadata() {
+    let content = with_header(
+        "K:\"a\"\nU:\"u\"\nE:\"e\"\nM:\"url\"\"https://x.com\"\"role\"\"Maintainer\"\nK:\"b\"\nU:\"u\"\nE:\"e\"\n"
+    );
+    let las = process_las(content.as_bytes()).unwrap();
+    let (_k, a) = las.lookup_id(&String::from("a")).unwrap();
+    assert_eq!(a.get_metadata("url"), Some(&b"https://x.com"[..]));
+    assert_eq!(a.author_meta().role.as_deref(), Some("Maintainer"));
+    assert_eq!(a.get_metadata("fingerprint"), None);
+
+    let (_k, b) = las.lookup_id(&String::from("b")).unwrap();
+    assert!(b.metadata.is_empty());
+}
+
+#[test]
+fn test_parse_metadata_errors() {
+    // only directly after `E:`
+    let e = parse_err("K:\"a\"\nU:\"u\"\nM:\"url\"\"x\"\nE:\"e\"\n");
+    assert_eq!(e, LasParseError::new(5, 1, LasParseErrorKind::UnexpectedLine { expected: 'E', found: 'M' }));
+
+    let e = parse_err("K:\"a\"\nU:\"u\"\nE:\"e\"\nM:\"url\"\"x\"\nM:\"role\"\"y\"\n");
+    assert_eq!(e, LasParseError::new(7, 1, LasParseErrorKind::UnexpectedLine { expected: 'K', found: 'M' }));
+
+    let e = parse_err("K:\"a\"\nU:\"u\"\nE:\"e\"\nM:\"url\"\n");
+    assert_eq!(e, LasParseError::new(6, 3, LasParseErrorKind::MetadataPairs(1)));
+}
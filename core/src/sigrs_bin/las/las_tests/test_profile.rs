@@ -0,0 +1,112 @@
+#[cfg(test)]
+use std::{ffi::OsString, fs, path::Path};
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::profile::{registry_path, run_profile, ProfileRegistry};
+
+#[cfg(test)]
+use crate::utils::{extract_config_path, ENV_CONFIG_PATH};
+
+/// Runs `sigrs profile <argv>` against the registry in `dir`
+#[cfg(test)]
+fn run(dir: &str, argv: &[&str]) -> Result<(), String> {
+    let default_path = Path::new(dir).join("config.sigrs");
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "profile"].iter().chain(argv))
+        .unwrap();
+    let Some(("profile", sub_matches)) = matches.subcommand() else {
+        panic!("profile not matched");
+    };
+    run_profile(sub_matches, &registry_path(&default_path), &default_path)
+}
+
+#[test]
+fn test_profile_registry() {
+    let dir = "./TestLasProfiles";
+    fs::create_dir_all(dir).unwrap();
+    let registry = || ProfileRegistry::load(&Path::new(dir).join("profiles.toml")).unwrap();
+    let default_path = OsString::from("./TestLasProfiles/config.sigrs");
+
+    let add_work = run(dir, &["add", "work"]);
+    let add_personal = run(dir, &["add", "personal", "./TestLasProfiles/nested/personal.sigrs"]);
+    let add_again = run(dir, &["add", "work"]);
+    let add_default = run(dir, &["add", "default"]);
+    let add_bad_name = run(dir, &["add", "a/b"]);
+    let work_created = Path::new(dir).join("work.sigrs").is_file();
+    let personal_created = Path::new(dir).join("nested/personal.sigrs").is_file();
+
+    let use_work = run(dir, &["use", "work"]);
+    let active_work = registry();
+    let use_unknown = run(dir, &["use", "nope"]);
+
+    let remove_work = run(dir, &["remove", "work"]);
+    let after_remove = registry();
+    let use_default = run(dir, &["use", "default"]);
+    let remove_unknown = run(dir, &["remove", "work"]);
+    let list = run(dir, &["list"]);
+
+    fs::remove_dir_all(dir).unwrap();
+
+    assert!(add_work.is_ok(), "{:?}", add_work);
+    assert!(add_personal.is_ok(), "{:?}", add_personal);
+    assert!(add_again.is_err());
+    assert!(add_default.is_err());
+    assert!(add_bad_name.is_err());
+    assert!(work_created);
+    assert!(personal_created);
+
+    assert!(use_work.is_ok(), "{:?}", use_work);
+    assert_eq!(active_work.active.as_deref(), Some("work"));
+    assert_eq!(
+        active_work.resolve(None, default_path.clone()).unwrap(),
+        Path::new(dir).join("work.sigrs").into_os_string()
+    );
+    // stored absolute
+    assert!(active_work.profiles["personal"].is_absolute());
+    assert_eq!(active_work.resolve(Some("default"), default_path.clone()).unwrap(), default_path);
+    assert!(active_work.resolve(Some("nope"), default_path.clone()).is_err());
+    assert!(use_unknown.is_err());
+
+    // removing the active profile falls back to default, files are kept
+    assert!(remove_work.is_ok(), "{:?}", remove_work);
+    assert_eq!(after_remove.active, None);
+    assert!(!after_remove.profiles.contains_key("work"));
+    assert_eq!(after_remove.resolve(None, default_path.clone()).unwrap(), default_path);
+    assert!(use_default.is_ok());
+    assert!(remove_unknown.is_err());
+    assert!(list.is_ok());
+}
+
+#[test]
+fn test_config_path_wins_over_profile() {
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "--profile", "work", "list-all", "--config-path", "./x.sigrs"])
+        .unwrap();
+    let Some(("list-all", sub_matches)) = matches.subcommand() else {
+        panic!("list-all not matched");
+    };
+    // global, so visible to every subcommand
+    assert_eq!(sub_matches.get_one::<String>("profile").map(String::as_str), Some("work"));
+    assert_eq!(extract_config_path(sub_matches).unwrap(), OsString::from("./x.sigrs"));
+}
+
+#[test]
+fn test_config_path_from_env() {
+    let parse = |argv: &[&str]| sigrs_command().try_get_matches_from(argv).unwrap();
+    let plain = parse(&["sigrs", "list-all"]);
+    let flag = parse(&["sigrs", "list-all", "--config-path", "./flag.sigrs"]);
+
+    std::env::set_var(ENV_CONFIG_PATH, "./env.sigrs");
+    let from_env = extract_config_path(plain.subcommand().unwrap().1);
+    let from_flag = extract_config_path(flag.subcommand().unwrap().1);
+    std::env::set_var(ENV_CONFIG_PATH, "");
+    let empty_ignored = extract_config_path(plain.subcommand().unwrap().1);
+    std::env::remove_var(ENV_CONFIG_PATH);
+
+    assert_eq!(from_env.unwrap(), OsString::from("./env.sigrs"));
+    assert_eq!(from_flag.unwrap(), OsString::from("./flag.sigrs"));
+    assert_ne!(empty_ignored, Ok(OsString::from("")));
+}
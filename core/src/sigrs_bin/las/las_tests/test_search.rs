@@ -0,0 +1,101 @@
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use std::path::Path;
+
+#[cfg(test)]
+use crate::las::{layers::LayeredLas, search::LasQuery, version::las_header};
+
+#[cfg(test)]
+use crate::memmap::process_las;
+
+/// (key, matches as `field:value`) of each identity `sigrs list-find <argv>` lists for `content`
+#[cfg(test)]
+fn find_ranked(content: &str, argv: &[&str]) -> Result<Vec<(String, Vec<String>)>, String> {
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "list-find"].iter().chain(argv))
+        .map_err(|e| e.to_string())?;
+    let Some(("list-find", sub_matches)) = matches.subcommand() else {
+        panic!("list-find not matched");
+    };
+    let query = LasQuery::from_args(sub_matches)?;
+    let las = LayeredLas::new(Path::new("./TestSearch.sigrs"), process_las(content.as_bytes()).unwrap());
+    Ok(query.find(&las)
+        .into_iter()
+        .map(|f| (
+            String::from_utf8_lossy(f.key).into_owned(),
+            f.matches.iter().map(|m| format!("{}:{}", m.field.name(), m.value)).collect(),
+        ))
+        .collect())
+}
+
+/// Keys only, in key order
+#[cfg(test)]
+fn find(content: &str, argv: &[&str]) -> Result<Vec<String>, String> {
+    let mut argv = argv.to_vec();
+    argv.extend(["--sort", "key"]);
+    Ok(find_ranked(content, &argv)?.into_iter().map(|(k, _)| k).collect())
+}
+
+#[test]
+fn test_list_find_modes() {
+    let content = format!(
+        "{}K:\"work\"\nU:\"Bob Smith\"\nE:\"bob@corp.com\"\"bob@gmail.com\"\nK:\"personal\"\nU:\"bob\"\nE:\"Bob@Proton.me\"\nK:\"other\"\nU:\"alice\"\nE:\"alice@gmail.com\"\n",
+        las_header()
+    );
+
+    // exact by default
+    assert!(find(&content, &["-E", "gmail.com"]).unwrap().is_empty());
+    assert_eq!(find(&content, &["-E", "bob@gmail.com"]).unwrap(), vec!["work"]);
+
+    assert_eq!(find(&content, &["-E", "gmail.com", "--contains"]).unwrap(), vec!["other", "work"]);
+    assert_eq!(find(&content, &["-E", "bob@proton.me", "-i"]).unwrap(), vec!["personal"]);
+    assert_eq!(find(&content, &["-U", "BOB", "--contains", "--ignore-case"]).unwrap(), vec!["personal", "work"]);
+    assert_eq!(find(&content, &["-E", "@(gmail|proton)\\.", "--regex"]).unwrap(), vec!["other", "work"]);
+    assert_eq!(find(&content, &["-E", "^bob@", "--regex", "-i"]).unwrap(), vec!["personal", "work"]);
+
+    // keys, any vs all
+    assert_eq!(find(&content, &["-K", "work", "-U", "alice"]).unwrap(), vec!["other", "work"]);
+    assert_eq!(
+        find(&content, &["-K", "o", "-E", "gmail", "--contains", "--all"]).unwrap(),
+        vec!["other", "work"]
+    );
+    assert_eq!(
+        find(&content, &["-K", "o", "-U", "Bob", "-E", "gmail", "--contains", "--all"]).unwrap(),
+        vec!["work"]
+    );
+
+    assert!(find(&content, &["-E", "(", "--regex"]).is_err());
+    assert!(find(&content, &["-E", "x", "--regex", "--contains"]).is_err());
+}
+
+#[test]
+fn test_list_find_ranking() {
+    let content = format!(
+        "{}K:\"a\"\nU:\"bob\"\nE:\"x@y.com\"\nK:\"b\"\nU:\"bob\"\"Bobby\"\nE:\"bob@x.com\"\"bob@y.com\"\nK:\"c\"\nU:\"alice\"\nE:\"bob@z.com\"\n",
+        las_header()
+    );
+    let args = ["-U", "bob", "-E", "bob", "--contains", "-i"];
+
+    // listed once each, most matched values first, ties in key order
+    let ranked = find_ranked(&content, &args).unwrap();
+    assert_eq!(ranked, vec![
+        ("b".to_string(), vec![
+            "username:bob".to_string(), "username:Bobby".to_string(),
+            "email:bob@x.com".to_string(), "email:bob@y.com".to_string(),
+        ]),
+        ("a".to_string(), vec!["username:bob".to_string()]),
+        ("c".to_string(), vec!["email:bob@z.com".to_string()]),
+    ]);
+
+    let mut by_key = args.to_vec();
+    by_key.extend(["--sort", "key", "--limit", "2"]);
+    let keys: Vec<String> = find_ranked(&content, &by_key).unwrap().into_iter().map(|(k, _)| k).collect();
+    assert_eq!(keys, vec!["a", "b"]);
+
+    let mut limited = args.to_vec();
+    limited.extend(["--limit", "1"]);
+    assert_eq!(find_ranked(&content, &limited).unwrap().len(), 1);
+    assert!(find_ranked(&content, &["-U", "bob", "--sort", "date"]).is_err());
+}
@@ -0,0 +1,252 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use std::os::unix::fs::PermissionsExt;
+
+#[cfg(test)]
+use crate::las::storage::{handle_add_new, handle_update};
+
+#[cfg(test)]
+use crate::las::delete::handle_delete;
+
+#[cfg(test)]
+use crate::las::history::history_dir;
+
+#[cfg(test)]
+use crate::las::lock::lock_path;
+
+#[cfg(test)]
+use crate::las::version::las_header;
+
+#[cfg(test)]
+use crate::memmap::process_las;
+
+/// Runs `sigrs update --config-path <path> <extra>`
+#[cfg(test)]
+fn run_update(path: &str, extra: &[&str]) -> Result<(), String> {
+    let mut argv = vec!["sigrs", "update", "--config-path", path];
+    argv.extend_from_slice(extra);
+    let matches = sigrs_command().try_get_matches_from(argv).unwrap();
+    let Some(("update", sub_matches)) = matches.subcommand() else {
+        panic!("update not matched");
+    };
+    handle_update(sub_matches)
+}
+
+/// Removes config.sigrs at `path`, && the lock file/history sigrs created next to it
+#[cfg(test)]
+pub fn cleanup(path: &str) {
+    fs::remove_file(path).unwrap();
+    fs::remove_file(lock_path(path.as_ref())).unwrap();
+    fs::remove_dir_all(history_dir(path.as_ref())).unwrap();
+}
+
+#[test]
+fn test_update_set_defaults() {
+    let path = "./TestUpdateSetDefaults.sigrs";
+    fs::write(path, format!(
+        "{}K:\"a\"\nU:\"u1\"\"u2\"\"u3\"\nE:\"e1\"\"e2\"\n",
+        las_header()
+    )).unwrap();
+
+    let reorder = run_update(path, &[
+        "--identity", "a", "--set-default-username", "u3", "--set-default-email", "e2"
+    ]);
+    let after_reorder = fs::read(path).unwrap();
+
+    // new value can be added && made default in one go
+    let add_default = run_update(path, &[
+        "--identity", "a", "--au", "u4", "--set-default-username", "u4"
+    ]);
+    let after_add = fs::read(path).unwrap();
+
+    let missing = run_update(path, &["--identity", "a", "--set-default-email", "nope"]);
+    let after_missing = fs::read(path).unwrap();
+
+    cleanup(path);
+
+    assert!(reorder.is_ok(), "{:?}", reorder);
+    let las = process_las(&after_reorder).unwrap();
+    let (_, identity) = las.lookup_id(&String::from("a")).unwrap();
+    assert_eq!(identity.default_username(), Some(&b"u3"[..]));
+    assert_eq!(identity.default_email(), Some(&b"e2"[..]));
+    // rest keep their order
+    assert_eq!(identity.usernames, vec![&b"u3"[..], &b"u1"[..], &b"u2"[..]]);
+    assert_eq!(identity.emails, vec![&b"e2"[..], &b"e1"[..]]);
+
+    assert!(add_default.is_ok(), "{:?}", add_default);
+    let las = process_las(&after_add).unwrap();
+    let (_, identity) = las.lookup_id(&String::from("a")).unwrap();
+    assert_eq!(identity.usernames, vec![&b"u4"[..], &b"u3"[..], &b"u1"[..], &b"u2"[..]]);
+
+    // unknown default is an error && leaves the file untouched
+    assert!(missing.is_err());
+    assert_eq!(after_missing, after_add);
+}
+
+#[test]
+fn test_update_metadata() {
+    let path = "./TestUpdateMetadata.sigrs";
+    fs::write(path, format!(
+        "{}K:\"a\"\nU:\"u\"\nE:\"e\"\nK:\"b\"\nU:\"u\"\nE:\"e\"\nM:\"role\"\"Dev\"\n",
+        las_header()
+    )).unwrap();
+
+    let set = run_update(path, &["--identity", "a", "--role", "Lead", "--url", "https://x.com"]);
+    let after_set = fs::read_to_string(path).unwrap();
+
+    let clear = run_update(path, &["--identity", "a", "--clear-metadata", "url", "--clear-metadata", "role"]);
+    let after_clear = fs::read_to_string(path).unwrap();
+
+    cleanup(path);
+
+    assert!(set.is_ok(), "{:?}", set);
+    // written in METADATA_FIELDS order, "b" untouched
+    assert_eq!(after_set, format!(
+        "{}K:\"a\"\nU:\"u\"\nE:\"e\"\nM:\"url\"\"https://x.com\"\"role\"\"Lead\"\nK:\"b\"\nU:\"u\"\nE:\"e\"\nM:\"role\"\"Dev\"\n",
+        las_header()
+    ));
+
+    assert!(clear.is_ok(), "{:?}", clear);
+    // empty `M:` line is dropped
+    assert_eq!(after_clear, format!(
+        "{}K:\"a\"\nU:\"u\"\nE:\"e\"\nK:\"b\"\nU:\"u\"\nE:\"e\"\nM:\"role\"\"Dev\"\n",
+        las_header()
+    ));
+}
+
+#[test]
+fn test_add_new_atomic_write() {
+    let dir = "./TestAddNewAtomicWrite";
+    let path = "./TestAddNewAtomicWrite/config.sigrs";
+    fs::create_dir_all(dir).unwrap();
+    // last line missing its newline, as after a hand edit
+    fs::write(path, format!("{}K:\"a\"\nU:\"u\"\nE:\"e\"", las_header())).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "add-new", "--config-path", path, "--identity", "b", "-U", "u", "-E", "e@x.com"])
+        .unwrap();
+    let Some(("add-new", sub_matches)) = matches.subcommand() else {
+        panic!("add-new not matched");
+    };
+    let res = handle_add_new(sub_matches);
+
+    let content = fs::read_to_string(path).unwrap();
+    let mode = fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    let leftover = fs::read_dir(dir).unwrap().count();
+
+    fs::remove_dir_all(dir).unwrap();
+
+    assert!(res.is_ok(), "{:?}", res);
+    assert_eq!(content, format!("{}K:\"a\"\nU:\"u\"\nE:\"e\"\nK:\"b\"\nU:\"u\"\nE:\"e@x.com\"\n", las_header()));
+    assert_eq!(mode, 0o600);
+    // only config.sigrs, its lock file && history, no temp files
+    assert_eq!(leftover, 3);
+}
+
+#[test]
+fn test_update_keeps_symlink() {
+    let dir = "./TestUpdateKeepsSymlink";
+    let target = "./TestUpdateKeepsSymlink/dotfiles.sigrs";
+    let link = "./TestUpdateKeepsSymlink/config.sigrs";
+    fs::create_dir_all(dir).unwrap();
+    fs::write(target, format!("{}K:\"a\"\nU:\"u\"\nE:\"e\"\n", las_header())).unwrap();
+    std::os::unix::fs::symlink("dotfiles.sigrs", link).unwrap();
+
+    let res = run_update(link, &["--identity", "a", "--au", "u2"]);
+    let still_link = fs::symlink_metadata(link).unwrap().file_type().is_symlink();
+    let content = fs::read_to_string(target).unwrap();
+
+    fs::remove_dir_all(dir).unwrap();
+
+    assert!(res.is_ok(), "{:?}", res);
+    assert!(still_link);
+    assert_eq!(content, format!("{}K:\"a\"\nU:\"u\"\"u2\"\nE:\"e\"\n", las_header()));
+}
+
+#[test]
+fn test_update_change_in_place() {
+    let path = "./TestUpdateChangeInPlace.sigrs";
+    fs::write(path, format!(
+        "{}K:\"a\"\nU:\"bob\"\"rob\"\nE:\"bob@x.com\"\"b@x.com\"\"ob@x.com\"\n",
+        las_header()
+    )).unwrap();
+    let identity = || {
+        let content = fs::read(path).unwrap();
+        let las = process_las(&content).unwrap();
+        let (_, identity) = las.lookup_id(&String::from("a")).unwrap();
+        let text = |values: &Vec<std::borrow::Cow<[u8]>>| {
+            values.iter().map(|v| String::from_utf8_lossy(v).into_owned()).collect::<Vec<String>>()
+        };
+        (text(&identity.usernames), text(&identity.emails))
+    };
+
+    // substrings of other values, only the exact value goes
+    let remove = run_update(path, &["--identity", "a", "--re", "b@x.com"]);
+    let after_remove = identity();
+
+    // the default stays the default
+    let change = run_update(path, &[
+        "--identity", "a", "--cu", "bob", "Bob Smith", "--ce", "bob@x.com", "bob@y.com", "--ce", "ob@x.com", "o@x.com"
+    ]);
+    let after_change = identity();
+
+    let missing = run_update(path, &["--identity", "a", "--cu", "nope", "x"]);
+    let taken = run_update(path, &["--identity", "a", "--cu", "rob", "Bob Smith"]);
+    let invalid = run_update(path, &["--identity", "a", "--ce", "o@x.com", "not an email"]);
+    let after_errors = identity();
+
+    cleanup(path);
+
+    assert!(remove.is_ok(), "{:?}", remove);
+    assert_eq!(after_remove.1, vec!["bob@x.com", "ob@x.com"]);
+
+    assert!(change.is_ok(), "{:?}", change);
+    assert_eq!(after_change.0, vec!["Bob Smith", "rob"]);
+    assert_eq!(after_change.1, vec!["bob@y.com", "o@x.com"]);
+
+    assert!(missing.is_err());
+    assert!(taken.is_err());
+    assert!(invalid.is_err());
+    assert_eq!(after_errors, after_change);
+}
+
+#[test]
+fn test_update_delete_errors_not_panics() {
+    let path = "./TestUpdateDeleteErrors.sigrs";
+    fs::write(path, format!(
+        "{}K:\"a\"\nU:\"u\"\nE:\"e\"\nK:\"b\"\nU:\"u\"\nE:\"e\"\n",
+        las_header()
+    )).unwrap();
+    let run_delete = |key: &str| {
+        let matches = sigrs_command()
+            .try_get_matches_from(["sigrs", "delete", "--config-path", path, "--identity", key])
+            .unwrap();
+        handle_delete(matches.subcommand().unwrap().1)
+    };
+
+    // would otherwise write two `K:"b"` lines
+    let rename_taken = run_update(path, &["--identity", "a", "--change-identity", "b"]);
+    let rename = run_update(path, &["--identity", "a", "--change-identity", "c"]);
+    let after_rename = fs::read_to_string(path).unwrap();
+
+    let truncated = format!("{}K:\"a\"\nU:\"u\"\n", las_header());
+    fs::write(path, &truncated).unwrap();
+    let update_truncated = run_update(path, &["--identity", "a", "--au", "x"]);
+    let delete_truncated = run_delete("a");
+    let after_truncated = fs::read_to_string(path).unwrap();
+
+    cleanup(path);
+
+    assert!(rename_taken.is_err());
+    assert!(rename.is_ok(), "{:?}", rename);
+    assert_eq!(after_rename, format!("{}K:\"c\"\nU:\"u\"\nE:\"e\"\nK:\"b\"\nU:\"u\"\nE:\"e\"\n", las_header()));
+    assert!(update_truncated.is_err_and(|e| e.contains("sigrs doctor")));
+    assert!(delete_truncated.is_err_and(|e| e.contains("sigrs doctor")));
+    assert_eq!(after_truncated, truncated);
+}
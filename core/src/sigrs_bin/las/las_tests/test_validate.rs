@@ -0,0 +1,100 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::las::{
+    las_tests::test_storage::cleanup,
+    storage::{handle_add_new, handle_update, read_store},
+    validate::{check_email, normalize_value, ValueKind},
+    version::las_header,
+};
+
+/// Runs `sigrs <command> --config-path <path> <extra>`
+#[cfg(test)]
+fn run(path: &str, command: &str, extra: &[&str]) -> Result<(), String> {
+    let mut argv = vec!["sigrs", command, "--config-path", path];
+    argv.extend_from_slice(extra);
+    let matches = sigrs_command().try_get_matches_from(argv).unwrap();
+    match matches.subcommand() {
+        Some(("add-new", m)) => handle_add_new(m),
+        Some(("update", m)) => handle_update(m),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_check_email() {
+    for valid in [
+        "bob@x.com",
+        "bob.smith+tag@mail.x.co",
+        "\"bob smith\"@x.com",
+        "\"b@b\"@x.com",
+        "bob@[192.168.0.1]",
+        "josé@exämple.com",
+    ] {
+        assert!(check_email(valid).is_ok(), "{} should be valid", valid);
+    }
+    for invalid in [
+        "bob",
+        "@x.com",
+        "bob@",
+        "bob..smith@x.com",
+        ".bob@x.com",
+        "bob@x.com.",
+        "bob smith@x.com",
+        "Bob <bob@x.com>",
+        "bob@x@y.com",
+        "\"bo\"b\"@x.com",
+    ] {
+        assert!(check_email(invalid).is_err(), "{} should be invalid", invalid);
+    }
+}
+
+#[test]
+fn test_normalize_value() {
+    assert_eq!(normalize_value(ValueKind::Username, "  Bob Smith \t").unwrap(), "Bob Smith");
+    // e + combining acute becomes the single code point
+    assert_eq!(normalize_value(ValueKind::Username, "Rene\u{301}").unwrap(), "Ren\u{e9}");
+    assert_eq!(normalize_value(ValueKind::Email, " bob@x.com\n").unwrap(), "bob@x.com");
+
+    assert!(normalize_value(ValueKind::Username, "   ").is_err());
+    assert!(normalize_value(ValueKind::Username, "bob\nsmith").is_err());
+    let err = normalize_value(ValueKind::Email, "not an email").unwrap_err();
+    assert!(err.contains("--no-validate"), "{}", err);
+}
+
+#[test]
+fn test_validate_add_new_update() {
+    let path = "./TestLasValidate.sigrs";
+    fs::write(path, las_header()).unwrap();
+    let identity = || {
+        let content = fs::read_to_string(path).unwrap();
+        read_store(&content).unwrap().1.remove(0)
+    };
+
+    let rejected = run(path, "add-new", &["--identity", "a", "-U", "bob", "-E", "bob.x.com"]);
+    let nothing_written = fs::read_to_string(path).unwrap();
+
+    run(path, "add-new", &["--identity", "a", "-U", " Rene\u{301} ", "-E", " bob@x.com "]).unwrap();
+    let added = identity();
+
+    let rejected_update = run(path, "update", &["--identity", "a", "--ae", "bob@"]);
+    run(path, "update", &["--identity", "a", "--ae", "not an email", "--no-validate"]).unwrap();
+    // matched against the stored, normalized value
+    run(path, "update", &["--identity", "a", "--ru", "Rene\u{301}", "--au", "Bob"]).unwrap();
+    run(path, "update", &["--identity", "a", "--de", "not an email"]).unwrap();
+    let updated = identity();
+
+    cleanup(path);
+
+    assert!(rejected.is_err());
+    assert_eq!(nothing_written, las_header());
+    assert_eq!(added.usernames, vec!["Ren\u{e9}"]);
+    assert_eq!(added.emails, vec!["bob@x.com"]);
+    assert!(rejected_update.is_err());
+    assert_eq!(updated.usernames, vec!["Bob"]);
+    assert_eq!(updated.emails, vec!["not an email", "bob@x.com"]);
+}
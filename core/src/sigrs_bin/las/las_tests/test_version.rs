@@ -0,0 +1,125 @@
+#[cfg(test)]
+use std::{
+    fs,
+    path::PathBuf,
+    str::FromStr
+};
+
+#[cfg(test)]
+use crate::las::version::{
+    LAS_VERSION,
+    LAS_VERSION_PREFIX,
+    las_header,
+    read_version,
+    check_version,
+    migrate_content,
+    ensure_current_version,
+};
+
+#[cfg(test)]
+use crate::memmap::process_las;
+
+/// config.sigrs as written before versioning, note the unescaped backslash
+#[cfg(test)]
+const V0_CONFIG: &str = "# File generated by signatu_rs. Do not manually edit this file. #
+K:\"key 1\"
+U:\"uname 1A\"\"domain\\user\"
+E:\"1A@x.com\"
+K:\"key 2\"
+U:\"uname 2A\"
+E:\"2A@x.com\"\"2B@x.com\"
+";
+
+#[test]
+fn test_read_version() {
+    assert_eq!(read_version(las_header().as_bytes()), Ok(LAS_VERSION));
+    assert_eq!(read_version(V0_CONFIG.as_bytes()), Ok(0));
+    assert_eq!(read_version(b""), Ok(0));
+
+    // marker only counts inside the leading comment block
+    let late_marker = format!("K:\"x\"\nU:\"x\"\nE:\"x\"\n{}9\n", LAS_VERSION_PREFIX);
+    assert_eq!(read_version(late_marker.as_bytes()), Ok(0));
+
+    let bad_marker = format!("# header\n{}abc\n", LAS_VERSION_PREFIX);
+    assert!(read_version(bad_marker.as_bytes()).is_err());
+}
+
+#[test]
+fn test_check_version() {
+    assert!(check_version(LAS_VERSION).is_ok());
+    assert!(check_version(0).is_err());
+    assert!(check_version(LAS_VERSION + 1).is_err());
+}
+
+#[test]
+fn test_process_las_rejects_other_versions() {
+    assert!(process_las(V0_CONFIG.as_bytes()).is_err());
+
+    let newer = format!("# header\n{}{}\n", LAS_VERSION_PREFIX, LAS_VERSION + 1);
+    assert!(process_las(newer.as_bytes()).is_err());
+}
+
+#[test]
+fn test_migrate_v0() {
+    let migrated = migrate_content(V0_CONFIG, 0).unwrap();
+    assert_eq!(read_version(migrated.as_bytes()), Ok(LAS_VERSION));
+    // header comment is not duplicated
+    assert_eq!(migrated.matches("# File generated by signatu_rs").count(), 1);
+
+    let las = process_las(migrated.as_bytes()).unwrap();
+    assert_eq!(las.identities.len(), 2);
+
+    let (_k, id1) = las.lookup_id(&String::from("key 1")).unwrap();
+    assert_eq!(id1.usernames[0].as_ref(), b"uname 1A");
+    // legacy backslash kept literally, not treated as an escape
+    assert_eq!(id1.usernames[1].as_ref(), b"domain\\user");
+    assert_eq!(id1.emails[0].as_ref(), b"1A@x.com");
+
+    let (_k, id2) = las.lookup_id(&String::from("key 2")).unwrap();
+    assert_eq!(id2.emails.len(), 2);
+    assert_eq!(id2.emails[1].as_ref(), b"2B@x.com");
+
+    // migrating an up to date file is a no-op
+    assert_eq!(migrate_content(&migrated, LAS_VERSION).unwrap(), migrated);
+}
+
+#[test]
+fn test_migrate_v1() {
+    let v1 = "# File generated by signatu_rs. Do not manually edit this file. #\n#!las-version:1\nK:\"a\"\nU:\"u\"\nE:\"e\"\n";
+    let migrated = migrate_content(v1, 1).unwrap();
+    assert_eq!(migrated, format!("{}K:\"a\"\nU:\"u\"\nE:\"e\"\n", las_header()));
+}
+
+#[test]
+fn test_ensure_current_version_in_place() {
+    let path = PathBuf::from_str("./TestLasVersionMigrate.sigrs").unwrap();
+    let backup = PathBuf::from_str("./TestLasVersionMigrate.sigrs.v0.bak").unwrap();
+    let _ = fs::remove_file(&backup);
+    fs::write(&path, V0_CONFIG).unwrap();
+
+    ensure_current_version(&path).unwrap();
+
+    let upgraded = fs::read(&path).unwrap();
+    assert_eq!(read_version(&upgraded), Ok(LAS_VERSION));
+    assert_eq!(fs::read_to_string(&backup).unwrap(), V0_CONFIG, "backup does not match original");
+
+    // 2nd run does nothing
+    ensure_current_version(&path).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), upgraded);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&backup).unwrap();
+}
+
+#[test]
+fn test_templates_match_header() {
+    // other binaries can't call `las_header()`, so they hardcode it
+    let current = format!("{}{}", LAS_VERSION_PREFIX, LAS_VERSION);
+    for source in [
+        include_str!("../../../modifier_bin/main.rs"),
+        include_str!("../../../../../xtask/src/main.rs"),
+    ] {
+        assert!(source.contains(&current));
+        assert_eq!(source.matches(LAS_VERSION_PREFIX).count(), source.matches(&current).count());
+    }
+}
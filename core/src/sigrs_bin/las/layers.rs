@@ -0,0 +1,140 @@
+use std::path::Path;
+use crate::identity::{IdentityPerf, LocalAuthorStoragePerf};
+use crate::memmap::{get_shared_memmaps, process_las, SharedMmap};
+
+// ========== LAYERS (personal config.sigrs first, then read-only team stores)
+// sigrs profile add-shared /srv/infra/sigrs
+// sigrs list-all
+// == lists personal identities && every shared one, each with the store it came from
+// sigrs sign --identity deploy-bot
+// == found in a shared store if it isn't in config.sigrs
+//
+// Lookups go through the layers in order && the first store holding a key wins, so
+// a personal identity overrides a shared one with the same key (add it with
+// `sigrs add-new` to override). add-new/update/delete && every other write only
+// ever touch the personal config.sigrs.
+// A shared store that fails to parse is skipped with a warning, a broken team
+// store shouldn't stop anyone from signing with their own identities
+
+/// One parsed store
+#[derive(Debug)]
+pub struct StoreLayer<'a> {
+    pub source: &'a Path,
+    pub las: LocalAuthorStoragePerf<'a>,
+}
+
+/// An identity && the store it was found in
+#[derive(Debug, Clone, Copy)]
+pub struct LayeredEntry<'a> {
+    pub key: &'a [u8],
+    pub identity: &'a IdentityPerf<'a>,
+    pub source: &'a Path,
+}
+
+/// Stores in lookup order, only the first (personal) one is ever written
+#[derive(Debug)]
+pub struct LayeredLas<'a> {
+    pub layers: Vec<StoreLayer<'a>>,
+}
+
+impl<'a> LayeredLas<'a> {
+    /// Just the personal store
+    pub fn new(source: &'a Path, las: LocalAuthorStoragePerf<'a>) -> Self {
+        Self { layers: vec![StoreLayer { source, las }] }
+    }
+
+    /// `personal` followed by every shared store that parses
+    pub fn with_shared(
+        source: &'a Path,
+        personal: &'a [u8],
+        shared: &'a [SharedMmap]
+    ) -> Result<Self, String> {
+        let mut layered = Self::new(source, process_las(personal).map_err(|e| e.to_string())?);
+        for store in shared {
+            layered.push_shared(&store.source, store);
+        }
+        Ok(layered)
+    }
+
+    /// Adds a read-only store after the existing ones. Skipped with a warning if it
+    /// fails to parse, or if it is a store that's already layered
+    pub fn push_shared(&mut self, source: &'a Path, memmap: &'a [u8]) {
+        if self.layers.iter().any(|layer| same_file(layer.source, source)) {
+            return;
+        }
+        match process_las(memmap) {
+            Ok(las) => self.layers.push(StoreLayer { source, las }),
+            Err(e) => eprintln!("Skipped shared store {}: {}", source.display(), e),
+        }
+    }
+
+    /// More than the personal store, so output says where identities came from
+    pub fn is_layered(&self) -> bool {
+        self.layers.len() > 1
+    }
+
+    /// First store holding `key`
+    pub fn lookup_id(&self, key: &str) -> Option<LayeredEntry<'_>> {
+        self.layers.iter().find_map(|layer| {
+            layer.las.identities
+                .get_key_value(key.as_bytes())
+                .map(|(k, v)| LayeredEntry { key: k.as_ref(), identity: v.as_ref(), source: layer.source })
+        })
+    }
+
+    /// Identities `is_match` accepts in key order, keys shadowed by an earlier
+    /// store are skipped
+    pub fn find_matches<F>(&self, is_match: F) -> Vec<LayeredEntry<'_>>
+    where
+        F: Fn(&[u8], &IdentityPerf) -> bool
+    {
+        let mut found: Vec<LayeredEntry> = Vec::new();
+        for (index, layer) in self.layers.iter().enumerate() {
+            for (key, identity) in layer.las.find_matches(|_, _| true) {
+                let shadowed = self.layers[..index].iter()
+                    .any(|earlier| earlier.las.identities.contains_key(key));
+                if !shadowed && is_match(key, identity) {
+                    found.push(LayeredEntry { key, identity, source: layer.source });
+                }
+            }
+        }
+        // keys are unique once shadowed ones are skipped
+        found.sort_by_key(|entry| entry.key);
+        found
+    }
+
+    /// Every identity, see `find_matches`
+    pub fn all(&self) -> Vec<LayeredEntry<'_>> {
+        self.find_matches(|_, _| true)
+    }
+
+    /// Human readable, with the store each identity came from once there is more than one
+    pub fn pretty_print(&self, entry: &LayeredEntry, verbose: bool) {
+        if self.is_layered() {
+            println!("Store: {}", entry.source.display());
+        }
+        self.layers[0].las.pretty_print(entry.key, entry.identity, verbose);
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Error for a write to `key` when the personal store doesn't have it, pointing
+/// at the shared store that does, if any
+pub fn missing_identity_error(key: &str) -> String {
+    // maps are only parsed here && dropped before returning
+    let shared = unsafe { get_shared_memmaps() };
+    shared.iter()
+        .find(|store| process_las(store)
+            .is_ok_and(|las| las.identities.contains_key(key.as_bytes())))
+        .map(|store| format!(
+            "Identity \"{}\" is in the read-only shared store {}. Add it with `sigrs add-new --identity \"{}\" ...` to override it in your own config.sigrs",
+            key, store.source.display(), key
+        ))
+        .unwrap_or_else(|| String::from("Identity does not exist"))
+}
@@ -1,6 +1,11 @@
 use clap::ArgMatches;
 use anyhow::Result;
+use std::path::PathBuf;
 use crate::memmap::*;
+use crate::utils::extract_config_path;
+use super::layers::{LayeredEntry, LayeredLas};
+use super::search::{FieldMatch, LasQuery};
+use super::output::{render, ListedIdentity, OutputFormat};
 
 // UNSAFE WARNING
 // See `memmap.rs` for explanations for why these functions are unsafe
@@ -15,47 +20,103 @@ use crate::memmap::*;
 // sigrs list-find --email "xxx@email.com"
 // = Prints all containing 1 or more of the options provided
 // sigrs list-find --username "fluxdiv" --email "x@e.com y@e.com"
+// == substring/case-insensitive/regex && key search, see `search.rs`
+// == every list command takes `--format json|yaml|csv|tsv|<template>`, see `output.rs`
+// == shared team stores are listed after config.sigrs, with the store each
+// == identity came from, see `layers.rs`
+
+/// Prints `identities` in the `--format` passed, with where each came from when
+/// `layered` (shared stores are set up)
+fn print_formatted(format: &OutputFormat, identities: &[LayeredEntry], layered: bool) -> Result<(), String> {
+    let listed: Vec<ListedIdentity> = identities.iter()
+        .map(|e| {
+            let listed = ListedIdentity::new(e.key, e.identity);
+            if layered { listed.with_store(e.source) } else { listed }
+        })
+        .collect();
+    print!("{}", render(format, &listed)?);
+    Ok(())
+}
+
+/// `email "x@y.com", username "bob"`
+fn describe_matches(matches: &[FieldMatch]) -> String {
+    matches.iter()
+        .map(|m| format!("{} \"{}\"", m.field.name(), m.value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
 pub unsafe fn handle_list(args: &ArgMatches) -> Result<(), String> {
 
+    let source = PathBuf::from(extract_config_path(args)?);
     let memmap_las = get_memmap(args)?;
-    let las = process_las(&memmap_las);
-    las.pretty_print_all(args.get_flag("verbose"));
+    let shared = get_shared_memmaps();
+    let las = LayeredLas::with_shared(&source, &memmap_las, &shared)?;
+    let all = las.all();
+    match args.get_one::<OutputFormat>("format") {
+        Some(format) => print_formatted(format, &all, las.is_layered())?,
+        None => {
+            for entry in &all {
+                las.pretty_print(entry, args.get_flag("verbose"));
+            }
+        },
+    }
 
     Ok(())
 }
 
 pub unsafe fn handle_list_by_id(args: &ArgMatches) -> Result<(), String> {
 
+    let source = PathBuf::from(extract_config_path(args)?);
     let memmap_las = get_memmap(args)?;
-    let las = process_las(&memmap_las);
+    let shared = get_shared_memmaps();
+    let las = LayeredLas::with_shared(&source, &memmap_las, &shared)?;
     let id_key = args.get_one::<String>("id").unwrap();
+    let entry = las.lookup_id(id_key)
+        .ok_or_else(|| format!("Identity \"{}\" not found", id_key))?;
 
-    las.pretty_print_id(id_key, args.get_flag("verbose"))
+    match args.get_one::<OutputFormat>("format") {
+        Some(format) => print_formatted(format, &[entry], las.is_layered()),
+        None => {
+            las.pretty_print(&entry, args.get_flag("verbose"));
+            Ok(())
+        },
+    }
 }
 
 
 pub unsafe fn handle_list_find(args: &ArgMatches) -> Result<(), String> {
 
-    let usernames: Vec<String> = args
-        .get_many::<String>("usernames")
-        .map(|vals| vals.cloned().collect())
-        .unwrap_or_else(Vec::new);
-
-    // Extract the "emails list if available
-    let emails: Vec<String> = args
-        .get_many::<String>("emails")
-        .map(|vals| vals.cloned().collect())
-        .unwrap_or_else(Vec::new);
-
-    if usernames.len() + emails.len() <= 0 {
-        return Err(String::from("At least 1 username or email must be provided"));
-    }
+    // compiled before mapping, a bad regex errors without touching config.sigrs
+    let query = LasQuery::from_args(args)?;
 
+    let source = PathBuf::from(extract_config_path(args)?);
     let memmap_las = get_memmap(args)?;
-    let las = process_las(&memmap_las);
-    // print out any entry that contains one of the emails or usernames
-    las.print_any_match(&usernames, &emails);
+    let shared = get_shared_memmaps();
+    let las = LayeredLas::with_shared(&source, &memmap_las, &shared)?;
+    // every identity matching the query once, ranked, see `search.rs`
+    let found = query.find(&las);
+    match args.get_one::<OutputFormat>("format") {
+        Some(format) => {
+            let listed: Vec<ListedIdentity> = found.iter()
+                .map(|f| {
+                    let listed = ListedIdentity::new(f.key, f.identity).with_matches(f.matches.clone());
+                    if las.is_layered() { listed.with_store(f.source) } else { listed }
+                })
+                .collect();
+            print!("{}", render(format, &listed)?);
+        },
+        None => {
+            for f in &found {
+                println!("Matched ({}): {}", f.matches.len(), describe_matches(&f.matches));
+                let entry = LayeredEntry { key: f.key, identity: f.identity, source: f.source };
+                las.pretty_print(&entry, true);
+            }
+            if found.is_empty() {
+                println!("No matching identities found");
+            }
+        },
+    }
 
     Ok(())
 }
@@ -0,0 +1,91 @@
+// ============================= config.sigrs (LAS) locking
+// Advisory locks so parallel sigrs processes (e.g. CI matrix jobs) never
+// interleave read-modify-write cycles, or write while another maps the file
+//
+// - readers (list-*, sign) take a shared lock
+// - writers (add-new, update, delete, doctor --fix ...) take an exclusive lock
+//
+// The lock is held on `<config path>.lock`, not config.sigrs itself, since
+// writers replace config.sigrs via rename (see `utils::write_atomic`) && a lock
+// on the old file would not be seen by anyone opening the new one.
+// Locks are only advisory, editors etc. ignore them.
+use clap::ArgMatches;
+use std::{
+    fs::{File, TryLockError},
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// Seconds to wait for a lock when `--lock-timeout` is not passed
+pub const DEFAULT_LOCK_TIMEOUT: u64 = 10;
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockKind {
+    Shared,
+    Exclusive,
+}
+
+/// Held lock on a config.sigrs, released on drop
+#[derive(Debug)]
+pub struct LasLock {
+    _handle: File,
+}
+
+/// `<config path>.lock`
+pub fn lock_path(config_path: &Path) -> PathBuf {
+    let mut path = config_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// `--lock-timeout` or the default
+pub fn extract_lock_timeout(args: &ArgMatches) -> Duration {
+    let secs = args.try_get_one::<u64>("lock-timeout")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(DEFAULT_LOCK_TIMEOUT);
+    Duration::from_secs(secs)
+}
+
+/// Locks the config.sigrs at `config_path`, waiting up to `timeout`
+pub fn lock_las(config_path: &Path, kind: LockKind, timeout: Duration) -> Result<LasLock, String> {
+    let path = lock_path(config_path);
+    let handle = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|e| format!("Problem opening lock file {}: {:?}", path.display(), e))?;
+
+    let start = Instant::now();
+    loop {
+        let res = match kind {
+            LockKind::Shared => handle.try_lock_shared(),
+            LockKind::Exclusive => handle.try_lock(),
+        };
+        match res {
+            Ok(()) => return Ok(LasLock { _handle: handle }),
+            Err(TryLockError::WouldBlock) => {
+                if start.elapsed() >= timeout {
+                    return Err(format!(
+                        "config.sigrs is locked by another sigrs process (waited {}s for {}). Try again, or raise `--lock-timeout`",
+                        timeout.as_secs(), path.display()
+                    ));
+                }
+                sleep(LOCK_RETRY_INTERVAL);
+            },
+            Err(TryLockError::Error(e)) => {
+                return Err(format!("Problem locking {}: {:?}", path.display(), e));
+            }
+        }
+    }
+}
+
+/// Exclusive lock for `--config-path` (or the saved path) using `--lock-timeout`
+pub fn lock_las_for_write(args: &ArgMatches, config_path: &Path) -> Result<LasLock, String> {
+    lock_las(config_path, LockKind::Exclusive, extract_lock_timeout(args))
+}
@@ -0,0 +1,227 @@
+use clap::ArgMatches;
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+use crate::memmap::get_memmap;
+use crate::utils::write_atomic;
+use super::harvest::{merge_people, HarvestedPerson};
+use super::storage::{read_store, StoredIdentity};
+
+// ========== IMPORT-MAILMAP (an identity for every person in a .mailmap)
+// sigrs import-mailmap
+// sigrs import-mailmap ./some/repo/.mailmap --dry-run
+// == the canonical name/email become the identity's defaults, every alias is added after
+// == them. A person whose canonical email is already stored is merged into that identity
+// ========== EXPORT-MAILMAP (a .mailmap from every identity)
+// sigrs export-mailmap
+// sigrs export-mailmap --output ./.mailmap
+// == the canonical value is the default username/email of each identity
+//
+// https://git-scm.com/docs/gitmailmap
+// Proper Name <commit@email.xx>
+// <proper@email.xx> <commit@email.xx>
+// Proper Name <proper@email.xx> <commit@email.xx>
+// Proper Name <proper@email.xx> Commit Name <commit@email.xx>
+
+/// One line of a .mailmap
+#[derive(Debug, Clone, PartialEq)]
+pub struct MailmapEntry {
+    pub proper_name: Option<String>,
+    /// None for `Proper Name <commit@email.xx>`, which only fixes the name
+    pub proper_email: Option<String>,
+    pub commit_name: Option<String>,
+    pub commit_email: String,
+}
+
+impl MailmapEntry {
+    /// The email this entry maps commits to
+    fn canonical_email(&self) -> &str {
+        self.proper_email.as_deref().unwrap_or(&self.commit_email)
+    }
+}
+
+/// Splits `Name <email> rest` into (name, email, rest)
+fn take_name_email(text: &str) -> Option<(Option<String>, String, &str)> {
+    let start = text.find('<')?;
+    let len = text[start..].find('>')?;
+    let name = text[..start].trim();
+    let email = text[start + 1..start + len].trim().to_string();
+    Some(((!name.is_empty()).then(|| name.to_string()), email, &text[start + len + 1..]))
+}
+
+pub fn parse_mailmap(content: &str) -> Result<Vec<MailmapEntry>, String> {
+    let mut entries = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, email, rest) = take_name_email(line)
+            .ok_or_else(|| format!("line {}: expected `<email>`", i + 1))?;
+
+        // anything after the last email is a comment
+        entries.push(match take_name_email(rest) {
+            Some((commit_name, commit_email, _)) => MailmapEntry {
+                proper_name: name,
+                proper_email: Some(email),
+                commit_name,
+                commit_email,
+            },
+            None => MailmapEntry {
+                proper_name: name,
+                proper_email: None,
+                commit_name: None,
+                commit_email: email,
+            },
+        });
+    }
+    Ok(entries)
+}
+
+/// Names are case sensitive, emails are not
+fn push_unique(values: &mut Vec<String>, value: &str, ignore_case: bool) {
+    let same = |v: &String| if ignore_case { v.eq_ignore_ascii_case(value) } else { v == value };
+    if !value.is_empty() && !values.iter().any(same) {
+        values.push(value.to_string());
+    }
+}
+
+/// Everything mapped to one canonical email
+#[derive(Default)]
+struct MailmapGroup {
+    canonical_email: String,
+    proper_names: Vec<String>,
+    alias_names: Vec<String>,
+    alias_emails: Vec<String>,
+}
+
+/// One person per canonical email (case insensitive), canonical values first.
+/// Returns (people, canonical emails that never get a name)
+pub fn mailmap_people(entries: &[MailmapEntry], source: &Path) -> (Vec<HarvestedPerson>, Vec<String>) {
+    let mut groups: Vec<MailmapGroup> = Vec::new();
+    for entry in entries {
+        let canonical = entry.canonical_email();
+        let index = match groups.iter().position(|g| g.canonical_email.eq_ignore_ascii_case(canonical)) {
+            Some(index) => index,
+            None => {
+                groups.push(MailmapGroup { canonical_email: canonical.to_string(), ..MailmapGroup::default() });
+                groups.len() - 1
+            }
+        };
+        let group = &mut groups[index];
+        if let Some(name) = &entry.proper_name {
+            push_unique(&mut group.proper_names, name, false);
+        }
+        if let Some(name) = &entry.commit_name {
+            push_unique(&mut group.alias_names, name, false);
+        }
+        push_unique(&mut group.alias_emails, &entry.commit_email, true);
+    }
+
+    let mut people = Vec::new();
+    let mut nameless = Vec::new();
+    for group in groups {
+        let mut usernames = group.proper_names;
+        for name in &group.alias_names {
+            push_unique(&mut usernames, name, false);
+        }
+        if usernames.is_empty() {
+            nameless.push(group.canonical_email);
+            continue;
+        }
+        let mut emails = vec![group.canonical_email];
+        for email in &group.alias_emails {
+            push_unique(&mut emails, email, true);
+        }
+        people.push(HarvestedPerson {
+            usernames,
+            emails,
+            sources: vec![source.to_path_buf()],
+            ..HarvestedPerson::default()
+        });
+    }
+    (people, nameless)
+}
+
+pub fn handle_import_mailmap(args: &ArgMatches) -> Result<(), String> {
+    let file = args.get_one::<OsString>("file")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(".mailmap"));
+    let content = fs::read_to_string(&file)
+        .map_err(|e| format!("Problem reading {}: {:?}", file.display(), e))?;
+    let entries = parse_mailmap(&content)
+        .map_err(|e| format!("Problem parsing {}: {}", file.display(), e))?;
+
+    let (people, nameless) = mailmap_people(&entries, &file);
+    for email in &nameless {
+        println!("skip   <{}> (no name)", email);
+    }
+
+    let dry_run = args.get_flag("dry-run");
+    let (added, merged) = merge_people(args, &format!("import-mailmap {}", file.display()), &people, dry_run)?;
+
+    let summary = format!("{} new, {} merged, from {}", added, merged, file.display());
+    if dry_run {
+        println!("Dry run, nothing written. Would add {}", summary);
+    } else {
+        println!("Imported {}", summary);
+    }
+    Ok(())
+}
+
+/// `Name <email>`, or `<email>` without a name
+fn name_email(name: &str, email: &str) -> String {
+    if name.is_empty() {
+        format!("<{}>", email)
+    } else {
+        format!("{} <{}>", name, email)
+    }
+}
+
+/// .mailmap lines for one identity, None without a default username && email
+pub fn identity_mailmap(identity: &StoredIdentity) -> Option<String> {
+    let (name, email) = (identity.usernames.first()?, identity.emails.first()?);
+    let canonical = name_email(name, email);
+
+    let mut lines = format!("# {}\n{}\n", identity.key, canonical);
+    // any commit using another email, whatever the name
+    for other in &identity.emails[1..] {
+        lines.push_str(&format!("{} <{}>\n", canonical, other));
+    }
+    // git already maps these through the first line, kept so import-mailmap gets the aliases back
+    for alias in &identity.usernames[1..] {
+        lines.push_str(&format!("{} {}\n", canonical, name_email(alias, email)));
+    }
+    Some(lines)
+}
+
+// See `memmap.rs` for explanations for why this function is unsafe
+pub unsafe fn handle_export_mailmap(args: &ArgMatches) -> Result<(), String> {
+    let content = {
+        // Handles --config-path, shared lock until dropped
+        let memmap_las = get_memmap(args)?;
+        let content = std::str::from_utf8(&memmap_las)
+            .map_err(|e| format!("config.sigrs is not valid UTF-8: {}", e))?;
+        let (_comments, identities) = read_store(content)?;
+
+        let mut mailmap = String::from("# Generated by `sigrs export-mailmap`\n");
+        for identity in &identities {
+            match identity_mailmap(identity) {
+                Some(lines) => mailmap.push_str(&lines),
+                None => eprintln!("Skipped \"{}\", it has no username or no email", identity.key),
+            }
+        }
+        mailmap
+    };
+
+    match args.get_one::<OsString>("output").map(PathBuf::from) {
+        Some(path) => {
+            write_atomic(&path, content.as_bytes())?;
+            println!("Exported to {}", path.display());
+        },
+        None => print!("{}", content),
+    }
+    Ok(())
+}
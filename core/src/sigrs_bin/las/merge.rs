@@ -0,0 +1,89 @@
+use clap::ArgMatches;
+use super::export::merge_identity;
+use super::storage::{rewrite_store, StoredIdentity};
+
+// ========== CLONE (copy an identity under a new key)
+// sigrs clone --identity "work" --as "work-laptop"
+// == usernames, emails && metadata are copied as is, the copy is added last
+// ========== MERGE (combine identities into one)
+// sigrs merge --into "bob" --from "bob-2"
+// sigrs merge --into "bob" --from "bob-2" --from "robert"
+// == usernames/emails of each --from are added after the ones already on --into,
+// == in order && without duplicates, so --into keeps its defaults. Metadata on
+// == --into wins, --from only fills fields it lacks, rules are added after --into's.
+// == Each --from is then deleted
+
+/// Finds `key` in `identities`, erroring with `role` (e.g. "--from") if missing
+fn position(identities: &[StoredIdentity], key: &str, role: &str) -> Result<usize, String> {
+    identities.iter()
+        .position(|i| i.key == key)
+        .ok_or_else(|| format!("{} identity \"{}\" does not exist", role, key))
+}
+
+/// `identities` with a copy of `key` added last as `new_key`
+pub fn clone_identity(
+    mut identities: Vec<StoredIdentity>,
+    key: &str,
+    new_key: &str
+) -> Result<Vec<StoredIdentity>, String> {
+    let source = position(&identities, key, "--identity")?;
+    if identities.iter().any(|i| i.key == new_key) {
+        return Err(format!("Identity \"{}\" already exists", new_key));
+    }
+    let copy = StoredIdentity { key: new_key.to_string(), ..identities[source].clone() };
+    identities.push(copy);
+    Ok(identities)
+}
+
+/// `identities` with each of `from` merged into `into` && removed, see above
+pub fn merge_identities(
+    mut identities: Vec<StoredIdentity>,
+    into: &str,
+    from: &[&str]
+) -> Result<Vec<StoredIdentity>, String> {
+    position(&identities, into, "--into")?;
+    for (i, key) in from.iter().enumerate() {
+        if *key == into {
+            return Err(format!("Cannot merge \"{}\" into itself", key));
+        }
+        if from[..i].contains(key) {
+            return Err(format!("--from \"{}\" is passed more than once", key));
+        }
+    }
+
+    for key in from {
+        let source = identities.remove(position(&identities, key, "--from")?);
+        // position shifts as --from identities are removed
+        let target = position(&identities, into, "--into")?;
+        merge_identity(&mut identities[target], source);
+    }
+    Ok(identities)
+}
+
+pub fn handle_clone(args: &ArgMatches) -> Result<(), String> {
+    let identity = args.get_one::<String>("identity")
+        .ok_or_else(|| String::from("--identity required"))?;
+    let new_key = args.get_one::<String>("as")
+        .ok_or_else(|| String::from("--as required"))?;
+
+    rewrite_store(args, &format!("clone \"{}\" as \"{}\"", identity, new_key), |identities| {
+        clone_identity(identities, identity, new_key)
+    })?;
+    println!("Cloned \"{}\" as \"{}\"", identity, new_key);
+    Ok(())
+}
+
+pub fn handle_merge(args: &ArgMatches) -> Result<(), String> {
+    let into = args.get_one::<String>("into")
+        .ok_or_else(|| String::from("--into required"))?;
+    let from: Vec<&str> = args.get_many::<String>("from")
+        .map(|vals| vals.map(String::as_str).collect())
+        .unwrap_or_default();
+    let quoted = from.iter().map(|k| format!("\"{}\"", k)).collect::<Vec<_>>().join(", ");
+
+    rewrite_store(args, &format!("merge {} into \"{}\"", quoted, into), |identities| {
+        merge_identities(identities, into, &from)
+    })?;
+    println!("Merged {} into \"{}\"", quoted, into);
+    Ok(())
+}
@@ -1,3 +1,23 @@
 pub mod list;
 pub mod delete;
 pub mod storage;
+pub mod format;
+pub mod version;
+pub mod doctor;
+pub mod config;
+pub mod lock;
+pub mod history;
+pub mod export;
+pub mod git_import;
+pub mod harvest;
+pub mod mailmap;
+pub mod validate;
+pub mod search;
+pub mod output;
+pub mod merge;
+pub mod profile;
+pub mod layers;
+pub mod rules;
+
+#[cfg(test)]
+mod las_tests;
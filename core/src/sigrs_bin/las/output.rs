@@ -0,0 +1,287 @@
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::identity::{IdentityPerf, METADATA_FIELDS};
+use super::search::FieldMatch;
+
+// ========== LIST --format (machine readable output for list-all, list-by-id && list-find)
+// sigrs list-all --format json
+// sigrs list-find -E gmail.com --contains --format csv
+// sigrs list-by-id --identity work --format yaml
+// sigrs list-all --format '{id}\t{default_email}'
+// == json && yaml are always a list, even for list-by-id
+// == csv && tsv start with a header row, multiple usernames/emails are joined with `;`
+// == templates print one line per identity, `\t` `\n` `\\` are unescaped && `{{` `}}`
+// == are literal braces. Placeholders:
+// {id} {default_username} {default_email} {usernames} {emails} {url} {role} {organization} {fingerprint} {store}
+// == list-find adds what each identity matched to json && yaml, `{matches}` in templates
+// == `store` is the config.sigrs the identity came from, see `layers.rs`. Only set
+// == when shared stores are set up, json/yaml/csv/tsv leave it out otherwise
+
+/// Joins multiple values in csv, tsv && template output
+const VALUE_SEPARATOR: &str = ";";
+
+const COLUMNS: [&str; 10] = [
+    "id", "default_username", "default_email", "usernames", "emails",
+    "url", "role", "organization", "fingerprint", "store",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    Text(String),
+    /// Index into `COLUMNS`
+    Field(usize),
+    /// `{matches}`, empty outside list-find
+    Matches,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Csv,
+    Tsv,
+    Template(Vec<TemplatePart>),
+}
+
+impl OutputFormat {
+    /// `json`, `yaml`, `csv`, `tsv` or a template containing `{placeholder}`s.
+    /// Used as the clap value parser for `--format`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "json" => Ok(Self::Json),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            template if template.contains('{') => parse_template(template).map(Self::Template),
+            other => Err(format!(
+                "unknown format \"{}\", expected json, yaml, csv, tsv or a template like '{{id}}\\t{{default_email}}'",
+                other
+            )),
+        }
+    }
+}
+
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some('\\') => text.push('\\'),
+                Some(other) => { text.push('\\'); text.push(other); },
+                None => text.push('\\'),
+            },
+            '{' if chars.peek() == Some(&'{') => { chars.next(); text.push('{'); },
+            '}' if chars.peek() == Some(&'}') => { chars.next(); text.push('}'); },
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unclosed `{{{}` in template, use `{{{{` for a literal brace", name)),
+                    }
+                }
+                let part = match COLUMNS.iter().position(|col| *col == name) {
+                    Some(index) => TemplatePart::Field(index),
+                    None if name == "matches" => TemplatePart::Matches,
+                    None => return Err(format!(
+                        "unknown placeholder {{{}}}, expected one of {} {{matches}}",
+                        name,
+                        COLUMNS.iter().map(|c| format!("{{{}}}", c)).collect::<Vec<_>>().join(" ")
+                    )),
+                };
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(part);
+            },
+            '}' => return Err(String::from("unmatched `}` in template, use `}}` for a literal brace")),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+    Ok(parts)
+}
+
+/// One identity as printed by `--format`, borrowing from the memmap where the
+/// values are valid UTF-8
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ListedIdentity<'a> {
+    pub id: Cow<'a, str>,
+    pub default_username: Option<Cow<'a, str>>,
+    pub default_email: Option<Cow<'a, str>>,
+    pub usernames: Vec<Cow<'a, str>>,
+    pub emails: Vec<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<Cow<'a, str>, Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<Cow<'a, str>>,
+    /// Only set by list-find
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<FieldMatch<'a>>,
+}
+
+impl<'a> ListedIdentity<'a> {
+    pub fn new(key: &'a [u8], identity: &'a IdentityPerf) -> Self {
+        let text = |bytes: &'a [u8]| String::from_utf8_lossy(bytes);
+        Self {
+            id: text(key),
+            default_username: identity.default_username().map(text),
+            default_email: identity.default_email().map(text),
+            usernames: identity.usernames.iter().map(|u| text(u)).collect(),
+            emails: identity.emails.iter().map(|e| text(e)).collect(),
+            metadata: identity.metadata.iter().map(|(f, v)| (text(f), text(v))).collect(),
+            store: None,
+            matches: Vec::new(),
+        }
+    }
+
+    pub fn with_store(mut self, source: &'a Path) -> Self {
+        self.store = Some(source.to_string_lossy());
+        self
+    }
+
+    pub fn with_matches(mut self, matches: Vec<FieldMatch<'a>>) -> Self {
+        self.matches = matches;
+        self
+    }
+
+    /// `field:value` for each match, joined
+    fn matches_column(&self) -> String {
+        self.matches.iter()
+            .map(|m| format!("{}:{}", m.field.name(), m.value))
+            .collect::<Vec<_>>()
+            .join(VALUE_SEPARATOR)
+    }
+
+    /// Values in `COLUMNS` order, multiple values joined
+    fn columns(&self) -> [String; 10] {
+        let optional = |value: &Option<Cow<str>>| value.as_deref().unwrap_or("").to_string();
+        let metadata = |field: &str| self.metadata.get(field).map(|v| v.to_string()).unwrap_or_default();
+        let [url, role, organization, fingerprint] = METADATA_FIELDS.map(metadata);
+        [
+            self.id.to_string(),
+            optional(&self.default_username),
+            optional(&self.default_email),
+            self.usernames.join(VALUE_SEPARATOR),
+            self.emails.join(VALUE_SEPARATOR),
+            url,
+            role,
+            organization,
+            fingerprint,
+            optional(&self.store),
+        ]
+    }
+}
+
+/// RFC 4180, quoted only when needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Tabs, newlines && backslashes escaped so every row stays on one line
+fn tsv_field(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+/// YAML double quoted scalars accept JSON strings as is
+fn yaml_scalar(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn render_yaml(identities: &[ListedIdentity]) -> String {
+    if identities.is_empty() {
+        return String::from("[]\n");
+    }
+    let mut out = String::new();
+    let optional = |value: &Option<Cow<str>>| value.as_deref().map(yaml_scalar).unwrap_or_else(|| String::from("null"));
+    let list = |out: &mut String, name: &str, values: &[Cow<str>]| {
+        if values.is_empty() {
+            out.push_str(&format!("  {}: []\n", name));
+        } else {
+            out.push_str(&format!("  {}:\n", name));
+            for value in values {
+                out.push_str(&format!("    - {}\n", yaml_scalar(value)));
+            }
+        }
+    };
+    for identity in identities {
+        out.push_str(&format!("- id: {}\n", yaml_scalar(&identity.id)));
+        out.push_str(&format!("  default_username: {}\n", optional(&identity.default_username)));
+        out.push_str(&format!("  default_email: {}\n", optional(&identity.default_email)));
+        list(&mut out, "usernames", &identity.usernames);
+        list(&mut out, "emails", &identity.emails);
+        if !identity.metadata.is_empty() {
+            out.push_str("  metadata:\n");
+            for (field, value) in &identity.metadata {
+                out.push_str(&format!("    {}: {}\n", yaml_scalar(field), yaml_scalar(value)));
+            }
+        }
+        if let Some(store) = &identity.store {
+            out.push_str(&format!("  store: {}\n", yaml_scalar(store)));
+        }
+        if !identity.matches.is_empty() {
+            out.push_str("  matches:\n");
+            for m in &identity.matches {
+                out.push_str(&format!("    - field: {}\n      value: {}\n", m.field.name(), yaml_scalar(&m.value)));
+            }
+        }
+    }
+    out
+}
+
+/// `identities` in `format`, ending with a newline unless empty
+pub fn render(format: &OutputFormat, identities: &[ListedIdentity]) -> Result<String, String> {
+    // `store` is the last column
+    let column_count = match identities.iter().any(|i| i.store.is_some()) {
+        true => COLUMNS.len(),
+        false => COLUMNS.len() - 1,
+    };
+    let rows = |field: fn(&str) -> String, separator: &str| {
+        let mut out = COLUMNS[..column_count].join(separator);
+        out.push('\n');
+        for identity in identities {
+            let row: Vec<String> = identity.columns()[..column_count].iter().map(|c| field(c)).collect();
+            out.push_str(&row.join(separator));
+            out.push('\n');
+        }
+        out
+    };
+
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(identities)
+            .map(|mut s| { s.push('\n'); s })
+            .map_err(|e| format!("Problem writing JSON: {}", e)),
+        OutputFormat::Yaml => Ok(render_yaml(identities)),
+        OutputFormat::Csv => Ok(rows(csv_field, ",")),
+        OutputFormat::Tsv => Ok(rows(tsv_field, "\t")),
+        OutputFormat::Template(parts) => {
+            let mut out = String::new();
+            for identity in identities {
+                let columns = identity.columns();
+                for part in parts {
+                    match part {
+                        TemplatePart::Text(text) => out.push_str(text),
+                        TemplatePart::Field(index) => out.push_str(&columns[*index]),
+                        TemplatePart::Matches => out.push_str(&identity.matches_column()),
+                    }
+                }
+                out.push('\n');
+            }
+            Ok(out)
+        },
+    }
+}
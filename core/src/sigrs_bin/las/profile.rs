@@ -0,0 +1,266 @@
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+use crate::utils::{get_config_path, write_atomic};
+use super::config::write_template;
+
+// ========== PROFILE (named config.sigrs files, e.g. separate work && personal identities)
+// sigrs profile add work
+// == registers <config dir>/work.sigrs, created empty if it doesn't exist
+// sigrs profile add personal ~/personal.sigrs
+// sigrs profile use work
+// == every LAS && sign command now reads/writes work.sigrs
+// sigrs profile list
+// sigrs profile remove work
+// == unregisters only, the file is kept
+// sigrs list-all --profile personal
+// == one command against another profile, without switching
+// sigrs profile add-shared /srv/infra/sigrs
+// sigrs profile remove-shared /srv/infra/sigrs
+// == read-only team stores, searched after the profile's own config.sigrs by every
+// == list && sign command, see `las/layers.rs`. A directory means every *.sigrs in it
+//
+// config.sigrs is picked by, first match wins:
+// `--config-path` > `--profile` > SIGRS_CONFIG_PATH > SIGRS_PROFILE > active profile
+// > path saved in the binary (`default`), see `utils.rs` for the environment variables
+//
+// The registry is profiles.toml next to the saved config.sigrs:
+// active = "work"
+// shared = ["/srv/infra/sigrs"]
+// [profiles]
+// work = "/home/alice/.config/sigrs/work.sigrs"
+
+/// The config.sigrs saved in the binary, always available && cannot be removed
+pub const DEFAULT_PROFILE: &str = "default";
+
+const REGISTRY_FILE_NAME: &str = "profiles.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileRegistry {
+    /// None means `default`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<String>,
+    /// Read-only stores searched after the active profile's, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shared: Vec<PathBuf>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, PathBuf>,
+}
+
+impl ProfileRegistry {
+    /// Missing registry is an empty one
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| format!("Problem parsing {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Problem reading {}: {:?}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Problem creating {}: {:?}", parent.display(), e))?;
+        }
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("Problem writing {}: {}", path.display(), e))?;
+        write_atomic(path, content.as_bytes())
+    }
+
+    /// Name of the profile used when `--profile` isn't passed
+    pub fn active_name(&self) -> &str {
+        self.active.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    /// config.sigrs of `name`, or of the active profile
+    pub fn resolve(&self, name: Option<&str>, default_path: OsString) -> Result<OsString, String> {
+        match name.unwrap_or(self.active_name()) {
+            DEFAULT_PROFILE => Ok(default_path),
+            name => self.profiles.get(name)
+                .map(|path| path.clone().into_os_string())
+                .ok_or_else(|| format!(
+                    "Unknown profile \"{}\". See `sigrs profile list`, or add it with `sigrs profile add {}`",
+                    name, name
+                )),
+        }
+    }
+}
+
+/// profiles.toml next to `default_path`
+pub fn registry_path(default_path: &Path) -> PathBuf {
+    match default_path.parent() {
+        Some(dir) => dir.join(REGISTRY_FILE_NAME),
+        None => PathBuf::from(REGISTRY_FILE_NAME),
+    }
+}
+
+/// config.sigrs for `--profile`/the active profile, see above
+pub fn profile_config_path(name: Option<&str>) -> Result<OsString, String> {
+    let default_path = get_config_path()?;
+    let registry = ProfileRegistry::load(&registry_path(default_path.as_ref()))?;
+    registry.resolve(name, default_path)
+}
+
+/// Every shared store file, directories expanded to the *.sigrs files in them
+/// (sorted by name). Missing entries are skipped with a warning
+pub fn expand_shared(shared: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in shared {
+        if entry.is_dir() {
+            let mut in_dir: Vec<PathBuf> = match fs::read_dir(entry) {
+                Ok(read_dir) => read_dir
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "sigrs"))
+                    .collect(),
+                Err(e) => {
+                    eprintln!("Skipped shared store {}: {:?}", entry.display(), e);
+                    continue;
+                },
+            };
+            in_dir.sort();
+            files.extend(in_dir);
+        } else if entry.is_file() {
+            files.push(entry.clone());
+        } else {
+            eprintln!("Skipped shared store {}: it does not exist", entry.display());
+        }
+    }
+    files
+}
+
+/// Shared store files from the registry, none if the registry can't be read
+pub fn shared_store_files() -> Vec<PathBuf> {
+    let Ok(default_path) = get_config_path() else {
+        return Vec::new();
+    };
+    match ProfileRegistry::load(&registry_path(default_path.as_ref())) {
+        Ok(registry) => expand_shared(&registry.shared),
+        Err(e) => {
+            eprintln!("Shared stores skipped: {}", e);
+            Vec::new()
+        },
+    }
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if name == DEFAULT_PROFILE {
+        return Err(format!("\"{}\" is the config path saved in sigrs, pick another name", DEFAULT_PROFILE));
+    }
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid profile name \"{}\", use letters, digits, `-` && `_`", name));
+    }
+    Ok(())
+}
+
+/// Relative paths are stored relative to the current dir, so they work from anywhere
+fn absolute(path: &Path) -> Result<PathBuf, String> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .map_err(|e| format!("Problem getting the current dir: {:?}", e))
+}
+
+pub fn handle_profile(args: &ArgMatches) -> Result<(), String> {
+    let default_path = PathBuf::from(get_config_path()?);
+    run_profile(args, &registry_path(&default_path), &default_path)
+}
+
+/// `sigrs profile ...` against the registry at `registry`
+pub fn run_profile(args: &ArgMatches, registry: &Path, default_path: &Path) -> Result<(), String> {
+    let mut profiles = ProfileRegistry::load(registry)?;
+
+    match args.subcommand() {
+        Some(("list", _)) => {
+            let rows = std::iter::once((DEFAULT_PROFILE, default_path))
+                .chain(profiles.profiles.iter().map(|(name, path)| (name.as_str(), path.as_path())));
+            for (name, path) in rows {
+                let marker = if name == profiles.active_name() { "*" } else { " " };
+                let missing = if path.is_file() { "" } else { " (missing)" };
+                println!("{} {:<12} {}{}", marker, name, path.display(), missing);
+            }
+            if !profiles.shared.is_empty() {
+                println!("\nShared (read-only, searched in this order after the active profile):");
+                for path in &profiles.shared {
+                    let missing = if path.exists() { "" } else { " (missing)" };
+                    println!("  {}{}", path.display(), missing);
+                }
+            }
+        },
+        Some(("add-shared", sub)) => {
+            let path = sub.get_one::<OsString>("path").ok_or_else(|| String::from("Path required"))?;
+            let path = absolute(Path::new(path))?;
+            if profiles.shared.contains(&path) {
+                return Err(format!("{} is already a shared store", path.display()));
+            }
+            if !path.exists() {
+                return Err(format!("{} does not exist", path.display()));
+            }
+            profiles.shared.push(path.clone());
+            profiles.save(registry)?;
+            println!("Added shared store {}", path.display());
+        },
+        Some(("remove-shared", sub)) => {
+            let path = sub.get_one::<OsString>("path").ok_or_else(|| String::from("Path required"))?;
+            let path = absolute(Path::new(path))?;
+            let before = profiles.shared.len();
+            profiles.shared.retain(|p| *p != path);
+            if profiles.shared.len() == before {
+                return Err(format!("{} is not a shared store", path.display()));
+            }
+            profiles.save(registry)?;
+            println!("Removed shared store {}, nothing was deleted", path.display());
+        },
+        Some(("add", sub)) => {
+            let name = sub.get_one::<String>("name").ok_or_else(|| String::from("Profile name required"))?;
+            check_name(name)?;
+            if profiles.profiles.contains_key(name) {
+                return Err(format!("Profile \"{}\" already exists, remove it first to change its path", name));
+            }
+            let path = match sub.get_one::<OsString>("path") {
+                Some(path) => absolute(Path::new(path))?,
+                None => registry.with_file_name(format!("{}.sigrs", name)),
+            };
+            if !path.exists() {
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("Problem creating {}: {:?}", parent.display(), e))?;
+                }
+                write_template(&path)?;
+                println!("Empty config created at {}", path.display());
+            }
+            profiles.profiles.insert(name.clone(), path.clone());
+            profiles.save(registry)?;
+            println!("Added profile \"{}\" ({}). Switch to it with `sigrs profile use {}`", name, path.display(), name);
+        },
+        Some(("remove", sub)) => {
+            let name = sub.get_one::<String>("name").ok_or_else(|| String::from("Profile name required"))?;
+            let Some(path) = profiles.profiles.remove(name) else {
+                return Err(format!("Unknown profile \"{}\"", name));
+            };
+            if profiles.active.as_deref() == Some(name.as_str()) {
+                profiles.active = None;
+                println!("\"{}\" was active, switched back to \"{}\"", name, DEFAULT_PROFILE);
+            }
+            profiles.save(registry)?;
+            println!("Removed profile \"{}\", {} was kept", name, path.display());
+        },
+        Some(("use", sub)) => {
+            let name = sub.get_one::<String>("name").ok_or_else(|| String::from("Profile name required"))?;
+            // errors on unknown names
+            profiles.resolve(Some(name), default_path.as_os_str().to_os_string())?;
+            profiles.active = (name != DEFAULT_PROFILE).then(|| name.clone());
+            profiles.save(registry)?;
+            println!("Now using profile \"{}\"", name);
+        },
+        _ => unreachable!(),
+    }
+    Ok(())
+}
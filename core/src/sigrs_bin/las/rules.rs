@@ -0,0 +1,306 @@
+use clap::ArgMatches;
+use regex::RegexBuilder;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use crate::memmap::{get_memmap, get_shared_memmaps};
+use crate::utils::extract_config_path;
+use super::git_import::parse_git_config;
+use super::layers::LayeredLas;
+use super::storage::{rewrite_store, StoredIdentity};
+
+// ========== RULES (pick an identity from where sign runs)
+// sigrs rule add --identity work --path ~/work/
+// == anything signed under ~/work/ (or a sub dir) uses `work`
+// sigrs rule add --identity work --remote git.corp.com
+// sigrs rule add --identity oss --remote "github.com/bob/*"
+// == repos with a remote on git.corp.com, or under github.com/bob/, read from the
+// == .git/config of the repo sign runs in. `*` matches anything, a pattern also
+// == matches everything below it, `git.corp.com` matches `git.corp.com/team/x`
+// sigrs rule remove --identity work --path ~/work/
+// sigrs rule list
+// sigrs whoami
+// == explains which identity sign would use && why
+//
+// Rules are stored on their identity in config.sigrs as an `R:` line:
+// R:"path""~/work/""remote""git.corp.com"
+// When sign gets no --identity && no .sigrs.toml names one, the first matching
+// rule wins. Identities are checked in file order, then shared stores (see
+// `layers.rs`), each identity's rules in the order they were added
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    /// Working dir prefix
+    Path,
+    /// Git remote URL pattern
+    Remote,
+}
+
+impl RuleKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "path" => Some(Self::Path),
+            "remote" => Some(Self::Remote),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Remote => "remote",
+        }
+    }
+}
+
+/// Where sign runs: the working dir && the remotes of the repo it is in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleContext {
+    pub dir: PathBuf,
+    /// (remote name, url) from .git/config, in order
+    pub remotes: Vec<(String, String)>,
+}
+
+impl RuleContext {
+    /// `dir` && the remotes of the nearest git repo at or above it
+    pub fn detect(dir: &Path) -> Self {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        let remotes = git_config_path(&dir)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| git_remotes(&content))
+            .unwrap_or_default();
+        Self { dir, remotes }
+    }
+}
+
+/// .git/config of the nearest repo, following `.git` files of worktrees && submodules
+fn git_config_path(dir: &Path) -> Option<PathBuf> {
+    let dot_git = dir.ancestors().map(|d| d.join(".git")).find(|p| p.exists())?;
+    let git_dir = if dot_git.is_dir() {
+        dot_git
+    } else {
+        let content = fs::read_to_string(&dot_git).ok()?;
+        let target = PathBuf::from(content.trim().strip_prefix("gitdir:")?.trim());
+        dot_git.parent()?.join(target)
+    };
+    // worktrees share the config of the main repo
+    let common = fs::read_to_string(git_dir.join("commondir"))
+        .map(|common| git_dir.join(common.trim()))
+        .unwrap_or(git_dir);
+    Some(common.join("config"))
+}
+
+/// `[remote "x"] url = ...` entries, unreadable configs have none
+pub fn git_remotes(content: &str) -> Vec<(String, String)> {
+    parse_git_config(content)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|entry| entry.section == "remote" && entry.key == "url")
+        .filter_map(|entry| Some((entry.subsection?, entry.value?)))
+        .collect()
+}
+
+/// `host/path` of a remote url or pattern: scheme, user, `.git` && trailing `/`
+/// dropped, scp-like `host:path` turned into `host/path`, lowercased
+pub fn normalize_remote(url: &str) -> String {
+    let url = url.trim();
+    let (rest, had_scheme) = match url.split_once("://") {
+        Some((_, rest)) => (rest, true),
+        None => (url, false),
+    };
+    // user@ only ever comes before the host
+    let host_end = rest.find('/').unwrap_or(rest.len());
+    let rest = match rest[..host_end].rfind('@') {
+        Some(at) => &rest[at + 1..],
+        None => rest,
+    };
+    let rest = match (had_scheme, rest.split_once(':')) {
+        // host:path, unless it is a port
+        (false, Some((host, path))) if !host.contains('/') => format!("{}/{}", host, path),
+        _ => rest.to_string(),
+    };
+    let rest = rest.trim_end_matches('/');
+    rest.strip_suffix(".git").unwrap_or(rest).to_lowercase()
+}
+
+/// `~/` is the home dir
+fn expand_home(pattern: &str) -> PathBuf {
+    if pattern == "~" {
+        return dirs::home_dir().unwrap_or_default();
+    }
+    match (pattern.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(pattern),
+    }
+}
+
+/// Errors for patterns that could never match
+pub fn check_pattern(kind: RuleKind, pattern: &str) -> Result<(), String> {
+    if pattern.trim().is_empty() {
+        return Err(String::from("Rule pattern cannot be empty"));
+    }
+    if kind == RuleKind::Path && !expand_home(pattern).is_absolute() {
+        return Err(format!("--path \"{}\" must be absolute or start with `~/`", pattern));
+    }
+    Ok(())
+}
+
+/// Why a rule matched `context`, None if it doesn't
+pub fn rule_matches(kind: RuleKind, pattern: &str, context: &RuleContext) -> Option<String> {
+    match kind {
+        RuleKind::Path => {
+            let prefix = expand_home(pattern);
+            let prefix = prefix.canonicalize().unwrap_or(prefix);
+            context.dir.starts_with(&prefix)
+                .then(|| format!("working dir {} is under {}", context.dir.display(), pattern))
+        },
+        RuleKind::Remote => {
+            let glob = normalize_remote(pattern)
+                .split('*')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".*");
+            let regex = RegexBuilder::new(&format!("^{}(/.*)?$", glob)).build().ok()?;
+            context.remotes.iter()
+                .find(|(_, url)| regex.is_match(&normalize_remote(url)))
+                .map(|(name, url)| format!("remote {} ({}) matches {}", name, url, pattern))
+        },
+    }
+}
+
+/// A rule that matched, && why
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleMatch {
+    pub key: String,
+    pub kind: RuleKind,
+    pub pattern: String,
+    pub reason: String,
+}
+
+/// First rule in `las` matching `context`, see above for the order
+pub fn first_match(las: &LayeredLas, context: &RuleContext) -> Option<RuleMatch> {
+    let lossy = |v: &[u8]| String::from_utf8_lossy(v).into_owned();
+    for layer in &las.layers {
+        for key in &layer.las.rule_order {
+            let Some(identity) = layer.las.identities.get(key) else {
+                continue;
+            };
+            for (kind, pattern) in &identity.rules {
+                let (kind, pattern) = (lossy(kind), lossy(pattern));
+                let Some(kind) = RuleKind::parse(&kind) else {
+                    continue;
+                };
+                if let Some(reason) = rule_matches(kind, &pattern, context) {
+                    return Some(RuleMatch { key: lossy(key), kind, pattern, reason });
+                }
+            }
+        }
+    }
+    None
+}
+
+/// (kind, pattern) from `--path`/`--remote`
+fn rule_arg(args: &ArgMatches) -> Result<(RuleKind, String), String> {
+    for kind in [RuleKind::Path, RuleKind::Remote] {
+        if let Some(pattern) = args.get_one::<String>(kind.name()) {
+            check_pattern(kind, pattern)?;
+            return Ok((kind, pattern.clone()));
+        }
+    }
+    Err(String::from("--path or --remote required"))
+}
+
+/// `identities` with the rule added to the end of `key`'s rules
+pub fn add_rule(
+    mut identities: Vec<StoredIdentity>,
+    key: &str,
+    kind: RuleKind,
+    pattern: &str
+) -> Result<Vec<StoredIdentity>, String> {
+    let identity = identities.iter_mut()
+        .find(|i| i.key == key)
+        .ok_or_else(|| format!("Identity \"{}\" does not exist", key))?;
+    let rule = (kind.name().to_string(), pattern.to_string());
+    if identity.rules.contains(&rule) {
+        return Err(format!("\"{}\" already has the rule {} \"{}\"", key, kind.name(), pattern));
+    }
+    identity.rules.push(rule);
+    Ok(identities)
+}
+
+/// `identities` without the rule on `key`
+pub fn remove_rule(
+    mut identities: Vec<StoredIdentity>,
+    key: &str,
+    kind: RuleKind,
+    pattern: &str
+) -> Result<Vec<StoredIdentity>, String> {
+    let identity = identities.iter_mut()
+        .find(|i| i.key == key)
+        .ok_or_else(|| format!("Identity \"{}\" does not exist", key))?;
+    let before = identity.rules.len();
+    identity.rules.retain(|(k, p)| !(k == kind.name() && p == pattern));
+    if identity.rules.len() == before {
+        return Err(format!("\"{}\" has no rule {} \"{}\", see `sigrs rule list`", key, kind.name(), pattern));
+    }
+    Ok(identities)
+}
+
+// See `memmap.rs` for explanations for why this function is unsafe
+pub unsafe fn handle_rule(args: &ArgMatches) -> Result<(), String> {
+    match args.subcommand() {
+        Some(("add", sub)) => {
+            let key = sub.get_one::<String>("identity").ok_or_else(|| String::from("--identity required"))?;
+            let (kind, pattern) = rule_arg(sub)?;
+            rewrite_store(sub, &format!("rule add {} \"{}\" to \"{}\"", kind.name(), pattern, key), |identities| {
+                add_rule(identities, key, kind, &pattern)
+            })?;
+            println!("\"{}\" is now used for {} \"{}\" when no --identity is passed", key, kind.name(), pattern);
+        },
+        Some(("remove", sub)) => {
+            let key = sub.get_one::<String>("identity").ok_or_else(|| String::from("--identity required"))?;
+            let (kind, pattern) = rule_arg(sub)?;
+            rewrite_store(sub, &format!("rule remove {} \"{}\" from \"{}\"", kind.name(), pattern, key), |identities| {
+                remove_rule(identities, key, kind, &pattern)
+            })?;
+            println!("Removed rule {} \"{}\" from \"{}\"", kind.name(), pattern, key);
+        },
+        Some(("list", sub)) => list_rules(sub)?,
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+// See `memmap.rs` for explanations for why this function is unsafe
+unsafe fn list_rules(args: &ArgMatches) -> Result<(), String> {
+    let source = PathBuf::from(extract_config_path(args)?);
+    let memmap_las = get_memmap(args)?;
+    let shared = get_shared_memmaps();
+    let las = LayeredLas::with_shared(&source, &memmap_las, &shared)?;
+
+    let mut position = 0;
+    for layer in &las.layers {
+        for key in &layer.las.rule_order {
+            let Some(identity) = layer.las.identities.get(key) else {
+                continue;
+            };
+            for (kind, pattern) in &identity.rules {
+                position += 1;
+                let store = if las.is_layered() { format!("  ({})", layer.source.display()) } else { String::new() };
+                println!(
+                    "{:>3}. {:<7} \"{}\" -> \"{}\"{}",
+                    position,
+                    String::from_utf8_lossy(kind),
+                    String::from_utf8_lossy(pattern),
+                    String::from_utf8_lossy(key),
+                    store
+                );
+            }
+        }
+    }
+    if position == 0 {
+        println!("No rules, add one with `sigrs rule add --identity <ID> --path <DIR>` or `--remote <PATTERN>`");
+    }
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use clap::ArgMatches;
+use regex::bytes::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::path::Path;
+use crate::identity::IdentityPerf;
+use super::layers::LayeredLas;
+
+// ========== LIST-FIND search terms
+// sigrs list-find -E "bob@x.com"
+// == exact match (default)
+// sigrs list-find -E gmail.com --contains
+// sigrs list-find -U bob --ignore-case
+// sigrs list-find -E "@(gmail|proton)\.com$" --regex
+// sigrs list-find -K work -E x.com --contains --all
+// == -K searches identity keys. By default an identity matching any term is
+// == listed, with --all it has to match every term
+// sigrs list-find -E gmail --contains --sort key --limit 5
+// == each identity is listed once with the values it matched, most matched values
+// == first (`--sort matches`, the default) or by key (`--sort key`)
+//
+// Terms are compiled once && matched against the memmapped slices directly,
+// nothing is copied out of config.sigrs to search it
+
+/// What a term is matched against
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Key,
+    Username,
+    Email,
+}
+
+impl SearchField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Key => "key",
+            Self::Username => "username",
+            Self::Email => "email",
+        }
+    }
+}
+
+/// A stored value one or more terms matched
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldMatch<'a> {
+    pub field: SearchField,
+    pub value: Cow<'a, str>,
+}
+
+/// An identity list-find found, && what in it matched
+#[derive(Debug, Clone)]
+pub struct FoundIdentity<'a> {
+    pub key: &'a [u8],
+    pub identity: &'a IdentityPerf<'a>,
+    /// Store the identity came from, see `layers.rs`
+    pub source: &'a Path,
+    pub matches: Vec<FieldMatch<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortOrder {
+    Key,
+    /// Most matched values first, ties by key
+    #[default]
+    Matches,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    /// Byte for byte, the case sensitive default
+    Exact(Vec<u8>),
+    /// Everything else, including --contains/--ignore-case on plain text
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn is_match(&self, value: &[u8]) -> bool {
+        match self {
+            Self::Exact(bytes) => value == bytes.as_slice(),
+            Self::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MatchMode {
+    #[default]
+    Exact,
+    Contains,
+    Regex,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchTerm {
+    pub field: SearchField,
+    pattern: Pattern,
+}
+
+impl SearchTerm {
+    pub fn new(field: SearchField, text: &str, mode: MatchMode, ignore_case: bool) -> Result<Self, String> {
+        let source = match mode {
+            MatchMode::Exact if !ignore_case => {
+                return Ok(Self { field, pattern: Pattern::Exact(text.as_bytes().to_vec()) });
+            },
+            MatchMode::Exact => format!("^{}$", regex::escape(text)),
+            MatchMode::Contains => regex::escape(text),
+            MatchMode::Regex => text.to_string(),
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| format!("Invalid regex \"{}\": {}", text, e))?;
+        Ok(Self { field, pattern: Pattern::Regex(regex) })
+    }
+
+    pub fn matches(&self, key: &[u8], identity: &IdentityPerf) -> bool {
+        match self.field {
+            SearchField::Key => self.pattern.is_match(key),
+            SearchField::Username => identity.usernames.iter().any(|u| self.pattern.is_match(u)),
+            SearchField::Email => identity.emails.iter().any(|e| self.pattern.is_match(e)),
+        }
+    }
+
+    /// Every value of `field` this term matches
+    fn matched_values<'a>(&self, key: &'a [u8], identity: &'a IdentityPerf) -> Vec<&'a [u8]> {
+        let values: Vec<&'a [u8]> = match self.field {
+            SearchField::Key => vec![key],
+            SearchField::Username => identity.usernames.iter().map(|u| u.as_ref()).collect(),
+            SearchField::Email => identity.emails.iter().map(|e| e.as_ref()).collect(),
+        };
+        values.into_iter().filter(|v| self.pattern.is_match(v)).collect()
+    }
+}
+
+/// Every term passed to list-find
+#[derive(Debug, Clone)]
+pub struct LasQuery {
+    pub terms: Vec<SearchTerm>,
+    /// Match every term instead of any
+    pub all: bool,
+    pub sort: SortOrder,
+    pub limit: Option<usize>,
+}
+
+impl LasQuery {
+    pub fn from_args(args: &ArgMatches) -> Result<Self, String> {
+        let mode = if args.get_flag("regex") {
+            MatchMode::Regex
+        } else if args.get_flag("contains") {
+            MatchMode::Contains
+        } else {
+            MatchMode::Exact
+        };
+        let ignore_case = args.get_flag("ignore-case");
+
+        let mut terms = Vec::new();
+        for (id, field) in [("keys", SearchField::Key), ("usernames", SearchField::Username), ("emails", SearchField::Email)] {
+            for text in args.get_many::<String>(id).into_iter().flatten() {
+                terms.push(SearchTerm::new(field, text, mode, ignore_case)?);
+            }
+        }
+        if terms.is_empty() {
+            return Err(String::from("At least 1 identity, username or email must be provided"));
+        }
+        let sort = match args.get_one::<String>("sort").map(String::as_str) {
+            Some("key") => SortOrder::Key,
+            _ => SortOrder::Matches,
+        };
+        Ok(Self {
+            terms,
+            all: args.get_flag("all"),
+            sort,
+            limit: args.get_one::<usize>("limit").copied(),
+        })
+    }
+
+    pub fn matches(&self, key: &[u8], identity: &IdentityPerf) -> bool {
+        if self.all {
+            self.terms.iter().all(|t| t.matches(key, identity))
+        } else {
+            self.terms.iter().any(|t| t.matches(key, identity))
+        }
+    }
+
+    /// Identities matching the query across every store, each once, sorted && limited
+    pub fn find<'a>(&self, las: &'a LayeredLas<'a>) -> Vec<FoundIdentity<'a>> {
+        let mut found: Vec<FoundIdentity> = las.find_matches(|key, identity| self.matches(key, identity))
+            .into_iter()
+            .map(|entry| {
+                let (key, identity) = (entry.key, entry.identity);
+                let mut matches: Vec<FieldMatch> = Vec::new();
+                for term in &self.terms {
+                    for value in term.matched_values(key, identity) {
+                        let found = FieldMatch { field: term.field, value: String::from_utf8_lossy(value) };
+                        if !matches.contains(&found) {
+                            matches.push(found);
+                        }
+                    }
+                }
+                FoundIdentity { key, identity, source: entry.source, matches }
+            })
+            .collect();
+
+        // found is already in key order, the sort is stable
+        if self.sort == SortOrder::Matches {
+            found.sort_by_key(|f| Reverse(f.matches.len()));
+        }
+        if let Some(limit) = self.limit {
+            found.truncate(limit);
+        }
+        found
+    }
+}
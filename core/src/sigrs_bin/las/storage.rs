@@ -1,11 +1,31 @@
 use clap::ArgMatches;
 use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom, Write},
-    collections::BTreeSet
+    fs,
+    io,
 };
 
-use crate::extract_config_path;
+use crate::utils::{extract_config_path, write_atomic};
+use crate::identity::METADATA_FIELDS;
+use crate::memmap::process_las;
+use super::format::{
+    identity_entry,
+    key_line,
+    metadata_line,
+    parse_key,
+    split_values,
+    values_line,
+};
+use super::history::record_change_or_warn;
+use super::layers::missing_identity_error;
+use super::lock::lock_las_for_write;
+use super::validate::{prepare_value, ValueKind};
+use super::version::{
+    ensure_current_version,
+    las_header,
+    migrated_in_memory,
+    LAS_HEADER_COMMENT,
+    LAS_VERSION_PREFIX,
+};
 
 // ============================= Local Author Storage (LAS)
 // ========== ADD-NEW (add a new author to local author storage)
@@ -15,6 +35,8 @@ use crate::extract_config_path;
 // sigrs add --identity "some_id" --username "fluxdiv" --email "xxx@email.com"
 // * or, user can pass multiple usernames/emails at once
 // sigrs add --id "someid" --usernames "fluxdiv" "john" --emails "x@e.com" "y@e.com"
+// * optional metadata
+// sigrs add --id "someid" --usernames "x" --emails "x@e.com" --url "https://x.com" --role "Maintainer"
 pub fn handle_add_new(args: &ArgMatches) -> Result<(), String> {
     // TODO: If --identity is not passed, interactive prompt
     let Some(identity) = args.get_one::<String>("identity") else {
@@ -32,58 +54,90 @@ pub fn handle_add_new(args: &ArgMatches) -> Result<(), String> {
         .map(|vals| vals.cloned().collect())
         .ok_or_else(|| String::from("Emails required if --identity provided"))?;
 
+    // trimmed, NFC normalized && checked unless --no-validate
+    let usernames = usernames.iter()
+        .map(|u| prepare_value(args, ValueKind::Username, u))
+        .collect::<Result<Vec<String>, String>>()?;
+    let emails = emails.iter()
+        .map(|e| prepare_value(args, ValueKind::Email, e))
+        .collect::<Result<Vec<String>, String>>()?;
+
+    let entry = StoredIdentity {
+        key: identity.clone(),
+        usernames,
+        emails,
+        metadata: metadata_args(args),
+        rules: Vec::new(),
+    };
+    add_identities(args, &format!("add-new \"{}\"", identity), |_| Ok(vec![entry]))
+}
+
+/// Appends the identities returned by `build` to config.sigrs in one write.
+/// `build` gets the current file content && runs under the write lock, so
+/// what it returns can depend on what is already stored.
+/// Errors if any of the keys already exist, writes nothing if `build` returns none
+pub fn add_identities<F>(args: &ArgMatches, description: &str, build: F) -> Result<(), String>
+where
+    F: FnOnce(&str) -> Result<Vec<StoredIdentity>, String>
+{
     let config_path = extract_config_path(args)?;
+    // held until the new file is written
+    let _lock = lock_las_for_write(args, config_path.as_ref())?;
+    ensure_current_version(config_path.as_ref())?;
+
+    // Missing file is fine, a new config is created
+    let file_content = match fs::read_to_string(&config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Problem opening config file: {:?}", e)),
+    };
 
-    let mut config_handle = File::options()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(&config_path)
-        .map_err(|e| format!("Problem opening/creating config file: {:?}", e))?;
+    let identities = build(&file_content)?;
+    if identities.is_empty() {
+        return Ok(());
+    }
 
-    let mut file_content = String::new();
-    config_handle.read_to_string(&mut file_content).unwrap();
+    let mut new_file_content = file_content.clone();
 
     // If didn't exist and new config was created, add top line comment
     if file_content.is_empty() {
-        config_handle.set_len(0).unwrap();
-        config_handle.seek(SeekFrom::Start(0)).unwrap();
-        config_handle.write_all(b"# File generated by signatu_rs. Do not manually edit this file. #\n").unwrap();
+        new_file_content.push_str(&las_header());
     } else {
         // validate identity doesn't already exist
         // could use memmap here but I'm already copying file anyway
         for line in file_content.lines() {
-            if let Some(key_line) = line.strip_prefix("K:") {
-                if key_line.trim_matches('"') == identity {
-                    return Err(format!("Identity \"{}\" already exists", identity));
-                }
+            if let Some(key) = parse_key(line).filter(|key| identities.iter().any(|i| &i.key == key)) {
+                return Err(format!("Identity \"{}\" already exists", key));
             }
         }
+        // hand edits may have dropped the final newline
+        if !file_content.ends_with('\n') {
+            new_file_content.push('\n');
+        }
     }
 
-    config_handle.seek(SeekFrom::End(0)).unwrap();
-
-    let mut entry_text = String::new();
-    // key line
-    entry_text.push_str("K:");
-    entry_text.push_str(&format!("\"{}\"\n", identity));
-
-    // usernames line
-    entry_text.push_str("U:");
-    for uname in usernames.iter() {
-        entry_text.push_str(&format!("\"{}\"", uname));
-    }
-    entry_text.push_str("\n");
-
-    // emails line
-    entry_text.push_str("E:");
-    for email in emails.iter() {
-        entry_text.push_str(&format!("\"{}\"", email));
+    for (i, identity) in identities.iter().enumerate() {
+        if identities[..i].iter().any(|prev| prev.key == identity.key) {
+            return Err(format!("Identity \"{}\" is added more than once", identity.key));
+        }
+        // key line, usernames line, emails line, all values escaped
+        new_file_content.push_str(&identity_entry(
+            &identity.key,
+            &identity.usernames,
+            &identity.emails,
+            &identity.metadata,
+            &identity.rules
+        ));
     }
-    entry_text.push_str("\n");
 
-    // write entry
-    config_handle.write_all(entry_text.as_bytes()).unwrap();
+    // write entries, never touches the live file until the new one is complete
+    write_atomic(config_path.as_ref(), new_file_content.as_bytes())?;
+    record_change_or_warn(
+        config_path.as_ref(),
+        file_content.as_bytes(),
+        new_file_content.as_bytes(),
+        description
+    );
 
     Ok(())
 }
@@ -97,27 +151,39 @@ pub fn handle_add_new(args: &ArgMatches) -> Result<(), String> {
 // sigrs update --identity "some-key" --add-usernames ["guy", "guy"] --add-emails ...
 // == changes
 // sigrs update --identity "some-key" --change-identity "other-key"
-// sigrs update --identity "some-key" --change-email --old="x@e.com" --new="y@e.com"
-// sigrs update --identity "some-key" --change-username --old="guy" --new="bob"
+// sigrs update --identity "some-key" --change-email "x@e.com" "y@e.com"
+// sigrs update --identity "some-key" --change-username "guy" "bob"
+// * in place, a renamed default stays the default
+// == metadata (set or clear, see `identity::METADATA_FIELDS`)
+// sigrs update --identity "some-key" --url "https://x.com" --role "Maintainer"
+// sigrs update --identity "some-key" --clear-metadata url --clear-metadata role
+// == defaults (the default is whichever value is stored first)
+// sigrs update --identity "some-key" --set-default-username "guy"
+// sigrs update --identity "some-key" --set-default-email "x@e.com"
+// * runs after additions, so a new value can be added && made default at once
 pub fn handle_update(args: &ArgMatches) -> Result<(), String> {
 
     let identity = args.get_one::<String>("identity")
         .ok_or_else(|| String::from("--identity required"))?;
 
     let config_path = extract_config_path(args)?;
+    // held until the new file is written
+    let _lock = lock_las_for_write(args, config_path.as_ref())?;
+    ensure_current_version(config_path.as_ref())?;
 
-    // Get file handle, do not create if didn't exist - nothing ot update
-    let mut config_handle = File::options()
-        .read(true)
-        .write(true)
-        .open(&config_path)
+    // do not create if didn't exist - nothing ot update
+    let file_content = fs::read_to_string(&config_path)
         .map_err(|e| format!("Problem opening config file: {:?}", e))?;
+    // the line walk below relies on a well formed file
+    let (_comments, existing) = read_store(&file_content)
+        .map_err(|e| format!("{}\nRun `sigrs doctor` to repair config.sigrs first", e))?;
+    if let Some(new_identity) = args.get_one::<String>("change-identity") {
+        if new_identity != identity && existing.iter().any(|i| i.key == *new_identity) {
+            return Err(format!("Identity \"{}\" already exists", new_identity));
+        }
+    }
 
-    let mut file_content = String::new();
-    config_handle.read_to_string(&mut file_content).unwrap();
-    assert!(!file_content.is_empty());
-
-    let mut line_iter = file_content.lines();
+    let mut line_iter = file_content.lines().peekable();
     let mut new_file_content = String::new();
     let mut found = false;
 
@@ -131,19 +197,27 @@ pub fn handle_update(args: &ArgMatches) -> Result<(), String> {
 
         // If key line, check identity matches
         if line.starts_with("K:") {
-            // unwrappable unless file format wrong
-            let uname_line = line_iter.next().unwrap();
-            let email_line = line_iter.next().unwrap();
+            // checked above, an error rather than a panic if that ever changes
+            let missing = || format!("Identity line \"{}\" is missing its `U:`/`E:` lines", line);
+            let uname_line = line_iter.next().ok_or_else(missing)?;
+            let email_line = line_iter.next().ok_or_else(missing)?;
+            // optional
+            let meta_line = line_iter.next_if(|l| l.starts_with("M:"));
+            let rules_line = line_iter.next_if(|l| l.starts_with("R:"));
 
             // If identity doesn't match, add identity & continue
-            let id_key = line.trim_matches(&['K', ':', '"']);
-            if id_key != identity {
+            let id_key = parse_key(line).unwrap_or_default();
+            if &id_key != identity {
                 new_file_content.push_str(line);
                 new_file_content.push('\n');
                 new_file_content.push_str(uname_line);
                 new_file_content.push('\n');
                 new_file_content.push_str(email_line);
                 new_file_content.push('\n');
+                for optional_line in [meta_line, rules_line].into_iter().flatten() {
+                    new_file_content.push_str(optional_line);
+                    new_file_content.push('\n');
+                }
                 continue;
             }
 
@@ -151,7 +225,8 @@ pub fn handle_update(args: &ArgMatches) -> Result<(), String> {
             found = true;
             // handle modifications, then push rest of lines
             if let Some(new_identity) = args.get_one::<String>("change-identity") {
-                new_file_content.push_str(&format!("K:\"{}\"\n", new_identity));
+                new_file_content.push_str(&key_line(new_identity));
+                new_file_content.push('\n');
             } else {
                 new_file_content.push_str(line);
                 new_file_content.push('\n');
@@ -159,28 +234,45 @@ pub fn handle_update(args: &ArgMatches) -> Result<(), String> {
 
             // Username mod
             let mut uname_mod = uname_line.to_string();
-            modify_line(LineType::UsernameLine(&mut uname_mod), args);
+            modify_line(LineType::UsernameLine(&mut uname_mod), args)?;
             new_file_content.push_str(&uname_mod);
             new_file_content.push('\n');
 
-            // Username mod
+            // Email mod
             let mut email_mod = email_line.to_string();
-            modify_line(LineType::EmailLine(&mut email_mod), args);
+            modify_line(LineType::EmailLine(&mut email_mod), args)?;
             new_file_content.push_str(&email_mod);
             new_file_content.push('\n');
+
+            // Metadata mod, line is dropped if nothing is left
+            let mut metadata = meta_line
+                .map(|l| metadata_pairs(&l[2..]))
+                .unwrap_or_default();
+            modify_metadata(&mut metadata, args);
+            if let Some(meta_mod) = metadata_line(&metadata) {
+                new_file_content.push_str(&meta_mod);
+                new_file_content.push('\n');
+            }
+            // rules are changed through `sigrs rule`, kept as is
+            if let Some(rules_line) = rules_line {
+                new_file_content.push_str(rules_line);
+                new_file_content.push('\n');
+            }
         }
     }
 
     if !found {
-        return Err(String::from("Identity does not exist"));
+        return Err(missing_identity_error(identity));
     }
 
-    config_handle.set_len(0)
-        .map_err(|e| format!("Cannot alter file size: {:?}", e))?;
-    config_handle.seek(SeekFrom::Start(0))
-        .map_err(|e| format!("Cannot seek start of file: {:?}", e))?;
-    config_handle.write_all(new_file_content.as_bytes())
-        .map_err(|e| format!("Cannot update file: {:?}", e))?;
+    write_atomic(config_path.as_ref(), new_file_content.as_bytes())
+        .map_err(|e| format!("Cannot update file: {}", e))?;
+    record_change_or_warn(
+        config_path.as_ref(),
+        file_content.as_bytes(),
+        new_file_content.as_bytes(),
+        &format!("update \"{}\"", identity)
+    );
 
     Ok(())
 }
@@ -191,58 +283,290 @@ enum LineType<'l> {
 }
 
 // Takes in a line & args, updates that line
-fn modify_line(l: LineType<'_>, args: &ArgMatches) -> () {
+// Works on the parsed values rather than the raw line, so a value is only ever
+// matched whole (removing "b@x.com" leaves "bob@x.com" alone)
+fn modify_line(l: LineType<'_>, args: &ArgMatches) -> Result<(), String> {
     // this is modifying value passed in, not bidning, which should be fine since it's
     // allocated & not memmapped here
-    let (fmt, line) = match l {
-        LineType::UsernameLine(uline) => ("username", uline),
-        LineType::EmailLine(eline) => ("email", eline)
+    let (fmt, kind, prefix, line) = match l {
+        LineType::UsernameLine(uline) => ("username", ValueKind::Username, 'U', uline),
+        LineType::EmailLine(eline) => ("email", ValueKind::Email, 'E', eline)
     };
+    // values to remove/rename/make default may be stored from before validation
+    // existed, so they match either as passed or normalized
+    let stored_forms = |value: &str| {
+        let mut forms = vec![value.to_string()];
+        if let Ok(normalized) = prepare_value(args, kind, value) {
+            if normalized != value {
+                forms.push(normalized);
+            }
+        }
+        forms
+    };
+    let mut values = split_values(&line[2..]);
+
+    // Removals done before renames && additions
+    if args.get_flag(&format!("remove-all-{}s", fmt)) {
+        values.clear();
+    } else if let Some(remove_many) = args.get_many::<String>(&format!("remove-{}", fmt)) {
+        for remove in remove_many {
+            let forms = stored_forms(remove);
+            values.retain(|v| !forms.contains(v));
+        }
+    }
 
-    // Removals done before additions
-
-    let remove_all_flag = args.get_flag(&format!("remove-all-{}s", fmt));
-
-    if remove_all_flag {
-        // set line to just U: or E:
-        line.replace_range(2.., "");
-    } else {
-        // Remove each individually
-        let maybe_remove_many: Option<Vec<String>> = args
-            .get_many::<String>(&format!("remove-{}", fmt))
-            .map(|vals| vals.cloned().collect());
-        
-        if let Some(remove_many) = maybe_remove_many {
-            for remove in remove_many.iter() {
-                // alright commit
-                // find pattern, remove it as long as start > 2
-                if let Some(start) = line.find(remove) {
-                    // start > 2 incase "remove" is "U:" etc.
-                    if start > 2usize {
-                        // replace -1 && + 1 to remove quotes 
-                        line.replace_range((start-1)..=(start + remove.len()), "");
-                    }
-                }
+    // renames keep the value's position, && so whether it is the default
+    if let Some(change_many) = args.get_occurrences::<String>(&format!("change-{}", fmt)) {
+        for mut change in change_many {
+            let (Some(old), Some(new)) = (change.next(), change.next()) else {
+                continue;
+            };
+            let forms = stored_forms(old);
+            let Some(pos) = values.iter().position(|v| forms.contains(v)) else {
+                return Err(format!(
+                    "Cannot change {} \"{}\", it is not stored on this identity",
+                    fmt, old
+                ));
+            };
+            let new = prepare_value(args, kind, new)?;
+            if values.iter().enumerate().any(|(i, v)| i != pos && *v == new) {
+                return Err(format!(
+                    "Cannot change {} \"{}\" to \"{}\", it is already stored on this identity. Use `--remove-{}` instead",
+                    fmt, old, new, fmt
+                ));
             }
+            values[pos] = new;
         }
     }
 
-    // additions
-    let maybe_add_many: Option<Vec<String>> = args
-        .get_many::<String>(&format!("add-{}", fmt))
-        .map(|vals| vals.cloned().collect());
-
-    if let Some(add_many) = maybe_add_many {
-        // The easy way to prevent dupes but comes with allocation
-        let mut x: BTreeSet<String> = BTreeSet::new();
-        for add in add_many.iter() {
-            // create entry with wrapping "", add to line
-            if x.insert(format!("\"{}\"", add)) {
-                line.push('\"');
-                line.push_str(add);
-                line.push('\"');
+    // additions, anything already stored is not added twice
+    if let Some(add_many) = args.get_many::<String>(&format!("add-{}", fmt)) {
+        for add in add_many {
+            let add = prepare_value(args, kind, add)?;
+            if !values.contains(&add) {
+                values.push(add);
             }
         }
     }
+
+    // default is index 0, move the chosen value there && keep the rest in order
+    if let Some(default) = args.get_one::<String>(&format!("set-default-{}", fmt)) {
+        let forms = stored_forms(default);
+        let Some(pos) = values.iter().position(|v| forms.contains(v)) else {
+            return Err(format!(
+                "Cannot set default {} \"{}\", it is not stored on this identity. Add it with `--add-{}` first",
+                fmt, default, fmt
+            ));
+        };
+        let value = values.remove(pos);
+        values.insert(0, value);
+    }
+
+    *line = values_line(prefix, &values);
+    Ok(())
 }
 
+
+/// Metadata fields passed as args (`--url` etc.), in `METADATA_FIELDS` order
+fn metadata_args(args: &ArgMatches) -> Vec<(String, String)> {
+    METADATA_FIELDS
+        .iter()
+        .filter_map(|field| {
+            args.get_one::<String>(field)
+                .map(|value| (field.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+/// (field, value) pairs from the body of an `M:` line
+fn metadata_pairs(body: &str) -> Vec<(String, String)> {
+    let mut values = split_values(body).into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(field), Some(value)) = (values.next(), values.next()) {
+        pairs.push((field, value));
+    }
+    pairs
+}
+
+// Clears, then sets metadata fields. Known fields are kept in
+// `METADATA_FIELDS` order, anything else is kept after them
+fn modify_metadata(metadata: &mut Vec<(String, String)>, args: &ArgMatches) {
+    if let Some(clear) = args.get_many::<String>("clear-metadata") {
+        let clear: Vec<&String> = clear.collect();
+        metadata.retain(|(field, _)| !clear.contains(&field));
+    }
+
+    for (field, value) in metadata_args(args) {
+        match metadata.iter_mut().find(|(f, _)| *f == field) {
+            Some(existing) => existing.1 = value,
+            None => metadata.push((field, value)),
+        }
+    }
+
+    sort_metadata(metadata);
+}
+
+/// Known fields in `METADATA_FIELDS` order, anything else kept after them
+pub fn sort_metadata(metadata: &mut [(String, String)]) {
+    metadata.sort_by_key(|(field, _)| {
+        METADATA_FIELDS
+            .iter()
+            .position(|known| known == field)
+            .unwrap_or(METADATA_FIELDS.len())
+    });
+}
+
+// ============================= Whole store read/write
+// For commands that rewrite every identity at once (import etc.)
+
+/// Owned copy of a stored identity
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StoredIdentity {
+    pub key: String,
+    pub usernames: Vec<String>,
+    pub emails: Vec<String>,
+    pub metadata: Vec<(String, String)>,
+    /// (kind, pattern), see `rules.rs`
+    pub rules: Vec<(String, String)>,
+}
+
+/// Reads config.sigrs content through `process_las`, so anything it would reject
+/// is rejected here too. Returns (user comments, identities in file order)
+pub fn read_store(content: &str) -> Result<(Vec<String>, Vec<StoredIdentity>), String> {
+    // new/reset store
+    if content.trim().is_empty() {
+        return Ok((vec![], vec![]));
+    }
+
+    let las = process_las(content.as_bytes()).map_err(|e| e.to_string())?;
+    let lossy = |v: &[u8]| String::from_utf8_lossy(v).into_owned();
+
+    let comments = content
+        .lines()
+        .filter(|line| line.starts_with('#'))
+        .filter(|line| *line != LAS_HEADER_COMMENT && !line.starts_with(LAS_VERSION_PREFIX))
+        .map(String::from)
+        .collect();
+
+    // process_las sorts by key, file order comes from the key lines
+    let mut identities = Vec::new();
+    for key in content.lines().filter_map(parse_key) {
+        let Some((_, identity)) = las.lookup_id(&key) else {
+            continue;
+        };
+        identities.push(StoredIdentity {
+            usernames: identity.usernames.iter().map(|u| lossy(u)).collect(),
+            emails: identity.emails.iter().map(|e| lossy(e)).collect(),
+            metadata: identity.metadata.iter().map(|(f, v)| (lossy(f), lossy(v))).collect(),
+            rules: identity.rules.iter().map(|(k, p)| (lossy(k), lossy(p))).collect(),
+            key,
+        });
+    }
+    Ok((comments, identities))
+}
+
+/// Inverse of `read_store`
+pub fn render_store(comments: &[String], identities: &[StoredIdentity]) -> String {
+    let mut content = las_header();
+    for comment in comments {
+        content.push_str(comment);
+        content.push('\n');
+    }
+    for identity in identities {
+        content.push_str(&identity_entry(
+            &identity.key,
+            &identity.usernames,
+            &identity.emails,
+            &identity.metadata,
+            &identity.rules
+        ));
+    }
+    content
+}
+
+/// Comments && identities of config.sigrs as `rewrite_store` would see them, but
+/// without locking, migrating on disk or creating the file. For dry runs
+pub fn read_store_unlocked(args: &ArgMatches) -> Result<(Vec<String>, Vec<StoredIdentity>), String> {
+    let config_path = extract_config_path(args)?;
+    let file_content = match fs::read_to_string(&config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Problem opening config file: {:?}", e)),
+    };
+    read_store(&migrated_in_memory(&file_content)?)
+        .map_err(|e| format!("{}\nRun `sigrs doctor` to repair config.sigrs first", e))
+}
+
+/// Replaces the identities in config.sigrs with what `update` returns, keeping comments.
+/// `update` runs under the write lock. Nothing is written if the identities are unchanged
+pub fn rewrite_store<F>(args: &ArgMatches, description: &str, update: F) -> Result<(), String>
+where
+    F: FnOnce(Vec<StoredIdentity>) -> Result<Vec<StoredIdentity>, String>
+{
+    let config_path = extract_config_path(args)?;
+    // held until the new file is written
+    let _lock = lock_las_for_write(args, config_path.as_ref())?;
+    ensure_current_version(config_path.as_ref())?;
+
+    // Missing file is fine, a new config is created
+    let file_content = match fs::read_to_string(&config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Problem opening config file: {:?}", e)),
+    };
+    let (comments, existing) = read_store(&file_content)
+        .map_err(|e| format!("{}\nRun `sigrs doctor` to repair config.sigrs first", e))?;
+
+    let identities = update(existing.clone())?;
+    if identities == existing && !file_content.is_empty() {
+        return Ok(());
+    }
+
+    let new_file_content = render_store(&comments, &identities);
+    write_atomic(config_path.as_ref(), new_file_content.as_bytes())?;
+    record_change_or_warn(
+        config_path.as_ref(),
+        file_content.as_bytes(),
+        new_file_content.as_bytes(),
+        description
+    );
+    Ok(())
+}
+
+/// Lowercase, with runs of anything but letters/digits replaced by `-`
+fn slug(value: &str) -> String {
+    let mut slug = String::new();
+    for c in value.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// A key for a new identity not in `taken`. Tries the name,
+/// then name + email domain, then name + a number
+pub fn unique_key(name: &str, email: &str, taken: &[String]) -> String {
+    let base = match slug(name) {
+        name if !name.is_empty() => name,
+        _ => slug(email.split('@').next().unwrap_or_default()),
+    };
+    let base = if base.is_empty() { String::from("author") } else { base };
+    let domain = email
+        .rsplit_once('@')
+        .map(|(_, domain)| slug(domain.split('.').next().unwrap_or(domain)))
+        .filter(|domain| !domain.is_empty());
+
+    let mut options = vec![base.clone()];
+    if let Some(domain) = domain {
+        options.push(format!("{}-{}", base, domain));
+    }
+    if let Some(key) = options.into_iter().find(|key| !taken.contains(key)) {
+        return key;
+    }
+    (2..).map(|n| format!("{}-{}", base, n))
+        .find(|key| !taken.contains(key))
+        .unwrap_or(base)
+}
@@ -0,0 +1,122 @@
+// ============================= Username / email validation
+// Values end up verbatim in published manifests, so before add-new/update store them:
+// - surrounding whitespace is trimmed
+// - unicode is NFC normalized, so "é" typed as 1 or 2 code points is stored the same
+// - control characters (newlines etc.) are rejected
+// - emails must be an RFC 5322 addr-spec, `local@domain` without comments or
+//   display names. Non-ASCII is allowed, as in RFC 6532
+// `--no-validate` stores values exactly as passed
+use clap::ArgMatches;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind {
+    Username,
+    Email,
+}
+
+impl ValueKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Username => "username",
+            Self::Email => "email",
+        }
+    }
+}
+
+/// RFC 5322 atext, plus any non-ASCII (RFC 6532)
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c) || !c.is_ascii()
+}
+
+/// `atext+ ("." atext+)*`
+fn check_dot_atom(value: &str, part: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Err(format!("the {} is empty", part));
+    }
+    for atom in value.split('.') {
+        if atom.is_empty() {
+            return Err(format!("the {} cannot start or end with `.`, or contain `..`", part));
+        }
+        if let Some(c) = atom.chars().find(|c| !is_atext(*c)) {
+            return Err(format!("`{}` is not allowed in the {} unless it is quoted", c, part));
+        }
+    }
+    Ok(())
+}
+
+/// `"..."` with qtext, spaces && quoted pairs
+fn check_quoted_local(value: &str) -> Result<(), String> {
+    let inner = &value[1..value.len() - 1];
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.next().is_none() => {
+                return Err(String::from("the local part ends with an unfinished `\\`"));
+            },
+            '"' => return Err(String::from("`\"` inside a quoted local part must be escaped")),
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+/// `[...]` with dtext
+fn check_domain_literal(value: &str) -> Result<(), String> {
+    let inner = &value[1..value.len() - 1];
+    match inner.chars().find(|c| matches!(c, '[' | ']' | '\\') || c.is_whitespace()) {
+        Some(c) => Err(format!("`{}` is not allowed in a domain literal", c)),
+        None => Ok(()),
+    }
+}
+
+/// Checks `email` is an RFC 5322 addr-spec (`local@domain`)
+pub fn check_email(email: &str) -> Result<(), String> {
+    // the local part may contain a quoted `@`, the domain can't
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return Err(String::from("missing `@`"));
+    };
+
+    if local.len() >= 2 && local.starts_with('"') && local.ends_with('"') {
+        check_quoted_local(local)?;
+    } else {
+        check_dot_atom(local, "local part (before `@`)")?;
+    }
+
+    if domain.starts_with('[') && domain.ends_with(']') {
+        check_domain_literal(domain)
+    } else {
+        check_dot_atom(domain, "domain (after `@`)")
+    }
+}
+
+/// Trims && NFC normalizes `value`, then checks it is a valid `kind`
+pub fn normalize_value(kind: ValueKind, value: &str) -> Result<String, String> {
+    let normalized: String = value.trim().nfc().collect();
+    let invalid = |reason: String| {
+        format!(
+            "Invalid {} \"{}\": {}. Use `--no-validate` to store it anyway",
+            kind.name(), value.escape_debug(), reason
+        )
+    };
+
+    if normalized.is_empty() {
+        return Err(invalid(String::from("it is empty")));
+    }
+    if normalized.chars().any(char::is_control) {
+        return Err(invalid(String::from("it contains a control character (newline, tab...)")));
+    }
+    if kind == ValueKind::Email {
+        check_email(&normalized).map_err(invalid)?;
+    }
+    Ok(normalized)
+}
+
+/// `normalize_value`, or `value` unchanged if `--no-validate` was passed
+pub fn prepare_value(args: &ArgMatches, kind: ValueKind, value: &str) -> Result<String, String> {
+    if args.get_flag("no-validate") {
+        Ok(value.to_string())
+    } else {
+        normalize_value(kind, value)
+    }
+}
@@ -0,0 +1,237 @@
+// ============================= config.sigrs (LAS) schema versioning
+// The first lines of every config.sigrs are comments, the 2nd being a
+// machine readable version marker:
+//
+// # File generated by signatu_rs. Do not manually edit this file. #
+// #!las-version:1
+//
+// Since the marker starts with `#`, older sigrs versions skip over it like
+// any other comment.
+//
+// Version history
+// 0 | No version marker, values written between quotes without escaping
+// 1 | Values escaped, see `las/format.rs`
+// 2 | Optional `M:` metadata line after `E:`
+// 3 | Optional `R:` identity selection rules line after `E:`/`M:`, see `las/rules.rs`
+//
+// When the schema changes:
+// - bump `LAS_VERSION`
+// - append a `migrate_vX_to_vY` fn to `MIGRATIONS`
+// - update the empty templates in modifier_bin && xtask, which can't call `las_header()`
+// Files on an older version are upgraded in place (with a backup) the next
+// time any LAS command touches them, see `ensure_current_version`.
+// Callers must hold an exclusive lock (`las/lock.rs`) while migrating.
+// Files that must not be written are migrated in memory, see `migrated_in_memory`
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use crate::utils::write_atomic;
+use super::format::{key_line, values_line};
+
+pub const LAS_VERSION: u32 = 3;
+pub const LAS_HEADER_COMMENT: &str = "# File generated by signatu_rs. Do not manually edit this file. #";
+pub const LAS_VERSION_PREFIX: &str = "#!las-version:";
+
+/// Migration from version `idx` to version `idx + 1`
+type Migration = fn(&str) -> Result<String, String>;
+const MIGRATIONS: &[Migration] = &[
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+];
+
+/// Header lines (including trailing newline) every config.sigrs starts with
+pub fn las_header() -> String {
+    format!("{}\n{}{}\n", LAS_HEADER_COMMENT, LAS_VERSION_PREFIX, LAS_VERSION)
+}
+
+/// Reads the version marker from the leading comment lines of `content`
+/// Returns 0 if there is no marker (pre-versioning file)
+pub fn read_version(content: &[u8]) -> Result<u32, String> {
+    for line in content.split(|&b| b == b'\n') {
+        if !line.starts_with(b"#") {
+            // header is over
+            break;
+        }
+        if let Some(raw) = line.strip_prefix(LAS_VERSION_PREFIX.as_bytes()) {
+            let raw = String::from_utf8_lossy(raw);
+            return raw.trim().parse::<u32>()
+                .map_err(|_| format!("Invalid LAS version marker: \"{}\"", raw.trim()));
+        }
+    }
+    Ok(0)
+}
+
+/// Errors unless `version` is exactly the version this binary reads/writes
+pub fn check_version(version: u32) -> Result<(), String> {
+    if version > LAS_VERSION {
+        return Err(format!(
+            "config.sigrs uses LAS version {}, but this sigrs only supports up to version {}. Update sigrs to use this file",
+            version, LAS_VERSION
+        ));
+    }
+    if version < LAS_VERSION {
+        return Err(format!(
+            "config.sigrs uses LAS version {} and must be migrated to version {}",
+            version, LAS_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Runs every migration needed to bring `content` from `from` up to `LAS_VERSION`
+pub fn migrate_content(content: &str, from: u32) -> Result<String, String> {
+    if from > LAS_VERSION {
+        check_version(from)?;
+    }
+    let mut migrated = content.to_string();
+    for migration in &MIGRATIONS[from as usize..] {
+        migrated = migration(&migrated)?;
+    }
+    Ok(migrated)
+}
+
+/// `content` as it would read after migrating, without touching any file. Used
+/// where the file must not be written (read-only stores, dry runs)
+/// Empty content is returned as is
+pub fn migrated_in_memory(content: &str) -> Result<Cow<'_, str>, String> {
+    if content.is_empty() {
+        return Ok(Cow::Borrowed(content));
+    }
+    match read_version(content.as_bytes())? {
+        LAS_VERSION => Ok(Cow::Borrowed(content)),
+        version => migrate_content(content, version).map(Cow::Owned),
+    }
+}
+
+/// Whether the config.sigrs at `path` is on another LAS version
+/// Missing or empty files don't need migrating
+pub fn needs_migration(path: &Path) -> Result<bool, String> {
+    let Ok(content) = fs::read(path) else {
+        return Ok(false);
+    };
+    if content.is_empty() {
+        return Ok(false);
+    }
+    Ok(read_version(&content)? != LAS_VERSION)
+}
+
+/// Upgrades the config.sigrs at `path` in place if it is on an older LAS version.
+/// The original file is copied to `<path>.v<old version>.bak` before being rewritten
+/// Missing or empty files are left alone
+pub fn ensure_current_version(path: &Path) -> Result<(), String> {
+    let content = match fs::read(path) {
+        Ok(c) => c,
+        // let the caller decide what a missing file means
+        Err(_) => return Ok(()),
+    };
+    if content.is_empty() {
+        return Ok(());
+    }
+
+    let version = read_version(&content)?;
+    if version == LAS_VERSION {
+        return Ok(());
+    }
+    if version > LAS_VERSION {
+        return check_version(version);
+    }
+
+    let content = String::from_utf8(content)
+        .map_err(|e| format!("config.sigrs is not valid UTF-8: {}", e))?;
+    let migrated = migrate_content(&content, version)?;
+
+    let backup_path = backup_path(path, &format!("v{}", version));
+    fs::copy(path, &backup_path)
+        .map_err(|e| format!("Problem backing up config before migration: {:?}", e))?;
+    write_atomic(path, migrated.as_bytes())
+        .map_err(|e| format!("Problem writing migrated config: {}", e))?;
+
+    eprintln!(
+        "Migrated {} from LAS version {} to {}. Backup saved at {}",
+        path.display(), version, LAS_VERSION, backup_path.display()
+    );
+    Ok(())
+}
+
+/// `<path>.<tag>.bak`, or `<path>.<tag>.<unix secs>.bak` if that already exists
+pub fn backup_path(path: &Path, tag: &str) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}.bak", tag));
+    let backup = PathBuf::from(backup);
+    if !backup.exists() {
+        return backup;
+    }
+
+    let now = UNIX_EPOCH.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}.{}.bak", tag, now));
+    PathBuf::from(backup)
+}
+
+// ============================= Migrations
+
+/// v0 wrote values between quotes as-is, re-write every value escaped
+/// && add the version marker
+/// Lines that can't be understood are kept as-is for `sigrs doctor` to report
+fn migrate_v0_to_v1(content: &str) -> Result<String, String> {
+    // v0 values are everything between each pair of quotes
+    fn legacy_values(body: &str) -> Vec<&str> {
+        body.split('"').skip(1).step_by(2).collect()
+    }
+
+    let mut migrated = String::new();
+    migrated.push_str(LAS_HEADER_COMMENT);
+    migrated.push('\n');
+    migrated.push_str(LAS_VERSION_PREFIX);
+    migrated.push_str("1\n");
+
+    for line in content.lines() {
+        // generated header is re-written above, keep any other comments
+        if line == LAS_HEADER_COMMENT {
+            continue;
+        }
+        let rewritten = match line.get(0..2) {
+            Some("K:") if legacy_values(&line[2..]).len() == 1 => {
+                key_line(legacy_values(&line[2..])[0])
+            },
+            Some(prefix @ ("U:" | "E:")) => {
+                values_line(prefix.chars().next().unwrap_or('U'), &legacy_values(&line[2..]))
+            },
+            _ => line.to_string(),
+        };
+        migrated.push_str(&rewritten);
+        migrated.push('\n');
+    }
+
+    Ok(migrated)
+}
+
+/// Rewrites the version marker only, for versions that just add optional lines
+fn bump_marker(content: &str, version: u32) -> String {
+    let mut migrated = String::new();
+    for line in content.lines() {
+        if line.starts_with(LAS_VERSION_PREFIX) {
+            migrated.push_str(LAS_VERSION_PREFIX);
+            migrated.push_str(&version.to_string());
+        } else {
+            migrated.push_str(line);
+        }
+        migrated.push('\n');
+    }
+    migrated
+}
+
+/// v2 only adds the optional `M:` line, bump the marker so older sigrs
+/// versions refuse the file instead of failing on `M:` lines
+fn migrate_v1_to_v2(content: &str) -> Result<String, String> {
+    Ok(bump_marker(content, 2))
+}
+
+/// v3 only adds the optional `R:` line, same as v2
+fn migrate_v2_to_v3(content: &str) -> Result<String, String> {
+    Ok(bump_marker(content, 3))
+}
@@ -13,14 +13,27 @@ use clap::{
 use anyhow::Result;
 
 mod identity;
+use identity::METADATA_FIELDS;
 
 mod las;
 use las::list::{handle_list, handle_list_find, handle_list_by_id};
+use las::output::OutputFormat;
 use las::storage::{handle_update, handle_add_new};
 use las::delete::handle_delete;
+use las::merge::{handle_clone, handle_merge};
+use las::profile::handle_profile;
+use las::rules::handle_rule;
+use las::doctor::handle_doctor;
+use las::config::{handle_reset_config, handle_generate_config};
+use las::lock::DEFAULT_LOCK_TIMEOUT;
+use las::history::{handle_undo, handle_redo, handle_history, HISTORY_LIMIT};
+use las::export::{handle_export, handle_import};
+use las::git_import::handle_import_git;
+use las::harvest::handle_harvest;
+use las::mailmap::{handle_import_mailmap, handle_export_mailmap};
 
 mod signing;
-use signing::handle::handle_sign;
+use signing::handle::{handle_sign, handle_whoami};
 
 mod utils;
 use utils::*;
@@ -38,8 +51,8 @@ fn main() -> Result<(), String> {
             print_bin_path()?;
         },
         Some(("get-config-path", sub_matches)) => {
-            // read appended config on this binary
-            let cfg_path = get_config_path()?;
+            // config saved in this binary, or the active/--profile profile's
+            let cfg_path = extract_config_path(sub_matches)?;
             let mut stdout = std::io::stdout().lock();
             stdout.write_all(cfg_path.as_bytes())
                 .map_err(|e| e.to_string())?;
@@ -48,6 +61,15 @@ fn main() -> Result<(), String> {
         Some(("las", _sub_matches)) => {
             println!("{}", LAS_HELP);
         },
+        Some(("doctor", sub_matches)) => {
+            handle_doctor(sub_matches)?;
+        },
+        Some(("reset-config", sub_matches)) => {
+            handle_reset_config(sub_matches)?;
+        },
+        Some(("generate-config", sub_matches)) => {
+            handle_generate_config(sub_matches)?;
+        },
         // =============== Signing
         Some(("sign", sub_matches)) => {
             unsafe {
@@ -58,13 +80,64 @@ fn main() -> Result<(), String> {
         Some(("add-new", sub_matches)) => {
             handle_add_new(sub_matches)?;
         },
-        // TODO Default username/email 
         Some(("update", sub_matches)) => {
             handle_update(sub_matches)?;
         },
         Some(("delete", sub_matches)) => {
             handle_delete(sub_matches)?;
         },
+        Some(("profile", sub_matches)) => {
+            handle_profile(sub_matches)?;
+        },
+        Some(("clone", sub_matches)) => {
+            handle_clone(sub_matches)?;
+        },
+        Some(("merge", sub_matches)) => {
+            handle_merge(sub_matches)?;
+        },
+        Some(("rule", sub_matches)) => {
+            unsafe {
+                handle_rule(sub_matches)?;
+            }
+        },
+        Some(("whoami", sub_matches)) => {
+            unsafe {
+                handle_whoami(sub_matches)?;
+            }
+        },
+        // ============== History
+        Some(("undo", sub_matches)) => {
+            handle_undo(sub_matches)?;
+        },
+        Some(("redo", sub_matches)) => {
+            handle_redo(sub_matches)?;
+        },
+        Some(("history", sub_matches)) => {
+            handle_history(sub_matches)?;
+        },
+        // ============== Import / Export
+        Some(("export", sub_matches)) => {
+            unsafe {
+                handle_export(sub_matches)?;
+            }
+        },
+        Some(("import", sub_matches)) => {
+            handle_import(sub_matches)?;
+        },
+        Some(("import-git", sub_matches)) => {
+            handle_import_git(sub_matches)?;
+        },
+        Some(("harvest", sub_matches)) => {
+            handle_harvest(sub_matches)?;
+        },
+        Some(("import-mailmap", sub_matches)) => {
+            handle_import_mailmap(sub_matches)?;
+        },
+        Some(("export-mailmap", sub_matches)) => {
+            unsafe {
+                handle_export_mailmap(sub_matches)?;
+            }
+        },
         // ============== Listing
         Some(("list-all", sub_matches)) => {
             unsafe {
@@ -98,6 +171,14 @@ pub fn sigrs_command() -> Command {
         .bin_name("sigrs")
         .display_name("sigrs")
         .color(clap::ColorChoice::Always)
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .global(true)
+                .next_line_help(true)
+                .help("Use this profile's config.sigrs for this command instead of the active one. `--config-path` wins over it, it wins over the SIGRS_CONFIG_PATH && SIGRS_PROFILE environment variables")
+                .value_parser(NonEmptyStringValueParser::new())
+        )
         // ================================= Misc
         .subcommand(
             Command::new("get-bin-path")
@@ -106,7 +187,8 @@ pub fn sigrs_command() -> Command {
         .subcommand(
             Command::new("get-config-path")
                 .long_flag("get-config-path")
-                .about("Check the currently saved config path")
+                .about("Print the config.sigrs path commands will use")
+                .long_about("Print the config.sigrs path commands will use: `--profile`, then the SIGRS_CONFIG_PATH or SIGRS_PROFILE environment variable, then the active profile, then the path saved in the sigrs_function binary")
         )
         .subcommand(
             Command::new("las")
@@ -117,6 +199,50 @@ pub fn sigrs_command() -> Command {
                 .next_line_help(true)
                 .long_about(LAS_HELP)
         )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check config.sigrs for formatting problems, and optionally repair them")
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .next_line_help(true)
+                        .help("Rewrite config.sigrs without the problems found. The original file is backed up next to it first")
+                        .long_help("Rewrite config.sigrs without the problems found. The original file is backed up to `config.sigrs.doctor.bak` first.\nDuplicate identities are merged, lines that cannot be read are removed, and missing `U:`/`E:` lines are added back empty.\nIdentities with no usernames or emails must still be fixed with `sigrs update`")
+                        .action(ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("reset-config")
+                .about("Archive the current config.sigrs and replace it with an empty template")
+                .long_about("Archive the current config.sigrs and replace it with an empty template.\nThe current file is renamed to `config.sigrs.reset-<unix timestamp>.bak` next to it, so nothing is lost")
+                .arg(config_path())
+                .arg(lock_timeout())
+        )
+        .subcommand(
+            Command::new("generate-config")
+                .long_flag("generate-config")
+                .about("Create an empty template config.sigrs at a location of your choosing")
+                .long_about(GENERATE_CONFIG_ABOUT)
+                .arg_required_else_help(true)
+                .arg(
+                    Arg::new("path")
+                        .required(true)
+                        .long("path")
+                        .short('p')
+                        .next_line_help(true)
+                        .help("Where to create the template. Missing parent directories are created")
+                        .value_parser(value_parser!(OsString))
+                )
+                .arg(
+                    Arg::new("register")
+                        .long("register")
+                        .next_line_help(true)
+                        .help("Also save `--path` as the config path, same as `sigrs set-config-path --path=<PATH> --no-generate`")
+                        .action(ArgAction::SetTrue)
+                )
+        )
         .subcommand(
             Command::new("sign")
                 .about("Sign your credentials to a configuration file(s)")
@@ -125,23 +251,26 @@ pub fn sigrs_command() -> Command {
                 // Choose which directories to look for configurations
                 // Choose what kind of configs to look for (if-signable, cargo.toml ..
                 .arg(config_path())
+                .arg(lock_timeout())
                 .arg(
                     Arg::new("identity")
-                        .help("Identity to use when signing a configuration file(s)")
+                        .help("Identity to use when signing a configuration file(s). Defaults to SIGRS_IDENTITY, then the one named in the nearest .sigrs.toml")
                         // .long_help("Info about how default is used etc.")
                         .long("identity")
                         .alias("id")
                 )
                 .arg(
                     Arg::new("username")
-                        .help("Sign with a custom, one-off username not stored in LAS")
+                        .long("username")
+                        .help("Sign with a custom, one-off username not stored in LAS. Defaults to SIGRS_USERNAME")
                         // .long_help("Yes, shouldn't force user to use LAS")
                         // features like sign all configs in working dir etc.
                         // .long_help("Because the signing features alone are useful")
                 )
                 .arg(
                     Arg::new("email")
-                        .help("Sign with a custom, one-off email not stored in LAS")
+                        .long("email")
+                        .help("Sign with a custom, one-off email not stored in LAS. Defaults to SIGRS_EMAIL")
                 )
                 .arg(
                     Arg::new("file").short('f')
@@ -178,11 +307,13 @@ pub fn sigrs_command() -> Command {
                 )
                 .arg(
                     Arg::new("working-dir")
+                        .long("working-dir")
                         .help("Search the current working directory, but not subdirectories within it")
                         .action(ArgAction::SetTrue)
                 )
                 .arg(
                     Arg::new("working-dir-recursive")
+                        .long("working-dir-recursive")
                         .help("Search current working directory && all sub-directories recursively. Optionally provide an integer value to be the maximum depth. If no value is passed, a default of `-1` is used, which is equivalent to 'search ALL sub-directories recursively'\nEx: `--working-dir-recursive` - Search ALL sub-dirs recursively\n`--working-dir-recursive=-1` - Search ALL sub-dirs recursively (same as passing no value)\n`--working-dir-recursive=0` - Search only working dir (same as `--working-dir` non-recursive)\n`--working-dir-recursive=2` - Search sub-dirs max depth 2 (`=` equal sign REQUIRED)")
                         .next_line_help(true)
                         .value_parser(value_parser!(i8).range(-1..))
@@ -202,6 +333,7 @@ pub fn sigrs_command() -> Command {
                 // =========================== File matching
                 .arg(
                     Arg::new("only-include")
+                        .long("only-include")
                         .next_line_help(true)
                         .help("Only include these types of configuration files. All others will be ignored. Cannot be used in conjunction with `only-exclude`\nBy default, ALL configuration types will be considered if `only-include` or `only-exclude` is not used.\nRun --help for how to use this parameter")
                         .long_help("Examples using default delimiter `::`, replace with custom delimiter if using --delimiter\nSingle | `--only-include Cargo.toml`\nMultiple | `--only-include Cargo.toml::package.json::setup.py`\nPossible values (casing DOES matter): < `Cargo.toml`, `package.json`, `pyproject.toml`, `setup.py`, `setup.cfg` >")
@@ -209,6 +341,7 @@ pub fn sigrs_command() -> Command {
                 )
                 .arg(
                     Arg::new("only-exclude")
+                        .long("only-exclude")
                         .next_line_help(true)
                         .help("Only exclude these types of configuration files. All others will be included. Cannot be used in conjunction with `only-include`\nBy default, ALL configuration types will be considered if `only-include` or `only-exclude` is not used.")
                         .long_help("Examples using default delimiter `::`, replace with custom delimiter if using --delimiter\nSingle | `--only-exclude Cargo.toml`\nMultiple | `--only-exclude Cargo.toml::package.json::setup.py`\nPossible values (casing DOES matter): < `Cargo.toml`, `package.json`, `pyproject.toml`, `setup.py`, `setup.cfg` >")
@@ -221,6 +354,7 @@ pub fn sigrs_command() -> Command {
                 )
                 .arg(
                     Arg::new("if-signable")
+                        .long("if-signable")
                         .next_line_help(true)
                         .help("Only sign matching configuration files if they already have authors")
                         .long_help("After finding configuration files that match your parameters, sigrs will check each of these files to see if it already has an authors field (or equivalent, depending on the type of configuration file) present, and your signature will only be added to the files that do.\n\nA config file with an empty authors list (but with an authors field present) WILL be signed.\n\nIf you only want to append your signature to an authors field if it already has 1+ authors within it, use `if-has-signatures` instead")
@@ -228,6 +362,7 @@ pub fn sigrs_command() -> Command {
                 )
                 .arg(
                     Arg::new("if-has-signatures")
+                        .long("if-has-signatures")
                         .next_line_help(true)
                         .help("Only sign matching configuration files if they already have authors")
                         .long_help("After finding configuration files that match your parameters, sigrs will check each of these files to see if it already has other authors listed, and your signature will only be added to the files that do.\n\nA config with an empty authors list (but with an authors field present) will NOT be signed.")
@@ -239,6 +374,13 @@ pub fn sigrs_command() -> Command {
                         .multiple(false)
                         .required(false)
                 )
+                .arg(
+                    Arg::new("no-project-file")
+                        .long("no-project-file")
+                        .help("Ignore any .sigrs.toml in the working dir or above it")
+                        .long_help("sign looks for a .sigrs.toml in the working dir && every dir above it. The first one found names the identity, username/email overrides && default filters for that repository. Flags passed always win over the file. This flag skips the lookup entirely")
+                        .action(ArgAction::SetTrue)
+                )
 
             // give users an option to specify --Cargo.toml or config type
             // --if-has-authors  | Only sign matching configs if it has authors already
@@ -257,6 +399,7 @@ pub fn sigrs_command() -> Command {
             Command::new("add-new")
                 .about("Create a new identity and save it in Local Author Storage")
                 .arg(config_path())
+                .arg(lock_timeout())
                 .arg(
                     Arg::new("identity")
                         .long("identity")
@@ -282,13 +425,15 @@ pub fn sigrs_command() -> Command {
                         .long_help("Emails to add to created identity. To include multiple emails, prefix each email with `-E`\nEx: `sigrs add-new --identity \"some_id\" -E \"x@y.com\" -E \"y@x.com\"`")
                         .action(ArgAction::Append)
                 )
-
+                .args(metadata_args())
+                .arg(no_validate())
         )
         .subcommand(
             Command::new("update")
                 .about("Update an existing identity stored in LAS")
                 .arg_required_else_help(true)
                 .arg(config_path())
+                .arg(lock_timeout())
                 .arg(
                     Arg::new("identity")
                         .long("identity")
@@ -314,6 +459,26 @@ pub fn sigrs_command() -> Command {
                         .long_help("Emails to add to this identity. To include multiple emails, prefix each email with `--ae`.\nEx: `sigrs update --identity \"x\" --ae \"x@y.com\" --ae \"y@x.com\"` ")
                         .action(ArgAction::Append)
                 )
+                .arg(
+                    Arg::new("change-username").next_line_help(true)
+                        .long("change-username")
+                        .alias("cu")
+                        .value_names(["OLD", "NEW"])
+                        .num_args(2)
+                        .help("Rename a stored username in place, keeping its position (&& whether it is the default)")
+                        .long_help("Rename a stored username in place, keeping its position (&& whether it is the default). To rename multiple usernames, prefix each pair with `--cu`.\nEx: `sigrs update --identity \"x\" --cu \"bob\" \"Bob Smith\"` ")
+                        .action(ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("change-email").next_line_help(true)
+                        .long("change-email")
+                        .alias("ce")
+                        .value_names(["OLD", "NEW"])
+                        .num_args(2)
+                        .help("Replace a stored email in place, keeping its position (&& whether it is the default)")
+                        .long_help("Replace a stored email in place, keeping its position (&& whether it is the default). To change multiple emails, prefix each pair with `--ce`.\nEx: `sigrs update --identity \"x\" --ce \"old@x.com\" \"new@x.com\"` ")
+                        .action(ArgAction::Append)
+                )
                 .arg(
                     Arg::new("remove-username").next_line_help(true)
                         .long("remove-username")
@@ -340,6 +505,32 @@ pub fn sigrs_command() -> Command {
                         .long_help("Remove all usernames from this identity.\nIf called with `add-username`, all usernames will be cleared before adding the new ones provided.")
                         .action(ArgAction::SetTrue)
                 )
+                .args(metadata_args())
+                .arg(
+                    Arg::new("clear-metadata").next_line_help(true)
+                        .long("clear-metadata")
+                        .help("Remove a metadata field from this identity")
+                        .long_help("Remove a metadata field from this identity. To clear multiple fields, prefix each field with `--clear-metadata`.\nIf called with the same field's set option (e.g. `--url`), the field is cleared then set to the new value.\nEx: `sigrs update --identity \"x\" --clear-metadata url --clear-metadata role` ")
+                        .value_parser(METADATA_FIELDS)
+                        .action(ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("set-default-username").next_line_help(true)
+                        .long("set-default-username")
+                        .alias("du")
+                        .help("Make this username the default used by `sigrs sign` when `--username` is not passed")
+                        .long_help("Make this username the default used by `sigrs sign` when `--username` is not passed.\nThe username must already be stored on this identity, or be added in the same command with `--au`.\nEx: `sigrs update --identity \"x\" --set-default-username \"rob\"` ")
+                        .value_parser(NonEmptyStringValueParser::new())
+                )
+                .arg(
+                    Arg::new("set-default-email").next_line_help(true)
+                        .long("set-default-email")
+                        .alias("de")
+                        .help("Make this email the default used by `sigrs sign` when `--email` is not passed")
+                        .long_help("Make this email the default used by `sigrs sign` when `--email` is not passed.\nThe email must already be stored on this identity, or be added in the same command with `--ae`.\nEx: `sigrs update --identity \"x\" --set-default-email \"y@x.com\"` ")
+                        .value_parser(NonEmptyStringValueParser::new())
+                )
+                .arg(no_validate())
                 .group(
                     // Only 1 removal method can be given for each
                     ArgGroup::new("removal-usernames")
@@ -354,12 +545,234 @@ pub fn sigrs_command() -> Command {
                         .required(false)
                 )
         )
+        // ===================================================== HISTORY
+        .subcommand(
+            Command::new("undo")
+                .about("Undo the last add-new, update or delete")
+                .long_about(history_about())
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(history_force())
+        )
+        .subcommand(
+            Command::new("redo")
+                .about("Redo the last undone change")
+                .long_about(history_about())
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(history_force())
+        )
+        .subcommand(
+            Command::new("history")
+                .about("List recorded changes that can be undone/redone")
+                .long_about(history_about())
+                .arg(config_path())
+                .arg(lock_timeout())
+        )
+        // ===================================================== IMPORT / EXPORT
+        .subcommand(
+            Command::new("export")
+                .about("Write every identity in LAS as JSON or TOML")
+                .long_about("Write every identity in LAS as JSON or TOML, to stdout or to `--output`.\nThe result can be read back with `sigrs import`, e.g. to move identities to another machine")
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(export_format())
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .next_line_help(true)
+                        .help("Write to this file instead of stdout. The format is taken from its extension unless `--format` is passed")
+                        .value_parser(value_parser!(OsString))
+                )
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Add identities from a file created by `sigrs export`")
+                .long_about("Add identities from a file created by `sigrs export`.\nBy default nothing is written if any identity in the file already exists in LAS, and the conflicting keys are listed.\nUse `--merge` or `--replace` to decide what happens to them")
+                .arg_required_else_help(true)
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(export_format())
+                .arg(no_validate())
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .help("JSON or TOML file to import")
+                        .value_parser(value_parser!(OsString))
+                )
+                .arg(
+                    Arg::new("merge")
+                        .long("merge")
+                        .next_line_help(true)
+                        .help("Add the usernames, emails and missing metadata of conflicting identities to the existing ones. Existing defaults are kept")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("replace")
+                        .long("replace")
+                        .next_line_help(true)
+                        .help("Make LAS contain exactly the identities in the file. Identities not in the file are removed")
+                        .long_help("Make LAS contain exactly the identities in the file. Identities not in the file are removed.\nThe previous state can be restored with `sigrs undo`")
+                        .action(ArgAction::SetTrue)
+                )
+                .group(
+                    ArgGroup::new("import-mode")
+                        .args(["merge", "replace"])
+                        .multiple(false)
+                        .required(false)
+                )
+        )
+        .subcommand(
+            Command::new("import-git")
+                .about("Add an identity for every user.name/user.email pair in your git config files")
+                .long_about("Add an identity for every user.name/user.email pair in your git config files.\nReads `~/.config/git/config`, `~/.gitconfig` and the `.git/config` of the repo containing the working directory, following `[include]` and `[includeIf]` paths. Git itself is not needed.\nEvery `[includeIf]` fragment is treated as its own identity, whatever its condition. Pairs already stored on an identity are skipped")
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(
+                    Arg::new("repo")
+                        .long("repo")
+                        .next_line_help(true)
+                        .help("Read the config of the git repository at this directory instead of the one containing the working directory.\nTo include multiple repositories, prefix each with `--repo`")
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(OsString))
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .short('f')
+                        .next_line_help(true)
+                        .help("Also read this git config file. To include multiple files, prefix each with `--file`")
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(OsString))
+                )
+                .arg(
+                    Arg::new("no-global")
+                        .long("no-global")
+                        .next_line_help(true)
+                        .help("Don't read `~/.gitconfig` or `~/.config/git/config`")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .next_line_help(true)
+                        .help("List the identities that would be added without writing them")
+                        .action(ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("harvest")
+                .about("Create or update identities from the authors already listed in config files")
+                .long_about("Create or update identities from the authors already listed in config files (Cargo.toml, package.json, composer.json, pyproject.toml).\nConfig files are found the same way as `sigrs sign`, skipping node_modules, vendor, target and .git.\nAuthors sharing an email become one identity with all their names. An identity already holding one of those emails gets the new values added instead.\nAuthors without both a name and an email are skipped")
+                .arg_required_else_help(true)
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(
+                    Arg::new("dir")
+                        .long("dir")
+                        .short('d')
+                        .next_line_help(true)
+                        .help("Directory to search recursively. To search multiple directories, prefix each with `--dir`")
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(OsString))
+                )
+                .arg(
+                    Arg::new("dir-with-depth")
+                        .long("dir-with-depth")
+                        .next_line_help(true)
+                        .help("Directory to search and a maximum depth of sub-directories, same as `sigrs sign`.\nEx: `--dir-with-depth 2::some_dir`")
+                        .action(ArgAction::Append)
+                        .value_name("DEPTH>::<DIR PATH")
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .short('f')
+                        .next_line_help(true)
+                        .help("Config file to read. To read multiple files, prefix each with `-f`")
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(OsString))
+                )
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .next_line_help(true)
+                        .help("Delimiter used in `--dir-with-depth`, `--only-include` and `--only-exclude`. Defaults to `::`")
+                        .default_value("::")
+                )
+                .arg(
+                    Arg::new("only-include")
+                        .long("only-include")
+                        .next_line_help(true)
+                        .help("Only read these types of config files.\nEx: `--only-include Cargo.toml::package.json`")
+                )
+                .arg(
+                    Arg::new("only-exclude")
+                        .long("only-exclude")
+                        .next_line_help(true)
+                        .help("Read every type of config file except these.\nEx: `--only-exclude composer.json`")
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .next_line_help(true)
+                        .help("List the identities that would be added or merged without writing them")
+                        .action(ArgAction::SetTrue)
+                )
+                .group(
+                    ArgGroup::new("harvest-paths")
+                        .args(["dir", "dir-with-depth", "file"])
+                        .multiple(true)
+                        .required(true)
+                )
+                .group(
+                    ArgGroup::new("only-include-exclude")
+                        .args(["only-include", "only-exclude"])
+                        .multiple(false)
+                        .required(false)
+                )
+        )
+        .subcommand(
+            Command::new("import-mailmap")
+                .about("Add an identity for every person in a git .mailmap")
+                .long_about("Add an identity for every person in a git .mailmap.\nThe canonical name and email of each entry become the identity's defaults, and every alias name/email is added after them.\nA person whose canonical email is already stored on an identity is merged into it, keeping that identity's defaults. Entries without any name are skipped")
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(
+                    Arg::new("file")
+                        .help("The .mailmap to read [default: ./.mailmap]")
+                        .value_parser(value_parser!(OsString))
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .next_line_help(true)
+                        .help("List the identities that would be added or merged without writing them")
+                        .action(ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("export-mailmap")
+                .about("Write a git .mailmap mapping every username/email of an identity to its defaults")
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .next_line_help(true)
+                        .help("Write to this file instead of stdout, e.g. `--output .mailmap`")
+                        .value_parser(value_parser!(OsString))
+                )
+        )
         // ===================================================== NEW LIST
-        // TODO: Add option for JSON output quality of life
         .subcommand(
             Command::new("list-all")
                 .about("List all identities stored in LAS")
                 .arg(config_path())
+                .arg(lock_timeout())
+                .arg(list_format())
                 .arg(
                     Arg::new("verbose")
                         .long("verbose")
@@ -371,6 +784,8 @@ pub fn sigrs_command() -> Command {
                 .about("List details about a specific identity in LAS")
                 .arg_required_else_help(true)
                 .arg(config_path())
+                .arg(lock_timeout())
+                .arg(list_format())
                 .arg(
                     Arg::new("verbose")
                         .long("verbose")
@@ -386,9 +801,11 @@ pub fn sigrs_command() -> Command {
         )
         .subcommand(
             Command::new("list-find")
-                .about("List details about all identities in LAS containing 1 or more of the provided arguments")
+                .about("List details about all identities in LAS matching 1 or more of the provided arguments")
                 .arg_required_else_help(true)
                 .arg(config_path())
+                .arg(lock_timeout())
+                .arg(list_format())
                 .arg(
                     Arg::new("verbose")
                         .long("verbose")
@@ -409,19 +826,69 @@ pub fn sigrs_command() -> Command {
                         .long_help("Emails to include in search. To include multiple emails, prefix each email with `-E`.\nEx: `sigrs list-find -E \"x@y.com\" -E \"y@x.com\"` ")
                         .action(ArgAction::Append)
                 )
+                .arg(
+                    Arg::new("keys").next_line_help(true)
+                        .short('K')
+                        .help("Identity keys to include in search")
+                        .long_help("Identity keys to include in search. To include multiple keys, prefix each key with `-K`.\nEx: `sigrs list-find -K \"work\" -K \"personal\"` ")
+                        .action(ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("contains").next_line_help(true)
+                        .long("contains")
+                        .help("Match values containing a term instead of equal to it\nEx: `sigrs list-find -E gmail.com --contains`")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("regex").next_line_help(true)
+                        .long("regex")
+                        .help("Treat every term as a regex, matching anywhere in the value unless anchored with `^`/`$`\nEx: `sigrs list-find -E \"@(gmail|proton)\\.com$\" --regex`")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("ignore-case").next_line_help(true)
+                        .long("ignore-case")
+                        .short('i')
+                        .help("Match terms case-insensitively, with any of the match modes")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("all").next_line_help(true)
+                        .long("all")
+                        .help("Only list identities matching every term, instead of any of them\nEx: `sigrs list-find -K work -E x.com --contains --all`")
+                        .action(ArgAction::SetTrue)
+                )
                 .group(
-                    // This group makes at least 1 of usernames or emails required
+                    // This group makes at least 1 of keys, usernames or emails required
                     ArgGroup::new("identifiers")
-                        .args(&["usernames", "emails"])
+                        .args(["keys", "usernames", "emails"])
                         .multiple(true)
                         .required(true)
                 )
+                .arg(
+                    Arg::new("sort").next_line_help(true)
+                        .long("sort")
+                        .help("List identities matching the most values first, or in key order [default: matches]")
+                        .value_parser(["matches", "key"])
+                )
+                .arg(
+                    Arg::new("limit").next_line_help(true)
+                        .long("limit")
+                        .help("List at most this many identities, after sorting")
+                        .value_parser(value_parser!(usize))
+                )
+                .group(
+                    ArgGroup::new("match-mode")
+                        .args(["contains", "regex"])
+                        .multiple(false)
+                )
         )
         .subcommand(
             Command::new("delete")
                 .about("Delete an entire Identity from local storage")
                 .arg_required_else_help(true)
                 .arg(config_path())
+                .arg(lock_timeout())
                 .arg(
                     Arg::new("identity")
                         .required(true)
@@ -430,18 +897,271 @@ pub fn sigrs_command() -> Command {
                         .value_parser(NonEmptyStringValueParser::new()),
                 )
         )
+        .subcommand(
+            Command::new("profile")
+                .about("Manage named config.sigrs files (profiles) && switch between them")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("List profiles, `*` marks the active one")
+                )
+                .subcommand(
+                    Command::new("add")
+                        .about("Register a config.sigrs as a profile, creating it empty if it doesn't exist")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Letters, digits, `-` && `_`")
+                                .value_parser(NonEmptyStringValueParser::new())
+                        )
+                        .arg(
+                            Arg::new("path")
+                                .help("config.sigrs for this profile [default: <name>.sigrs next to the saved config.sigrs]")
+                                .value_parser(value_parser!(OsString))
+                        )
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Unregister a profile. Its config.sigrs is kept")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .value_parser(NonEmptyStringValueParser::new())
+                        )
+                )
+                .subcommand(
+                    Command::new("add-shared")
+                        .about("Add a read-only team store, searched after the active profile by list && sign commands")
+                        .arg(
+                            Arg::new("path")
+                                .required(true)
+                                .help("A config.sigrs, or a directory of *.sigrs files")
+                                .value_parser(value_parser!(OsString))
+                        )
+                )
+                .subcommand(
+                    Command::new("remove-shared")
+                        .about("Stop searching a shared store. Nothing is deleted")
+                        .arg(
+                            Arg::new("path")
+                                .required(true)
+                                .value_parser(value_parser!(OsString))
+                        )
+                )
+                .subcommand(
+                    Command::new("use")
+                        .about("Make a profile active for every LAS && sign command. `default` is the config path saved in sigrs")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .value_parser(NonEmptyStringValueParser::new())
+                        )
+                )
+        )
+        .subcommand(
+            Command::new("clone")
+                .about("Copy an identity in LAS under a new key")
+                .arg_required_else_help(true)
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(
+                    Arg::new("identity")
+                        .required(true)
+                        .long("identity")
+                        .help("Identity to copy")
+                        .value_parser(NonEmptyStringValueParser::new()),
+                )
+                .arg(
+                    Arg::new("as")
+                        .required(true)
+                        .long("as")
+                        .help("Key of the copy, must not exist yet\nEx: `sigrs clone --identity \"work\" --as \"work-laptop\"`")
+                        .value_parser(NonEmptyStringValueParser::new()),
+                )
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Merge identities into another, then delete them")
+                .arg_required_else_help(true)
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(
+                    Arg::new("into")
+                        .required(true)
+                        .long("into")
+                        .help("Identity to keep. Its defaults && metadata are kept, the other usernames/emails are added after its own")
+                        .value_parser(NonEmptyStringValueParser::new()),
+                )
+                .arg(
+                    Arg::new("from").next_line_help(true)
+                        .required(true)
+                        .long("from")
+                        .help("Identity to merge && delete")
+                        .long_help("Identity to merge into --into && then delete. To merge multiple identities, prefix each with `--from`, they are merged in order.\nEx: `sigrs merge --into \"bob\" --from \"bob-2\" --from \"robert\"` ")
+                        .value_parser(NonEmptyStringValueParser::new())
+                        .action(ArgAction::Append)
+                )
+        )
+        // ===================================================== IDENTITY RULES
+        .subcommand(
+            Command::new("rule")
+                .about("Pick the identity `sign` uses from the working dir or git remotes when no --identity is passed")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a rule to an identity. The first matching rule wins, in config.sigrs order")
+                        .arg(config_path())
+                        .arg(lock_timeout())
+                        .arg(rule_identity())
+                        .args(rule_patterns())
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove a rule from an identity")
+                        .arg(config_path())
+                        .arg(lock_timeout())
+                        .arg(rule_identity())
+                        .args(rule_patterns())
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List every rule, in the order they are checked")
+                        .arg(config_path())
+                        .arg(lock_timeout())
+                )
+        )
+        .subcommand(
+            Command::new("whoami")
+                .about("Show the identity `sign` would use here without --identity, and why")
+                .arg(config_path())
+                .arg(lock_timeout())
+                .arg(
+                    Arg::new("no-project-file")
+                        .long("no-project-file")
+                        .help("Ignore any .sigrs.toml in the working dir or above it")
+                        .action(ArgAction::SetTrue)
+                )
+        )
 }
 
+/// `--identity` of `sigrs rule add/remove`
+fn rule_identity() -> Arg {
+    Arg::new("identity")
+        .long("identity")
+        .required(true)
+        .value_parser(NonEmptyStringValueParser::new())
+}
+
+/// `--path` or `--remote`, shared by `sigrs rule add/remove`
+fn rule_patterns() -> [Arg; 2] {
+    [
+        Arg::new("path")
+            .long("path")
+            .next_line_help(true)
+            .help("Use the identity in this dir && every dir below it. Absolute, or starting with `~/`\nEx: `sigrs rule add --identity work --path ~/work/`")
+            .required_unless_present("remote")
+            .conflicts_with("remote")
+            .value_parser(NonEmptyStringValueParser::new()),
+        Arg::new("remote")
+            .long("remote")
+            .next_line_help(true)
+            .help("Use the identity in repos with a git remote matching this pattern. `*` matches anything, a host or path also matches everything below it\nEx: `sigrs rule add --identity work --remote git.corp.com`")
+            .value_parser(NonEmptyStringValueParser::new()),
+    ]
+}
+
+
+/// Optional identity metadata, one arg per `METADATA_FIELDS` entry
+/// Shared by add-new, update && import
+fn metadata_args() -> [Arg; 4] {
+    [
+        Arg::new("url")
+            .long("url")
+            .next_line_help(true)
+            .help("Homepage/profile URL. Signed as `url` in package.json and `homepage` in composer.json")
+            .value_parser(NonEmptyStringValueParser::new()),
+        Arg::new("role")
+            .long("role")
+            .next_line_help(true)
+            .help("Role in the project, e.g. \"Developer\". Signed as `role` in composer.json")
+            .value_parser(NonEmptyStringValueParser::new()),
+        Arg::new("organization")
+            .long("organization")
+            .alias("org")
+            .next_line_help(true)
+            .help("Organization this identity belongs to. Stored and listed only")
+            .value_parser(NonEmptyStringValueParser::new()),
+        Arg::new("fingerprint")
+            .long("fingerprint")
+            .next_line_help(true)
+            .help("Signing key fingerprint (GPG/SSH). Stored and listed only")
+            .value_parser(NonEmptyStringValueParser::new()),
+    ]
+}
+
+/// Shared by undo && redo
+fn history_force() -> Arg {
+    Arg::new("force")
+        .long("force")
+        .next_line_help(true)
+        .help("Undo/redo even if config.sigrs was changed outside of add-new/update/delete since, discarding those changes")
+        .action(ArgAction::SetTrue)
+}
+
+/// `--format` for list-all, list-by-id && list-find, see `las/output.rs`
+fn list_format() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .next_line_help(true)
+        .help("Print as json, yaml, csv, tsv, or a template like '{id}\\t{default_email}'")
+        .long_help("Print as json, yaml, csv, tsv, or a template printed once per identity.\njson && yaml are always a list, csv && tsv start with a header row, multiple usernames/emails are joined with `;`.\nTemplate placeholders: {id} {default_username} {default_email} {usernames} {emails} {url} {role} {organization} {fingerprint}, {store} (the config.sigrs an identity came from, empty without shared stores) && {matches} for list-find\n`\\t` `\\n` are unescaped, `{{` `}}` are literal braces.\nEx: `sigrs list-all --format '{id}\\t{default_email}'`")
+        .value_parser(OutputFormat::parse)
+        .conflicts_with("verbose")
+}
+
+/// Shared by add-new && update
+fn no_validate() -> Arg {
+    Arg::new("no-validate")
+        .long("no-validate")
+        .next_line_help(true)
+        .help("Store usernames/emails exactly as passed, without trimming, NFC normalization or email syntax checks")
+        .action(ArgAction::SetTrue)
+}
+
+/// `--format` for export/import
+fn export_format() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .next_line_help(true)
+        .help("json or toml. Defaults to the file extension, or json when writing to stdout")
+        .value_parser(["json", "toml"])
+}
+
+/// Shared by every command that reads or writes config.sigrs
+fn lock_timeout() -> Arg {
+    Arg::new("lock-timeout")
+        .long("lock-timeout")
+        .value_name("SECONDS")
+        .next_line_help(true)
+        .help(format!("Seconds to wait for another sigrs process to release config.sigrs [default: {}]", DEFAULT_LOCK_TIMEOUT))
+        .long_help(format!(
+            "Seconds to wait for another sigrs process to release config.sigrs [default: {}]\nCommands that only read config.sigrs (list-*, sign) can run in parallel, commands that change it (add-new, update, delete...) wait for everything else to finish.\n`0` fails immediately if config.sigrs is in use",
+            DEFAULT_LOCK_TIMEOUT
+        ))
+        .value_parser(value_parser!(u64))
+}
 
 fn config_path() -> Arg {
     let mut help = String::new();
     help.push_str("Path to config.sigrs to look up identity from\n");
     help.push_str("If provided, sigrs will read the file at this path to get the identity.\n");
     help.push_str("-- Undefined behavior if the file is not formatted correctly from manual editing (use sigrs commands for editing to prevent incorrect formatting).\n");
-    help.push_str("If not provided, sigrs will use the saved path, which is located at the end of the sigrs_function binary.\n");
+    help.push_str("If not provided, sigrs will use `--profile`, then the SIGRS_CONFIG_PATH or SIGRS_PROFILE environment variable, then the active profile, then the saved path, which is located at the end of the sigrs_function binary.\n");
     help.push_str("By default, the saved path is the path to the default config.sigrs file generated at build/installation time.\n");
     help.push_str("-- Run `sigrs --config-path --help` to see these defaults based on your OS\n");
-    help.push_str("You can check what the current saved path is via `sigrs get-config-path`\n");
+    help.push_str("You can check which path will be used via `sigrs get-config-path`\n");
     help.push_str("You can update the saved path via:\n");
     help.push_str("- `sigrs set-config-path --path=/some/path/config.sigrs`\n");
     help.push_str("--- Creates an empty template config.sigrs at `--path`, then updates the saved config.sigrs path\n");
@@ -462,11 +1182,11 @@ Your LAS file will be stored in a `config.sigrs` file, typically located ___.
 
 Because `config.sigrs` uses a custom schema, it is highly recommended that you DO NOT manually edit your `config.sigrs`. If you want to edit/change something, use `sigrs update` etc.
 
-If your `config.sigrs` file becomes 'corrupted' (incorrect formatting) through manual changes, the sigrs tool will not function correctly. You can 'reset' to a blank `config.sigrs` via running `sigrs reset-config`.
+If your `config.sigrs` file becomes 'corrupted' (incorrect formatting) through manual changes, the sigrs tool will not function correctly. Run `sigrs doctor` to find the problems, and `sigrs doctor --fix` to repair them. You can 'reset' to a blank `config.sigrs` via running `sigrs reset-config`.
 ";
 
 const GENERATE_CONFIG_ABOUT: &str = r"
-You can run `sigrs --generate-config` to generate a `config.sigrs` to be stored at a location of your choosing.
+You can run `sigrs generate-config --path=/some/path/config.sigrs` to generate a `config.sigrs` to be stored at a location of your choosing.
 
 You do not need to run this command, as a `config.sigrs` will be generated the first time you use any other command if one does not exist.
 
@@ -484,6 +1204,15 @@ Windows
 Value: `{FOLDERID_RoamingAppData}`
 Example: `C:\Users\Alice\AppData\Roaming`
 
-If you choose to put your `config.sigrs` in a different location, you will NEED to specify that location every time you run a `config.sigrs` command.
+If you choose to put your `config.sigrs` in a different location, you will NEED to specify that location (`--config-path`) every time you run a `config.sigrs` command, unless you pass `--register`, which saves it as the default location, or set the SIGRS_CONFIG_PATH environment variable (useful in CI && containers).
 ";
 
+fn history_about() -> String {
+    format!("
+Every `add-new`, `update` and `delete` saves a copy of `config.sigrs` before and after the change in a `config.sigrs.history` directory next to it. The last {} changes are kept.
+
+`sigrs undo` restores `config.sigrs` to how it was before the last change, `sigrs redo` re-applies it. Making a new change after undoing discards anything that could have been redone.
+
+`sigrs history` lists the recorded changes, newest first. `*` marks the current state.
+", HISTORY_LIMIT)
+}
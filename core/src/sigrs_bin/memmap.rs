@@ -1,9 +1,24 @@
+use std::borrow::Cow;
 use std::fs::File;
+use std::ops::Deref;
+use std::path::PathBuf;
 use clap::ArgMatches;
 use anyhow::Result;
+use thiserror::Error;
 
 use memmap2::Mmap;
 use crate::identity::*;
+use crate::las::format::{find_value_end, unescape_value};
+use crate::las::lock::{extract_lock_timeout, lock_las, LasLock, LockKind};
+use crate::las::profile::shared_store_files;
+use crate::las::rules::RuleKind;
+use crate::las::version::{
+    check_version,
+    ensure_current_version,
+    migrated_in_memory,
+    needs_migration,
+    read_version,
+};
 use crate::utils::extract_config_path;
 
 
@@ -14,127 +29,339 @@ pub const POUND_BITS: u8 = 35u8;
 pub const K_BITS: u8 = 75u8;
 pub const U_BITS: u8 = 85u8;
 pub const E_BITS: u8 = 69u8;
+pub const M_BITS: u8 = 77u8;
+pub const R_BITS: u8 = 82u8;
+
+/// Memory map of config.sigrs, holds a shared lock for as long as it lives
+/// so no sigrs writer can change the file while it is mapped.
+/// Shared stores are never written by sigrs && may sit in a read-only dir, so
+/// they are mapped without a lock
+pub struct LasMmap {
+    mmap: Mmap,
+    _lock: Option<LasLock>,
+}
+
+impl Deref for LasMmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.mmap
+    }
+}
 
 /// Get Mmap from `--config-path` or dirs default
-pub unsafe fn get_memmap(args: &ArgMatches) -> Result<Mmap, String> {
-    let config_path = extract_config_path(args)?;
+pub unsafe fn get_memmap(args: &ArgMatches) -> Result<LasMmap, String> {
+    let config_path = PathBuf::from(extract_config_path(args)?);
+    let timeout = extract_lock_timeout(args);
+
+    // upgrade older LAS versions before mapping, never write while mapped
+    // exclusive lock is only taken when there is something to migrate
+    let mut lock = lock_las(&config_path, LockKind::Shared, timeout)?;
+    if needs_migration(&config_path)? {
+        drop(lock);
+        {
+            let _write_lock = lock_las(&config_path, LockKind::Exclusive, timeout)?;
+            ensure_current_version(&config_path)?;
+        }
+        lock = lock_las(&config_path, LockKind::Shared, timeout)?;
+    }
 
     {
         // scoped to immediately drop File handle after creating memmap
-        let handle = File::open(config_path)
+        let handle = File::open(&config_path)
             .map_err(|e| e.to_string())?;
 
-        unsafe {
+        let mmap = unsafe {
             Mmap::map(&handle)
-                .map_err(|e| String::from("Problem getting memory map"))
+                .map_err(|e| String::from("Problem getting memory map"))?
+        };
+        Ok(LasMmap { mmap, _lock: Some(lock) })
+    }
+}
+
+/// A read-only team store && its memmap, see `las/layers.rs`
+/// Derefs to the store's content, migrated in memory if it is on an older LAS version
+pub struct SharedMmap {
+    pub source: PathBuf,
+    mmap: LasMmap,
+    /// Only set for older stores, which are never migrated on disk
+    migrated: Option<Vec<u8>>,
+}
+
+impl Deref for SharedMmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.migrated.as_deref().unwrap_or(&self.mmap)
+    }
+}
+
+/// Maps every shared store in profiles.toml, skipping (with a warning) any
+/// that can't be opened or are on a newer LAS version, see `map_shared`
+pub unsafe fn get_shared_memmaps() -> Vec<SharedMmap> {
+    let mut maps = Vec::new();
+    for source in shared_store_files() {
+        match unsafe { map_shared(source.clone()) } {
+            Ok(map) => maps.push(map),
+            Err(e) => eprintln!("Skipped shared store {}: {}", source.display(), e),
         }
     }
+    maps
+}
+
+/// Maps the shared store at `source`. A store written by an older sigrs is
+/// migrated in memory, the file is left as it is
+// Unsafe for the same reason as `get_memmap`, the file could change while mapped
+pub unsafe fn map_shared(source: PathBuf) -> Result<SharedMmap, String> {
+    let handle = File::open(&source).map_err(|e| e.to_string())?;
+    let mmap = unsafe {
+        Mmap::map(&handle).map_err(|e| e.to_string())?
+    };
+    let content = std::str::from_utf8(&mmap).map_err(|e| e.to_string())?;
+    let migrated = match migrated_in_memory(content)? {
+        Cow::Owned(migrated) => Some(migrated.into_bytes()),
+        Cow::Borrowed(_) => None,
+    };
+    Ok(SharedMmap { source, mmap: LasMmap { mmap, _lock: None }, migrated })
+}
+
+/// Why a config.sigrs could not be parsed
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LasParseErrorKind {
+    #[error("{0}")]
+    Version(String),
+    #[error("unknown line type '{0}', expected a comment `#` or one of `K:` `U:` `E:` `M:` `R:`")]
+    UnknownLineType(char),
+    #[error("missing `:` after line type '{0}'")]
+    MissingColon(char),
+    #[error("expected a `{expected}:` line, found a `{found}:` line")]
+    UnexpectedLine { expected: char, found: char },
+    #[error("file ended before the identity's `{0}:` line")]
+    UnexpectedEof(char),
+    #[error("expected opening double quote, found '{0}'")]
+    ExpectedQuote(char),
+    #[error("value is missing its closing double quote")]
+    UnterminatedValue,
+    #[error("key line must contain exactly 1 value, found {0}")]
+    KeyValueCount(usize),
+    #[error("identity \"{0}\" is defined more than once")]
+    DuplicateKey(String),
+    #[error("metadata line must contain field/value pairs, found {0} values")]
+    MetadataPairs(usize),
+    #[error("rule line must contain kind/pattern pairs, found {0} values")]
+    RulePairs(usize),
+    #[error("unknown rule kind \"{0}\", expected `path` or `remote`")]
+    UnknownRuleKind(String),
+}
+
+/// Error parsing a config.sigrs, `line` && `column` are 1-based
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("config.sigrs line {line}, column {column}: {reason}")]
+pub struct LasParseError {
+    pub line: usize,
+    pub column: usize,
+    pub reason: LasParseErrorKind,
+}
+
+impl LasParseError {
+    pub fn new(line: usize, column: usize, reason: LasParseErrorKind) -> Self {
+        Self { line, column, reason }
+    }
+}
+
+/// Parses the quoted values of a K:/U:/E:/M: line, starting after the `X:` prefix
+pub fn parse_values<'a>(
+    line_slice: &'a [u8],
+    line_no: usize
+) -> Result<Vec<Cow<'a, [u8]>>, LasParseError> {
+    let mut values = Vec::new();
+    let mut cursor = 2;
+
+    while cursor < line_slice.len() {
+        // values are written back to back, next byte must open a value
+        if line_slice[cursor] != DOUBLE_QUOTE_BITS {
+            return Err(LasParseError::new(
+                line_no,
+                cursor + 1,
+                LasParseErrorKind::ExpectedQuote(char::from(line_slice[cursor]))
+            ));
+        }
+
+        // skip opening double quote
+        let start = cursor + 1;
+
+        // move to closing double quote, skipping escaped quotes
+        let Some(end) = find_value_end(line_slice, start) else {
+            return Err(LasParseError::new(line_no, cursor + 1, LasParseErrorKind::UnterminatedValue));
+        };
+
+        values.push(unescape_value(&line_slice[start..end]));
+        // skip closing double quote
+        cursor = end + 1;
+    }
+    Ok(values)
 }
 
-pub fn process_las<'a>(memmap: &'a [u8]) -> LocalAuthorStoragePerf<'a> {
+/// Parses a config.sigrs memmap into LAS
+/// Errors if the file is not on the current LAS version (see `las/version.rs`),
+/// or if it is not formatted correctly
+pub fn process_las<'a>(memmap: &'a [u8]) -> Result<LocalAuthorStoragePerf<'a>, LasParseError> {
+
+    read_version(memmap)
+        .and_then(check_version)
+        .map_err(|e| LasParseError::new(1, 1, LasParseErrorKind::Version(e)))?;
 
-    let mut key_buf = Vec::<&[u8]>::new();
-    let mut las = LocalAuthorStoragePerf::new(&memmap);
+    // (line number, key) of the identity currently being parsed
+    let mut key_buf: Option<(usize, Cow<'a, [u8]>)> = None;
+    let mut las = LocalAuthorStoragePerf::new(memmap);
     let mut identity_buf = IdentityPerf::new();
+    // identities are always K: -> U: -> E: -> optional M: -> optional R:
+    let mut expecting = K_BITS;
+    // an `M:` line may only directly follow an `E:` line
+    let mut metadata_allowed = false;
+    // an `R:` line may only directly follow an `E:` or `M:` line
+    let mut rules_allowed = false;
+    let mut line_no = 0;
     let mut s = 0;
 
-    loop {
-        // find next newline in memmap_las
-        if let Some(newline_pos) = memmap[s..]
+    // stores the identity in identity_buf once all its lines have been read
+    fn finish_identity<'a>(
+        las: &mut LocalAuthorStoragePerf<'a>,
+        key_buf: &mut Option<(usize, Cow<'a, [u8]>)>,
+        identity_buf: &mut IdentityPerf<'a>
+    ) -> Result<(), LasParseError> {
+        if let Some((key_line_no, key_slice)) = key_buf.take() {
+            if las.add_identity(key_slice.clone(), identity_buf).is_err() {
+                return Err(LasParseError::new(
+                    key_line_no,
+                    3,
+                    LasParseErrorKind::DuplicateKey(String::from_utf8_lossy(&key_slice).into_owned())
+                ));
+            }
+        }
+        // clear identity buf
+        *identity_buf = IdentityPerf::new();
+        Ok(())
+    }
+
+    while s < memmap.len() {
+        line_no += 1;
+
+        // find next newline in memmap_las, last line may not have one
+        let end = memmap[s..]
             .iter()
-            .position(|&b| b == NEW_LINE_BITS) {
-
-            // get line slice of memmap
-            let line_slice = &memmap[s.. s + newline_pos + 1];
-
-            // update starting position for next line
-            s += newline_pos + 1;
-
-            // match on first byte to determine line type
-            match &line_slice[0..1] {
-                &[POUND_BITS] => {
-                    // comment line ignore
-                    continue;
-                },
-                &[K_BITS] => {
-                    // handle key line
-                    let mut cursor = 3;
-                    while line_slice[cursor] != DOUBLE_QUOTE_BITS {
-                        cursor += 1;
-                    }
-                    key_buf.push(&line_slice[3..cursor]);
-                    // write keybuf
-                    // key_buf.write(&line_slice[3..cursor]).unwrap();
-                    continue;
-                },
-                &[U_BITS] => {
-                    // parse usernames directly from line_slice
-                    let mut cursor = 2;
-                    let line_len = line_slice.len();
-                    while cursor < line_len && line_slice[cursor] != NEW_LINE_BITS {
-                        // move to opening double quote
-                        while line_slice[cursor] != DOUBLE_QUOTE_BITS {
-                            cursor += 1;
-                        }
-
-                        // skip opening double quote
-                        cursor += 1;
-                        let start = cursor;
-
-                        // move to closing double quote
-                        while line_slice[cursor] != DOUBLE_QUOTE_BITS {
-                            cursor += 1;
-                        }
-
-                        // slice containing username
-                        identity_buf.add_username(&line_slice[start..cursor]);
-                        // skip closing double quote
-                        cursor += 1;
-                    }
-                    // identity_buf has all usernames
-                    continue;
-                },
-                &[E_BITS] => {
-                    // handle emails line
-                    let mut cursor = 2;
-                    let line_len = line_slice.len();
-                    while cursor < line_len && line_slice[cursor] != NEW_LINE_BITS {
-                        // move to opening "
-                        while line_slice[cursor] != DOUBLE_QUOTE_BITS {
-                            cursor += 1;
-                        }
-                        // skip opening
-                        cursor += 1;
-                        let start = cursor;
-                        // move to closing "
-                        while line_slice[cursor] != DOUBLE_QUOTE_BITS {
-                            cursor += 1;
-                        }
-
-                        // add email slice
-                        identity_buf.add_email(&line_slice[start..cursor]);
-                        // skip closing double quote
-                        cursor += 1;
-                    }
+            .position(|&b| b == NEW_LINE_BITS)
+            .map_or(memmap.len(), |newline_pos| s + newline_pos);
 
-                    // after emails are done, the identity is finished, store in LAS
-                    if let Some(key_slice) = key_buf.pop() {
-                        las.add_identity(key_slice, &identity_buf).unwrap();
-                    }
+        // get line slice of memmap, without newline
+        let mut line_slice = &memmap[s..end];
+
+        // update starting position for next line
+        s = end + 1;
+
+        // tolerate CRLF from hand edits, a real CR in a value is always escaped
+        if let Some(stripped) = line_slice.strip_suffix(b"\r") {
+            line_slice = stripped;
+        }
+
+        // match on first byte to determine line type
+        let line_type = match line_slice.first() {
+            // blank line, ignore
+            None => continue,
+            // comment line ignore
+            Some(&POUND_BITS) => continue,
+            Some(&b) if b == K_BITS || b == U_BITS || b == E_BITS || b == M_BITS || b == R_BITS => b,
+            Some(&b) => {
+                return Err(LasParseError::new(line_no, 1, LasParseErrorKind::UnknownLineType(char::from(b))));
+            }
+        };
+
+        if line_slice.get(1) != Some(&b':') {
+            return Err(LasParseError::new(line_no, 2, LasParseErrorKind::MissingColon(char::from(line_type))));
+        }
 
-                    // clear identity buf
-                    identity_buf = IdentityPerf::new();
+        let is_metadata = line_type == M_BITS && metadata_allowed;
+        let is_rules = line_type == R_BITS && rules_allowed;
+        if line_type != expecting && !is_metadata && !is_rules {
+            return Err(LasParseError::new(
+                line_no,
+                1,
+                LasParseErrorKind::UnexpectedLine {
+                    expected: char::from(expecting),
+                    found: char::from(line_type)
+                }
+            ));
+        }
+        metadata_allowed = false;
+        rules_allowed = false;
+
+        let values = parse_values(line_slice, line_no)?;
 
-                },
-                _ => {
-                    unimplemented!();
+        match line_type {
+            K_BITS => {
+                // previous identity had no `M:` line
+                finish_identity(&mut las, &mut key_buf, &mut identity_buf)?;
+
+                // handle key line
+                if values.len() != 1 {
+                    return Err(LasParseError::new(line_no, 3, LasParseErrorKind::KeyValueCount(values.len())));
+                }
+                key_buf = values.into_iter().next().map(|key| (line_no, key));
+                expecting = U_BITS;
+            },
+            U_BITS => {
+                // identity_buf gets all usernames
+                for uname in values {
+                    identity_buf.add_username(uname);
+                }
+                expecting = E_BITS;
+            },
+            E_BITS => {
+                // handle emails line
+                for email in values {
+                    identity_buf.add_email(email);
+                }
+
+                // identity is stored once the next line shows whether it has `M:`/`R:` lines
+                expecting = K_BITS;
+                metadata_allowed = true;
+                rules_allowed = true;
+            },
+            M_BITS => {
+                // handle metadata line, field && value alternate
+                if !values.len().is_multiple_of(2) {
+                    return Err(LasParseError::new(line_no, 3, LasParseErrorKind::MetadataPairs(values.len())));
+                }
+                let mut values = values.into_iter();
+                while let (Some(field), Some(value)) = (values.next(), values.next()) {
+                    identity_buf.add_metadata(field, value);
                 }
+                rules_allowed = true;
+            },
+            _ => {
+                // handle rules line, kind && pattern alternate
+                if !values.len().is_multiple_of(2) {
+                    return Err(LasParseError::new(line_no, 3, LasParseErrorKind::RulePairs(values.len())));
+                }
+                let mut values = values.into_iter();
+                while let (Some(kind), Some(pattern)) = (values.next(), values.next()) {
+                    if RuleKind::parse(&String::from_utf8_lossy(&kind)).is_none() {
+                        let kind = String::from_utf8_lossy(&kind).into_owned();
+                        return Err(LasParseError::new(line_no, 3, LasParseErrorKind::UnknownRuleKind(kind)));
+                    }
+                    identity_buf.add_rule(kind, pattern);
+                }
+
+                // after rules the identity is finished, store in LAS
+                finish_identity(&mut las, &mut key_buf, &mut identity_buf)?;
             }
-        } else {
-            break;
         }
-    };
+    }
 
-    las
-}
+    if expecting != K_BITS {
+        return Err(LasParseError::new(line_no + 1, 1, LasParseErrorKind::UnexpectedEof(char::from(expecting))));
+    }
+    finish_identity(&mut las, &mut key_buf, &mut identity_buf)?;
 
+    Ok(las)
+}
@@ -3,12 +3,16 @@ use std::{
     env::current_dir,
     ffi::OsString,
     fs::read_dir,
-    ops::Deref,
     path::{Path, PathBuf},
 };
 use clap::ArgMatches;
-use crate::memmap::{get_memmap, process_las};
+use crate::identity::AuthorMeta;
+use crate::las::layers::LayeredLas;
+use crate::las::rules::{first_match, RuleContext};
+use crate::memmap::{get_memmap, get_shared_memmaps};
+use crate::utils::{env_value, extract_config_path, ENV_EMAIL, ENV_IDENTITY, ENV_USERNAME};
 use super::{
+    project::{find_project_file, ProjectConfig, PROJECT_FILE_NAME},
     signpath::SignPath,
     filters:: {
         ConfigTypeFilter,
@@ -19,14 +23,28 @@ use super::{
     }, 
 };
 
+/// Dependency/build output dirs, their configs belong to someone else
+const SKIPPED_DIRS: [&str; 4] = [".git", "node_modules", "vendor", "target"];
+
+/// Which files `find_config_paths` picks up from the directories it walks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Discovery {
+    /// Every file, ones sigrs can't sign are reported as errors (`sigrs sign`)
+    AllFiles,
+    /// Only config files, `SKIPPED_DIRS` aren't entered (`sigrs harvest`)
+    ConfigFiles,
+}
+
 /// Returns list of file paths that are `depth` directories deep within `dir`
 /// `depth: 0` returns only files within `dir`
 /// `depth: 1` returns files within `dir` + files 1 sub-dir deep
+/// With `Discovery::ConfigFiles` files sigrs can't sign && `SKIPPED_DIRS` are left out
 /// Errors reading directories are currently propagated
 /// - Should they be ignored? Printed? return a struct of { paths: Vec<>, errors: Vec<String> } ?
 fn visit_depth(
     path: &Path,
     depth: u8,
+    discovery: Discovery,
 ) -> Result<Vec<PathBuf>, String> {
 
     let mut files: Vec<PathBuf> = vec![];
@@ -38,11 +56,15 @@ fn visit_depth(
             let entry = entry.map_err(|e| e.to_string())?;
             let entry_path = entry.path();
             if !entry_path.is_dir() {
-                files.push(entry_path.to_path_buf());
+                if discovery == Discovery::AllFiles || SignPath::is_config_file(&entry_path) {
+                    files.push(entry_path.to_path_buf());
+                }
             } else {
                 // only recurse on dir if not end depth
-                if depth > 0 {
-                    let i = visit_depth(&path, depth - 1)?;
+                let skipped = discovery == Discovery::ConfigFiles
+                    && entry.file_name().to_str().is_some_and(|name| SKIPPED_DIRS.contains(&name));
+                if depth > 0 && !skipped {
+                    let i = visit_depth(&entry_path, depth - 1, discovery)?;
                     files.extend(i);
                 }
             }
@@ -58,31 +80,48 @@ fn visit_depth(
 // UNSAFE: uses memorymap to read from LAS if needed, see `memmap.rs` for info
 pub unsafe fn handle_sign(args: &ArgMatches) -> Result<(), String> {
 
-    // Path 1)
-    // TODO No identity passed, go through prompt
-    let Some(identity_key) = args.get_one::<String>("identity") else {
-        return Err(String::from("Interactivity coming soon"));
+    // Defaults for this repository, flags passed always win, see `project.rs`
+    let project = match args.get_flag("no-project-file") {
+        true => ProjectConfig::default(),
+        false => load_project()?,
     };
 
     // Path 3.2)
     // If both --email & --username are provided, don't need to access LAS
-    let maybe_email = args.get_one::<String>("email");
-    let maybe_uname = args.get_one::<String>("username");
+    // SIGRS_EMAIL/SIGRS_USERNAME && .sigrs.toml fill in missing flags
+    let maybe_email = one_off_value(args, "email", ENV_EMAIL, &project.email);
+    let maybe_uname = one_off_value(args, "username", ENV_USERNAME, &project.username);
 
-    if let (Some(custom_email), Some(custom_uname)) = (maybe_email, maybe_uname) {
+    if let (Some(custom_email), Some(custom_uname)) = (&maybe_email, &maybe_uname) {
         // Both custom values are provided, don't need LAS, just go sign
-        return do_signing(custom_uname.as_bytes(), custom_email.as_bytes(), args);
+        // one-off author, so no metadata
+        return do_signing(custom_uname.as_bytes(), custom_email.as_bytes(), &AuthorMeta::default(), args, &project);
     }
 
     // Path 3.1 && 2) Both of these paths need LAS lookup
     // If either --email or --uname passed, use that custom value + LAS default
     // If neither passed, use default value for both
     // Handles --config-path
+    // Personal config.sigrs first, then shared team stores, see `las/layers.rs`
+    let source = PathBuf::from(extract_config_path(args)?);
     let memmap_las = get_memmap(args)?;
-    let las = process_las(&memmap_las);
-    let id_storage_entry = las.lookup_id(identity_key)
-        .ok_or_else(|| String::from("Identity does not exist"))?;
-    let identity = id_storage_entry.1.deref();
+    let shared = get_shared_memmaps();
+    let las = LayeredLas::with_shared(&source, &memmap_las, &shared)?;
+
+    // Path 1)
+    // TODO No identity passed, in .sigrs.toml or matched by a rule, go through prompt
+    let Some((identity_key, reason)) = select_identity(args, &project, &las)? else {
+        return Err(format!(
+            "No --identity or {} passed, no {} names one && no rule matches. See `sigrs whoami`. Interactivity coming soon",
+            ENV_IDENTITY, PROJECT_FILE_NAME
+        ));
+    };
+    if args.get_one::<String>("identity").is_none() {
+        println!("Signing as \"{}\" ({})", identity_key, reason);
+    }
+    let identity = las.lookup_id(&identity_key)
+        .ok_or_else(|| format!("Identity \"{}\" does not exist", identity_key))?
+        .identity;
 
     // Use --username if passed otherwise look up default
     let username = match maybe_uname {
@@ -91,7 +130,7 @@ pub unsafe fn handle_sign(args: &ArgMatches) -> Result<(), String> {
         },
         None => {
             // Look up from LAS
-            let x = identity.usernames.get(0)
+            let x = identity.default_username()
                 .ok_or_else(|| String::from("Identity does not contain any usernames"))?;
             Ok(x.to_vec())
         }
@@ -104,17 +143,127 @@ pub unsafe fn handle_sign(args: &ArgMatches) -> Result<(), String> {
         },
         None => {
             // Look up from LAS
-            let x = identity.emails.get(0)
+            let x = identity.default_email()
                 .ok_or_else(|| String::from("Identity does not contain any emails"))?;
             Ok(x.to_vec())
         }
     }?;
 
+    // url, role etc. for signers whose format supports them
+    let meta = identity.author_meta();
+
     // Have username && email now go sign
-    do_signing(username, email, args)
+    do_signing(username, email, &meta, args, &project)
+}
+
+/// `--<flag>`, else the environment variable `env`, else .sigrs.toml's value
+pub fn one_off_value(args: &ArgMatches, flag: &str, env: &str, project: &Option<String>) -> Option<String> {
+    args.try_get_one::<String>(flag).ok().flatten().cloned()
+        .or_else(|| env_value(env))
+        .or_else(|| project.clone())
+}
+
+/// Identity to sign with && why: --identity, then SIGRS_IDENTITY, then the nearest
+/// .sigrs.toml, then the first rule in LAS matching the working dir/git remotes,
+/// see `las/rules.rs`
+fn select_identity(
+    args: &ArgMatches,
+    project: &ProjectConfig,
+    las: &LayeredLas
+) -> Result<Option<(String, String)>, String> {
+    if let Some(key) = args.try_get_one::<String>("identity").ok().flatten() {
+        return Ok(Some((key.clone(), String::from("passed with --identity"))));
+    }
+    if let Some(key) = env_value(ENV_IDENTITY) {
+        return Ok(Some((key, format!("set by {}", ENV_IDENTITY))));
+    }
+    if let Some(key) = &project.identity {
+        let source = project.source.as_deref().unwrap_or(Path::new(PROJECT_FILE_NAME));
+        return Ok(Some((key.clone(), format!("named in {}", source.display()))));
+    }
+    let working_dir = current_dir().map_err(|e| format!("{:?}", e))?;
+    Ok(first_match(las, &RuleContext::detect(&working_dir)).map(|rule| {
+        let reason = format!("rule {} \"{}\": {}", rule.kind.name(), rule.pattern, rule.reason);
+        (rule.key, reason)
+    }))
+}
+
+/// `sigrs whoami`, what `sigrs sign` without --identity would sign with && why
+// See `memmap.rs` for explanations for why this function is unsafe
+pub unsafe fn handle_whoami(args: &ArgMatches) -> Result<(), String> {
+    let project = match args.get_flag("no-project-file") {
+        true => ProjectConfig::default(),
+        false => load_project()?,
+    };
+    let working_dir = current_dir().map_err(|e| format!("{:?}", e))?;
+    let context = RuleContext::detect(&working_dir);
+    println!("Working dir: {}", context.dir.display());
+    if context.remotes.is_empty() {
+        println!("Git remotes: none");
+    }
+    for (name, url) in &context.remotes {
+        println!("Git remote:  {} {}", name, url);
+    }
+
+    let maybe_email = one_off_value(args, "email", ENV_EMAIL, &project.email);
+    let maybe_uname = one_off_value(args, "username", ENV_USERNAME, &project.username);
+    if let (Some(username), Some(email)) = (&maybe_uname, &maybe_email) {
+        println!(
+            "\nOne-off author \"{}\" <{}> from {}/{} or {}, LAS isn't used",
+            username, email, ENV_USERNAME, ENV_EMAIL, PROJECT_FILE_NAME
+        );
+        return Ok(());
+    }
+
+    let source = PathBuf::from(extract_config_path(args)?);
+    let memmap_las = get_memmap(args)?;
+    let shared = get_shared_memmaps();
+    let las = LayeredLas::with_shared(&source, &memmap_las, &shared)?;
+    let Some((key, reason)) = select_identity(args, &project, &las)? else {
+        println!("\nNo identity selected: {} isn't set, no {} names one && no rule matches", ENV_IDENTITY, PROJECT_FILE_NAME);
+        println!("Pass --identity to sign, or add a rule with `sigrs rule add --identity <ID> --path <DIR>`");
+        return Ok(());
+    };
+
+    println!("\nIdentity \"{}\", {}", key, reason);
+    match las.lookup_id(&key) {
+        Some(entry) => {
+            let text = |value: Option<&[u8]>| value
+                .map(|v| format!("\"{}\"", String::from_utf8_lossy(v)))
+                .unwrap_or_else(|| String::from("none"));
+            let username = maybe_uname.map(|u| format!("\"{}\"", u))
+                .unwrap_or_else(|| text(entry.identity.default_username()));
+            let email = maybe_email.map(|e| format!("\"{}\"", e))
+                .unwrap_or_else(|| text(entry.identity.default_email()));
+            println!("Signs as {} <{}>", username, email);
+            if las.is_layered() {
+                println!("From {}", entry.source.display());
+            }
+        },
+        None => println!("But \"{}\" does not exist in config.sigrs", key),
+    }
+    Ok(())
+}
+
+/// Nearest .sigrs.toml from the working dir, empty if there is none
+fn load_project() -> Result<ProjectConfig, String> {
+    let working_dir = current_dir().map_err(|e| format!("{:?}", e))?;
+    match find_project_file(&working_dir) {
+        Some(path) => {
+            println!("Using {}", path.display());
+            ProjectConfig::load(&path)
+        },
+        None => Ok(ProjectConfig::default()),
+    }
 }
 
-fn do_signing<T>(username: T, email: T, args: &ArgMatches) -> Result<(), String> 
+fn do_signing<T>(
+    username: T,
+    email: T,
+    meta: &AuthorMeta,
+    args: &ArgMatches,
+    project: &ProjectConfig
+) -> Result<(), String>
 where
     T: Into<Vec<u8>>,
 {
@@ -123,7 +272,7 @@ where
     let uname_bytes: Vec<u8> = username.into();
     let email_bytes: Vec<u8> = email.into();
 
-    let (mut paths, errs) = get_signing_paths(args);
+    let (mut paths, errs) = get_signing_paths(args, project);
 
     // Just logging errors getting signing paths for now
     for e in errs.into_iter() {
@@ -134,7 +283,7 @@ where
         .iter_mut()
         .map(|path| {
             path.open_file_handle()
-                .and_then(|p| p.sign(uname_bytes.as_slice(), email_bytes.as_slice()))
+                .and_then(|p| p.sign(uname_bytes.as_slice(), email_bytes.as_slice(), meta))
         })
         .for_each(|res| {
             match res {
@@ -149,7 +298,38 @@ where
 
 // Use args to find && return all paths that need to be signed
 /// Returns (Vec<SignPaths to sign>  ,  Vec<Error strings creating SignPaths>)
-fn get_signing_paths<'s>(args: &'s ArgMatches) -> (Vec<SignPath>, Vec<String>) {
+/// Filters from `project` only apply when the matching flags weren't passed
+fn get_signing_paths(args: &ArgMatches, project: &ProjectConfig) -> (Vec<SignPath>, Vec<String>) {
+    let (paths, err_vals) = find_config_paths(args, Discovery::AllFiles);
+
+    // Mutually exclusive
+    let mut filters: Vec<Box<dyn FileFilter>> = Vec::new();
+    let type_flags = args.contains_id("only-include") || args.contains_id("only-exclude");
+    if let Some(type_filter) = project.type_filter().filter(|_| !type_flags) {
+        filters.push(type_filter);
+    }
+    if args.get_flag("if-signable") {
+        filters.push(Box::new(SignableFilter::IfSignable));
+    } else if args.get_flag("if-has-signatures") {
+        filters.push(Box::new(SignableFilter::IfHasSignatures));
+    } else if let Some(signable_filter) = project.signable_filter() {
+        filters.push(signable_filter);
+    }
+
+    // Sort filters by access requirements
+    // filters requiring file reads come last, so they are only checked if nessecary
+    filters.sort_by_access();
+
+    let mut paths = paths;
+    (paths.apply_filters(filters), err_vals)
+}
+
+/// Every config file matching the directory && config type args
+/// (`--file`, `--dir`, `--dir-with-depth`, `--working-dir[-recursive]`,
+/// `--only-include`/`--only-exclude`). Also used by `sigrs harvest`,
+/// which only defines some of them, see `Discovery`
+/// Returns (Vec<SignPaths found>  ,  Vec<Error strings creating SignPaths>)
+pub fn find_config_paths(args: &ArgMatches, discovery: Discovery) -> (Vec<SignPath>, Vec<String>) {
 
     let mut path_vals: BTreeSet<SignPath> = BTreeSet::new();
     let mut err_vals: Vec<String> = Vec::new();
@@ -179,7 +359,13 @@ fn get_signing_paths<'s>(args: &'s ArgMatches) -> (Vec<SignPath>, Vec<String>) {
     {
         // for each dir, recursively get all files (--dir) has no depth limit
         for dir in dirs {
-            let p_bufs = visit_depth(Path::new(&dir), u8::MAX).unwrap();
+            let p_bufs = match visit_depth(Path::new(&dir), u8::MAX, discovery) {
+                Ok(p_bufs) => p_bufs,
+                Err(e) => {
+                    err_vals.push(format!("Problem reading {:?}: {}", dir, e));
+                    continue;
+                }
+            };
             for pb in p_bufs {
                 let v = SignPath::new(pb);
                 match v {
@@ -199,14 +385,26 @@ fn get_signing_paths<'s>(args: &'s ArgMatches) -> (Vec<SignPath>, Vec<String>) {
         .map(|dirs| dirs.cloned().collect::<Vec<String>>()) 
     {
         // get delimiter
-        let delimiter = args.get_one::<&String>("delimiter").unwrap();
+        let delimiter = args.get_one::<String>("delimiter").unwrap();
         // for each, split by delimiter, then use visit_depth
         for dwd in dwds {
             // split each dir-with-depth by delimiter
-            let (depth, dir) = parse_dir_with_depth(&dwd, &delimiter).unwrap();
+            let (depth, dir) = match parse_dir_with_depth(&dwd, delimiter) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    err_vals.push(e);
+                    continue;
+                }
+            };
 
             // use visit_depth to get files && append to file_paths
-            let p_bufs = visit_depth(Path::new(&dir), depth).unwrap();
+            let p_bufs = match visit_depth(Path::new(&dir), depth, discovery) {
+                Ok(p_bufs) => p_bufs,
+                Err(e) => {
+                    err_vals.push(format!("Problem reading {:?}: {}", dir, e));
+                    continue;
+                }
+            };
             for pb in p_bufs {
                 let v = SignPath::new(pb);
                 match v {
@@ -222,11 +420,12 @@ fn get_signing_paths<'s>(args: &'s ArgMatches) -> (Vec<SignPath>, Vec<String>) {
     }
 
     // if including current working directory (but not recursive sub-dirs)
-    if args.get_flag("working-dir") {
+    // try_get, since not every command using this defines it
+    if args.try_get_one::<bool>("working-dir").ok().flatten().is_some_and(|v| *v) {
         let working_dir = current_dir();
         match working_dir {
             Ok(wd) => {
-                let p_bufs = visit_depth(wd.as_path(), 0u8).unwrap();
+                let p_bufs = visit_depth(wd.as_path(), 0u8, discovery).unwrap();
                 for pb in p_bufs.into_iter() {
                     let v = SignPath::new(pb);
                     match v {
@@ -246,7 +445,7 @@ fn get_signing_paths<'s>(args: &'s ArgMatches) -> (Vec<SignPath>, Vec<String>) {
     }
 
     // working-dir-recursive has default missing value -1 if no depth level provided
-    if let Some(depth) = args.get_one::<i8>("working-dir-recursive") {
+    if let Some(depth) = args.try_get_one::<i8>("working-dir-recursive").ok().flatten() {
 
         let working_dir = current_dir();
         // If -1 (which is default) recurse all, 
@@ -255,7 +454,7 @@ fn get_signing_paths<'s>(args: &'s ArgMatches) -> (Vec<SignPath>, Vec<String>) {
             Ok(wd) => {
                 // hack to safely cast to u8
                 let depth = if depth <= &-1i8 { u8::MAX } else { depth.unsigned_abs() };
-                let p_bufs = visit_depth(wd.as_path(), depth).unwrap();
+                let p_bufs = visit_depth(wd.as_path(), depth, discovery).unwrap();
                 for pb in p_bufs {
                     let v = SignPath::new(pb);
                     match v {
@@ -282,34 +481,22 @@ fn get_signing_paths<'s>(args: &'s ArgMatches) -> (Vec<SignPath>, Vec<String>) {
     let mut filters: Vec<Box<dyn FileFilter>> = Vec::new();
     
     // These are mutually exclusive
-    if let Some(only_include) = args.get_one::<&String>("only-include") {
+    if let Some(only_include) = args.get_one::<String>("only-include") {
         let delimiter = args.get_one::<String>("delimiter").unwrap();
         let include_types = parse_only_include_exclude(only_include, delimiter);
         filters.push(Box::new(ConfigTypeFilter::Include(include_types)));
-    } else if let Some(only_exclude) = args.get_one::<&String>("only-exclude") {
+    } else if let Some(only_exclude) = args.get_one::<String>("only-exclude") {
         let delimiter = args.get_one::<String>("delimiter").unwrap();
         let exclude_types = parse_only_include_exclude(only_exclude, delimiter);
         filters.push(Box::new(ConfigTypeFilter::Exclude(exclude_types)));
     }
 
-    // Mutually exclusive
-    if args.get_flag("if-signable") {
-        filters.push(Box::new(SignableFilter::IfSignable));
-    } else if args.get_flag("if-has-signatures") {
-        filters.push(Box::new(SignableFilter::IfHasSignatures));
-    }
-
-    // All file filters have been parsed/extracted, apply them to 
-    // all the paths that matched directory filters
-    // ---
-    // Sort filters by access requirements
-    // filters requiring file reads come last, so they are only checked if nessecary
-    filters.sort_by_access();
-
+    // Filters requiring file reads (--if-signable etc.) are applied by
+    // `get_signing_paths` after these, so they are only checked if nessecary
     let mut path_vals_vec = path_vals.into_iter().collect::<Vec<SignPath>>();
-    path_vals_vec.apply_filters(filters);
+    let path_vals_vec = path_vals_vec.apply_filters(filters);
 
-    // At this point, entries have been deduped, filters have been applied
+    // At this point, entries have been deduped, type filters have been applied
     // and path_vals should contain all matching SignPaths,
     // and err_vals should contain err strings for problems creating SignPaths
     (path_vals_vec, err_vals)
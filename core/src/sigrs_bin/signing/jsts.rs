@@ -1,11 +1,12 @@
 use std::fs::File;
 use crate::signing::signpath::SignPath;
-use crate::signing::signing_utils::{extract_file_content, generate_temp_path};
+use crate::signing::signing_utils::{extract_file_content, generate_temp_path, json_author, FoundAuthor};
 use std::io::{
     self, Read, Seek, SeekFrom, Write
 };
 use std::path::PathBuf;
 use serde_json::{Value, json};
+use crate::identity::AuthorMeta;
 
 pub fn package_json_is_signable(sign_path: &mut SignPath, mut file: File) -> bool {
 
@@ -80,7 +81,8 @@ pub fn sign_package_json(
     f: &mut Option<File>,
     fc: &mut Option<String>,
     uname: &[u8],
-    email: &[u8]
+    email: &[u8],
+    meta: &AuthorMeta
 ) -> Result<String, String> {
 
     let file_contents = extract_file_content(&path, f.as_mut(), fc.as_mut())?;
@@ -106,22 +108,22 @@ pub fn sign_package_json(
     let em = String::from_utf8(email.to_vec())
         .map_err(|e| e.to_string())?;
 
+    // {"name": name, "email": email, "url": url}, url only if set
+    let mut entry = json!({
+        "name": name,
+        "email": em,
+    });
+    if let Some(url) = &meta.url {
+        entry["url"] = Value::String(url.clone());
+    }
+
     // seems redundant, but ensures the "contributrs" field is an array
     // since contributors can be either array or single string
     if let Value::Array(contribs) = contributors_field {
-        // {"name": name, "email": email}
-        let entry = json!({
-            "name": name,
-            "email": em,
-        });
         contribs.push(entry);
     } else if let Value::String(contrib) = contributors_field {
         // Convert to array with existing contrib inside
         // append new entry with name/em
-        let entry = json!({
-            "name": name,
-            "email": em,
-        });
 
         // replace string field with array
         *contributors_field = Value::Array(vec![
@@ -202,3 +204,17 @@ pub fn sign_package_json(
 //   ]
 // }
 
+
+/// `author` && every `contributors` entry, string or object form
+pub fn package_json_authors(content: &str) -> Result<Vec<FoundAuthor>, String> {
+    let parsed: Value = serde_json::from_str(content)
+        .map_err(|e| format!("Problem parsing JSON: {:?}", e))?;
+
+    let mut found: Vec<FoundAuthor> = parsed.get("author").and_then(json_author).into_iter().collect();
+    match parsed.get("contributors") {
+        Some(Value::Array(contribs)) => found.extend(contribs.iter().filter_map(json_author)),
+        Some(contrib) => found.extend(json_author(contrib)),
+        None => {},
+    }
+    Ok(found)
+}
@@ -7,6 +7,7 @@ pub mod rust;
 pub mod jsts;
 pub mod php;
 pub mod ruby;
+pub mod project;
 
 #[cfg(test)]
 mod signing_tests;
@@ -1,11 +1,12 @@
 use std::fs::File;
 use crate::signing::signpath::SignPath;
-use crate::signing::signing_utils::{extract_file_content, generate_temp_path};
+use crate::signing::signing_utils::{extract_file_content, generate_temp_path, json_author, FoundAuthor};
 use std::io::{
     self, Read, Seek, SeekFrom, Write
 };
 use std::path::PathBuf;
 use serde_json::{Value, json};
+use crate::identity::AuthorMeta;
 
 pub fn composer_json_is_signable(sign_path: &mut SignPath, mut file: File) -> bool {
 
@@ -80,7 +81,8 @@ pub fn sign_composer_json(
     f: &mut Option<File>,
     fc: &mut Option<String>,
     uname: &[u8],
-    email: &[u8]
+    email: &[u8],
+    meta: &AuthorMeta
 ) -> Result<String, String> {
 
     let file_contents = extract_file_content(&path, f.as_mut(), fc.as_mut())?;
@@ -107,11 +109,18 @@ pub fn sign_composer_json(
 
     // composer.json authors field must be an array
     if let Value::Array(authors) = authors_field {
-        // {"name": name, "email": email}
-        let entry = json!({
+        // {"name": name, "email": email, "homepage": url, "role": role}
+        // homepage && role only if set
+        let mut entry = json!({
             "name": name,
             "email": em,
         });
+        if let Some(url) = &meta.url {
+            entry["homepage"] = Value::String(url.clone());
+        }
+        if let Some(role) = &meta.role {
+            entry["role"] = Value::String(role.clone());
+        }
         authors.push(entry);
     } else {
         return Err(String::from("Invalid composer.json format"));
@@ -191,3 +200,14 @@ pub fn sign_composer_json(
 //   }
 // }
 //
+
+/// Every `authors` entry, `{ name, email, homepage, role }`
+pub fn composer_json_authors(content: &str) -> Result<Vec<FoundAuthor>, String> {
+    let parsed: Value = serde_json::from_str(content)
+        .map_err(|e| format!("Problem parsing JSON: {:?}", e))?;
+
+    Ok(match parsed.get("authors") {
+        Some(Value::Array(authors)) => authors.iter().filter_map(json_author).collect(),
+        _ => vec![],
+    })
+}
@@ -0,0 +1,97 @@
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+use super::filters::{ConfigTypeFilter, FileFilter, SignableFilter};
+
+// ========== PROJECT FILE (per repository signing defaults)
+// sigrs sign --working-dir-recursive
+// == sign walks up from the working dir to the first .sigrs.toml, so no
+// == --identity is needed in a repository that has one like:
+//
+// identity = "work"
+// username = "Bob Smith"            # optional, instead of the identity's default
+// email = "bob@corp.com"            # optional, instead of the identity's default
+// only-include = ["Cargo.toml"]     # or only-exclude, same values as the flags
+// if-signable = true                # or if-has-signatures
+//
+// == flags passed to sign, then SIGRS_IDENTITY/SIGRS_USERNAME/SIGRS_EMAIL, always
+// == win over the file. With both username && email set the identity is optional,
+// == LAS isn't read. Without an identity here, in --identity or SIGRS_IDENTITY, the
+// == rules in config.sigrs pick one, see `las/rules.rs`
+// sigrs sign --working-dir --no-project-file
+// == ignores any .sigrs.toml
+
+pub const PROJECT_FILE_NAME: &str = ".sigrs.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProjectConfig {
+    pub identity: Option<String>,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub only_include: Vec<String>,
+    #[serde(default)]
+    pub only_exclude: Vec<String>,
+    #[serde(default)]
+    pub if_signable: bool,
+    #[serde(default)]
+    pub if_has_signatures: bool,
+    /// File this was loaded from
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl ProjectConfig {
+    /// Errors on invalid toml, unknown keys && options that can't be used together
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(content).map_err(|e| e.to_string())?;
+        if !config.only_include.is_empty() && !config.only_exclude.is_empty() {
+            return Err(String::from("only-include && only-exclude cannot be used together"));
+        }
+        if config.if_signable && config.if_has_signatures {
+            return Err(String::from("if-signable && if-has-signatures cannot be used together"));
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Problem reading {}: {:?}", path.display(), e))?;
+        let config = Self::parse(&content).map_err(|e| format!("Problem parsing {}: {}", path.display(), e))?;
+        Ok(Self { source: Some(path.to_path_buf()), ..config })
+    }
+
+    /// only-include/only-exclude, for when neither flag was passed
+    pub fn type_filter(&self) -> Option<Box<dyn FileFilter>> {
+        let types = |values: &[String]| values.iter().map(OsString::from).collect();
+        if !self.only_include.is_empty() {
+            Some(Box::new(ConfigTypeFilter::Include(types(&self.only_include))))
+        } else if !self.only_exclude.is_empty() {
+            Some(Box::new(ConfigTypeFilter::Exclude(types(&self.only_exclude))))
+        } else {
+            None
+        }
+    }
+
+    /// if-signable/if-has-signatures, for when neither flag was passed
+    pub fn signable_filter(&self) -> Option<Box<dyn FileFilter>> {
+        if self.if_signable {
+            Some(Box::new(SignableFilter::IfSignable))
+        } else if self.if_has_signatures {
+            Some(Box::new(SignableFilter::IfHasSignatures))
+        } else {
+            None
+        }
+    }
+}
+
+/// First .sigrs.toml in `start` or any dir above it
+pub fn find_project_file(start: &Path) -> Option<PathBuf> {
+    start.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE_NAME))
+        .find(|path| path.is_file())
+}
@@ -7,7 +7,9 @@ use toml::Value;
 use crate::signing::signpath::SignPath;
 use crate::signing::signing_utils::{
     extract_file_content,
-    generate_temp_path
+    generate_temp_path,
+    parse_author_string,
+    FoundAuthor
 };
 
 /// Checks if authors field is present within a pyproject_toml
@@ -227,6 +229,53 @@ pub fn sign_pyproject_toml(
 // Will not be supporting `setup.py` since there is no agreed upon standard
 // for defining multiple authors. If this changes in the future I can add support
 
+/// Every entry in any `authors` array, both the PEP 621 `{ name, email }` form
+/// ([project]) && the `Name <email>` form (poetry)
+pub fn pyproject_toml_authors(content: &str) -> Result<Vec<FoundAuthor>, String> {
+    let parsed = content.parse::<Value>()
+        .map_err(|e| format!("Problem parsing TOML: {}", e))?;
+
+    fn author(value: &Value) -> Option<FoundAuthor> {
+        match value {
+            Value::String(author) => Some(parse_author_string(author)),
+            Value::Table(table) => {
+                let get = |key: &str| {
+                    table.get(key)
+                        .and_then(Value::as_str)
+                        .map(str::trim)
+                        .filter(|v| !v.is_empty())
+                        .map(String::from)
+                };
+                Some(FoundAuthor { name: get("name"), email: get("email"), ..FoundAuthor::default() })
+            },
+            _ => None,
+        }
+    }
+
+    fn search_authors(value: &Value, found: &mut Vec<FoundAuthor>) {
+        if let Some(authors) = value.get("authors").and_then(Value::as_array) {
+            found.extend(authors.iter().filter_map(author));
+        }
+        match value {
+            Value::Table(table) => {
+                for val in table.values() {
+                    search_authors(val, found);
+                }
+            },
+            Value::Array(array) => {
+                for val in array {
+                    search_authors(val, found);
+                }
+            },
+            _ => {} // ignore non collections
+        }
+    }
+
+    let mut found = Vec::new();
+    search_authors(&parsed, &mut found);
+    Ok(found)
+}
+
 // pub fn setup_py_is_signable(sign_path: &mut SignPath, mut file: File) -> bool {
 //     false
 // }
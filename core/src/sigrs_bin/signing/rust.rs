@@ -1,7 +1,9 @@
 use std::fs::File;
 use crate::signing::signing_utils::{
     extract_file_content,
-    generate_temp_path
+    generate_temp_path,
+    parse_author_string,
+    FoundAuthor
 };
 use crate::signing::signpath::SignPath;
 use std::io::{
@@ -217,3 +219,32 @@ pub fn sign_cargo_toml(
     
     Ok(format!("{} successfully updated", path.to_str().unwrap_or("Config")))
 }
+
+/// Every `Name <email>` entry in any `authors` array ([package], [workspace.package] ..)
+pub fn cargo_toml_authors(content: &str) -> Result<Vec<FoundAuthor>, String> {
+    let parsed = content.parse::<Value>()
+        .map_err(|e| format!("Problem parsing TOML: {}", e))?;
+
+    fn search_authors(value: &Value, found: &mut Vec<FoundAuthor>) {
+        if let Some(authors) = value.get("authors").and_then(Value::as_array) {
+            found.extend(authors.iter().filter_map(Value::as_str).map(parse_author_string));
+        }
+        match value {
+            Value::Table(table) => {
+                for val in table.values() {
+                    search_authors(val, found);
+                }
+            },
+            Value::Array(array) => {
+                for val in array {
+                    search_authors(val, found);
+                }
+            },
+            _ => {} // ignore non collections
+        }
+    }
+
+    let mut found = Vec::new();
+    search_authors(&parsed, &mut found);
+    Ok(found)
+}
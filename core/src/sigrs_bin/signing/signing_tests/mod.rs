@@ -9,3 +9,7 @@ pub mod test_rust;
 pub mod test_jsts;
 #[cfg(test)]
 pub mod test_php;
+#[cfg(test)]
+pub mod test_authors;
+#[cfg(test)]
+pub mod test_project;
@@ -0,0 +1,83 @@
+#[cfg(test)]
+use std::path::PathBuf;
+
+#[cfg(test)]
+use crate::signing::signing_utils::{parse_author_string, FoundAuthor};
+
+#[cfg(test)]
+use crate::signing::{
+    jsts::package_json_authors,
+    php::composer_json_authors,
+    python::pyproject_toml_authors,
+    rust::cargo_toml_authors,
+    signpath::SignPath,
+};
+
+#[cfg(test)]
+use crate::identity::AuthorMeta;
+
+#[cfg(test)]
+fn author(name: Option<&str>, email: Option<&str>) -> FoundAuthor {
+    FoundAuthor {
+        name: name.map(String::from),
+        email: email.map(String::from),
+        meta: AuthorMeta::default(),
+    }
+}
+
+#[test]
+fn test_parse_author_string() {
+    let with_url = parse_author_string("Barney Rubble <b@rubble.com> (http://barnyrubble.tumblr.com/)");
+    assert_eq!(with_url.name.as_deref(), Some("Barney Rubble"));
+    assert_eq!(with_url.email.as_deref(), Some("b@rubble.com"));
+    assert_eq!(with_url.meta.url.as_deref(), Some("http://barnyrubble.tumblr.com/"));
+
+    assert_eq!(parse_author_string("  Jane   Doe <jane@x.com>"), author(Some("Jane Doe"), Some("jane@x.com")));
+    assert_eq!(parse_author_string("Jane Doe"), author(Some("Jane Doe"), None));
+    assert_eq!(parse_author_string("jane@x.com"), author(None, Some("jane@x.com")));
+    assert_eq!(parse_author_string("<jane@x.com>"), author(None, Some("jane@x.com")));
+    assert_eq!(parse_author_string(""), author(None, None));
+}
+
+#[test]
+fn test_config_authors() {
+    let cargo = "[package]\nname = \"x\"\nauthors = [\"Jane <jane@x.com>\", \"Bob\"]\n[workspace.package]\nauthors = [\"Ann <ann@x.com>\"]\n";
+    let package = r#"{
+        "author": "Jane <jane@x.com>",
+        "contributors": [{ "name": "Bob", "email": "bob@x.com", "url": "bob.dev" }, "Ann <ann@x.com>", 5]
+    }"#;
+    let composer = r#"{ "authors": [{ "name": "Jane", "email": "jane@x.com", "homepage": "jane.dev", "role": "Lead" }] }"#;
+    let pyproject = "[project]\nauthors = [{ name = \"Jane\", email = \"jane@x.com\" }, { email = \"bob@x.com\" }]\n[tool.poetry]\nauthors = [\"Ann <ann@x.com>\"]\n";
+
+    let cargo = cargo_toml_authors(cargo).unwrap();
+    let package = package_json_authors(package).unwrap();
+    let composer = composer_json_authors(composer).unwrap();
+    let pyproject = pyproject_toml_authors(pyproject).unwrap();
+
+    assert_eq!(cargo.len(), 3);
+    assert!(cargo.contains(&author(Some("Bob"), None)));
+    assert!(cargo.contains(&author(Some("Ann"), Some("ann@x.com"))));
+
+    assert_eq!(package.len(), 3);
+    assert_eq!(package[0], author(Some("Jane"), Some("jane@x.com")));
+    assert_eq!(package[1].meta.url.as_deref(), Some("bob.dev"));
+    assert_eq!(package[2], author(Some("Ann"), Some("ann@x.com")));
+
+    assert_eq!(composer.len(), 1);
+    assert_eq!(composer[0].meta.url.as_deref(), Some("jane.dev"));
+    assert_eq!(composer[0].meta.role.as_deref(), Some("Lead"));
+
+    assert_eq!(pyproject.len(), 3);
+    assert!(pyproject.contains(&author(None, Some("bob@x.com"))));
+    assert!(pyproject.contains(&author(Some("Ann"), Some("ann@x.com"))));
+
+    assert!(cargo_toml_authors("not [toml").is_err());
+    assert!(package_json_authors("{").is_err());
+}
+
+#[test]
+fn test_signpath_new_unsupported() {
+    assert!(SignPath::new(PathBuf::from("./README.md")).is_err());
+    assert!(SignPath::is_config_file(&PathBuf::from("./a/Cargo.toml")));
+    assert!(!SignPath::is_config_file(&PathBuf::from("./a/cargo.toml")));
+}
@@ -15,9 +15,15 @@ use crate::signing::jsts::{
     package_json_is_signable,
 };
 
+#[cfg(test)]
+use crate::signing::jsts::sign_package_json;
+
 #[cfg(test)]
 use crate::signing::signpath::SignPath;
 
+#[cfg(test)]
+use crate::identity::AuthorMeta;
+
 
 #[test]
 fn test_noauthors_hasauthors_package_json() {
@@ -166,5 +172,30 @@ fn test_withauthors_issignable_package_json() {
     delete_test_file(&test_buf).unwrap();
 }
 
+#[test]
+fn test_sign_with_metadata_jsts() {
 
+    let mut test_buf = PathBuf::from_str("./TestPackageJsonSignMeta.json").unwrap();
+    let cfg_type = ConfigFileType::PackageJson(ConfigTemplate::NoAuthors);
+    gen_test_config(cfg_type, &test_buf).unwrap();
 
+    let meta = AuthorMeta {
+        url: Some(String::from("https://bob.dev")),
+        role: Some(String::from("Maintainer")),
+        ..AuthorMeta::default()
+    };
+    let res = sign_package_json(&mut test_buf, &mut None, &mut None, b"bob", b"bob@x.com", &meta);
+    let signed = std::fs::read_to_string(&test_buf).unwrap();
+
+    delete_test_file(&test_buf).unwrap();
+
+    assert!(res.is_ok(), "{:?}", res);
+    let parsed: serde_json::Value = serde_json::from_str(&signed).unwrap();
+    // NoAuthors template, so ours is the only entry
+    let entry = &parsed["contributors"][0];
+    assert_eq!(entry["name"], "bob");
+    assert_eq!(entry["email"], "bob@x.com");
+    assert_eq!(entry["url"], "https://bob.dev");
+    // package.json contributors have no role field
+    assert!(entry.get("role").is_none());
+}
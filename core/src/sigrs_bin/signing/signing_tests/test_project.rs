@@ -0,0 +1,86 @@
+#[cfg(test)]
+use std::{fs, path::Path};
+
+#[cfg(test)]
+use crate::sigrs_command;
+
+#[cfg(test)]
+use crate::utils::ENV_EMAIL;
+
+#[cfg(test)]
+use crate::signing::{
+    filters::FileFilterApply,
+    handle::{find_config_paths, one_off_value, Discovery},
+    project::{find_project_file, ProjectConfig, PROJECT_FILE_NAME},
+};
+
+#[test]
+fn test_project_config_parse() {
+    let config = ProjectConfig::parse(
+        "identity = \"work\"\nemail = \"bob@corp.com\"\nonly-include = [\"Cargo.toml\"]\nif-signable = true\n"
+    ).unwrap();
+    assert_eq!(config.identity.as_deref(), Some("work"));
+    assert_eq!(config.username, None);
+    assert_eq!(config.email.as_deref(), Some("bob@corp.com"));
+    assert_eq!(config.only_include, vec!["Cargo.toml"]);
+    assert!(config.type_filter().is_some());
+    assert!(config.signable_filter().is_some());
+
+    assert_eq!(ProjectConfig::parse("").unwrap(), ProjectConfig::default());
+    assert!(ProjectConfig::default().type_filter().is_none());
+    // typos are errors rather than silently ignored
+    assert!(ProjectConfig::parse("identiy = \"work\"").is_err());
+    assert!(ProjectConfig::parse("only-include = [\"Cargo.toml\"]\nonly-exclude = [\"setup.py\"]").is_err());
+    assert!(ProjectConfig::parse("if-signable = true\nif-has-signatures = true").is_err());
+}
+
+#[test]
+fn test_project_file_lookup() {
+    let dir = Path::new("./TestSignProject");
+    let nested = dir.join("crates/inner");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(dir.join(PROJECT_FILE_NAME), "identity = \"work\"\nonly-include = [\"Cargo.toml\"]\n").unwrap();
+    fs::write(dir.join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+    fs::write(dir.join("package.json"), "{}\n").unwrap();
+
+    let from_nested = find_project_file(&nested);
+    let config = ProjectConfig::load(&dir.join(PROJECT_FILE_NAME));
+
+    let matches = sigrs_command()
+        .try_get_matches_from(["sigrs", "sign", "-d", "./TestSignProject"])
+        .unwrap();
+    let (mut paths, _) = find_config_paths(matches.subcommand().unwrap().1, Discovery::AllFiles);
+    let found_before = paths.len();
+    let filtered = paths.apply_filters(config.clone().unwrap().type_filter().into_iter().collect());
+
+    let skipped = sigrs_command().try_get_matches_from(["sigrs", "sign", "--no-project-file"]);
+
+    fs::remove_dir_all(dir).unwrap();
+
+    // nearest file above the working dir
+    assert_eq!(from_nested, Some(dir.join(PROJECT_FILE_NAME)));
+    assert_eq!(config.unwrap().identity.as_deref(), Some("work"));
+    assert_eq!(found_before, 2);
+    assert_eq!(filtered.len(), 1);
+    assert!(skipped.is_ok_and(|m| m.subcommand().unwrap().1.get_flag("no-project-file")));
+}
+
+#[test]
+fn test_one_off_value_precedence() {
+    let project = Some(String::from("project@corp.com"));
+    let plain = sigrs_command().try_get_matches_from(["sigrs", "sign"]).unwrap();
+    let flag = sigrs_command().try_get_matches_from(["sigrs", "sign", "--email", "flag@corp.com"]).unwrap();
+    let (plain, flag) = (plain.subcommand().unwrap().1, flag.subcommand().unwrap().1);
+
+    let without_env = one_off_value(plain, "email", ENV_EMAIL, &project);
+    std::env::set_var(ENV_EMAIL, "env@corp.com");
+    let with_env = one_off_value(plain, "email", ENV_EMAIL, &project);
+    let with_flag = one_off_value(flag, "email", ENV_EMAIL, &project);
+    std::env::remove_var(ENV_EMAIL);
+
+    // flag > environment > .sigrs.toml
+    assert_eq!(without_env.as_deref(), Some("project@corp.com"));
+    assert_eq!(with_env.as_deref(), Some("env@corp.com"));
+    assert_eq!(with_flag.as_deref(), Some("flag@corp.com"));
+    assert_eq!(one_off_value(plain, "email", ENV_EMAIL, &None), None);
+}
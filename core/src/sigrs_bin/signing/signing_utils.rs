@@ -11,6 +11,8 @@ use std::{
         UNIX_EPOCH
     }
 };
+use serde_json::Value;
+use crate::identity::AuthorMeta;
 
 /// If fc (file content) is Some, that is returned
 /// `else`
@@ -76,3 +78,70 @@ pub fn generate_temp_path(path: &PathBuf) -> Result<PathBuf, String> {
     tmp_path.push(format!("sigrs{seed:#x}"));
     Ok(tmp_path)
 }
+
+/// An author already listed in a config file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FoundAuthor {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// url/role, for formats that have them
+    pub meta: AuthorMeta,
+}
+
+/// Takes `<...>` or `(...)` out of `value`, returning (inside, rest)
+fn take_delimited(value: &str, open: char, close: char) -> (Option<String>, String) {
+    let Some(start) = value.find(open) else {
+        return (None, value.to_string());
+    };
+    let Some(len) = value[start..].find(close) else {
+        return (None, value.to_string());
+    };
+    let inside = value[start + 1..start + len].trim();
+    let rest = format!("{} {}", &value[..start], &value[start + len + 1..]);
+    ((!inside.is_empty()).then(|| inside.to_string()), rest)
+}
+
+/// Parses the `Name <email> (url)` string form used by Cargo.toml, poetry && npm.
+/// Every part is optional, a lone `x@y` is taken as an email
+pub fn parse_author_string(value: &str) -> FoundAuthor {
+    let (email, rest) = take_delimited(value, '<', '>');
+    let (url, rest) = take_delimited(&rest, '(', ')');
+    let name = rest.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    let (name, email) = match (name, email) {
+        (name, None) if name.contains('@') && !name.contains(' ') => (None, Some(name)),
+        (name, email) => ((!name.is_empty()).then_some(name), email),
+    };
+    FoundAuthor {
+        name,
+        email,
+        meta: AuthorMeta { url, ..AuthorMeta::default() },
+    }
+}
+
+/// An author from a JSON config, either the string form or
+/// `{"name", "email", "url"|"homepage", "role"}`
+pub fn json_author(value: &Value) -> Option<FoundAuthor> {
+    match value {
+        Value::String(author) => Some(parse_author_string(author)),
+        Value::Object(author) => {
+            let get = |key: &str| {
+                author.get(key)
+                    .and_then(Value::as_str)
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(String::from)
+            };
+            Some(FoundAuthor {
+                name: get("name"),
+                email: get("email"),
+                meta: AuthorMeta {
+                    url: get("url").or_else(|| get("homepage")),
+                    role: get("role"),
+                    ..AuthorMeta::default()
+                },
+            })
+        },
+        _ => None,
+    }
+}
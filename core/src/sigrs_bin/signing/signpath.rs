@@ -1,34 +1,43 @@
 use std::{
     cmp::{Ord, Ordering},
     ffi::OsStr,
-    fs::File,
+    fs::{self, File},
     path::{Path, PathBuf},
     io::{Read, Seek, SeekFrom},
 };
 
+use crate::identity::AuthorMeta;
 use super::{
     rust::{
         cargo_is_signable,
         cargo_has_authors,
+        cargo_toml_authors,
         sign_cargo_toml,
     },
     python::{ 
         sign_pyproject_toml,
         pyproject_toml_is_signable,
         pyproject_toml_has_authors,
+        pyproject_toml_authors,
     }, 
     jsts::{
         sign_package_json,
         package_json_is_signable,
-        package_json_has_authors
+        package_json_has_authors,
+        package_json_authors,
     },
     php::{
         sign_composer_json,
         composer_json_is_signable,
-        composer_json_has_authors
+        composer_json_has_authors,
+        composer_json_authors,
     },
+    signing_utils::FoundAuthor,
 };
 
+/// File names `SignPath::new` accepts
+pub const CONFIG_FILE_NAMES: [&str; 4] = ["Cargo.toml", "package.json", "composer.json", "pyproject.toml"];
+
 pub enum SignPath {
     // https://docs.rs/cargo-util-schemas/0.7.0/cargo_util_schemas/manifest/struct.TomlPackage.html#structfield.authors
     // ------------------- Rust
@@ -114,12 +123,21 @@ impl SignPath {
                     file_content: None
                 }
             },
-            Some(_) | None => panic!("TODO: Handle nonmatching config file names? or err")
+            Some(_) | None => {
+                return Err(format!("{:?} is not a supported config file", fpath));
+            }
         };
 
         Ok(ret)
     }
 
+    /// Whether `path` is named like a config file sigrs can sign
+    pub fn is_config_file(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| CONFIG_FILE_NAMES.contains(&name))
+    }
+
     pub fn get_path<'s>(&'s self) -> &'s Path {
         let (path, _maybe_file) = match self {
             Self::CargoToml { path, file, .. }
@@ -195,9 +213,10 @@ impl SignPath {
 
 
     /// Signs this SignPath's path with a Username_bytes && Author_bytes
+    /// `meta` fields are only written by formats that support them
     /// ! Sign cannot require self.file to exist, because it wont if user
     /// didn't use --is-signable filters etc.
-    pub fn sign(&mut self, uname: &[u8], email: &[u8]) -> Result<String, String> {
+    pub fn sign(&mut self, uname: &[u8], email: &[u8], meta: &AuthorMeta) -> Result<String, String> {
         match self {
             // Each of these methods should return an Ok("file_x was signed") msg
             Self::CargoToml { path, file, file_content } => {
@@ -206,11 +225,11 @@ impl SignPath {
             },
 
             Self::PackageJson { path, file, file_content } => {
-                sign_package_json(path, file, file_content, uname, email)
+                sign_package_json(path, file, file_content, uname, email, meta)
             },
 
             Self::ComposerJson { path, file, file_content } => {
-                sign_composer_json(path, file, file_content, uname, email)
+                sign_composer_json(path, file, file_content, uname, email, meta)
             },
 
             Self::PyProjectToml { path, file, file_content } => {
@@ -220,6 +239,29 @@ impl SignPath {
 
     }
 
+    /// Authors already listed in this config file, used by `sigrs harvest`
+    pub fn authors(&self) -> Result<Vec<FoundAuthor>, String> {
+        let (path, maybe_file_content) = match self {
+            Self::CargoToml { path, file_content, .. }
+            | Self::PackageJson { path, file_content, .. }
+            | Self::ComposerJson { path, file_content, .. }
+            | Self::PyProjectToml { path, file_content, .. } => (path, file_content)
+        };
+        // read only, harvesting must work on checkouts we can't write to
+        let content = match maybe_file_content {
+            Some(content) => content.clone(),
+            None => fs::read_to_string(path)
+                .map_err(|e| format!("Problem reading '{:?}': {:?}", path, e))?,
+        };
+
+        match self {
+            Self::CargoToml { .. } => cargo_toml_authors(&content),
+            Self::PackageJson { .. } => package_json_authors(&content),
+            Self::ComposerJson { .. } => composer_json_authors(&content),
+            Self::PyProjectToml { .. } => pyproject_toml_authors(&content),
+        }
+    }
+
     /// Checks if self same variant as other
     pub fn same_variant_as(&self, other: &Self) -> bool {
         matches!( 
@@ -1,30 +1,54 @@
 use clap::ArgMatches;
 use anyhow::Result;
 use std::io::{
-    Read, Seek, SeekFrom, Write
+    self, Read, Seek, SeekFrom, Write
 };
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::os::unix::ffi::OsStringExt;
 use std::os::unix::fs::FileExt;
 use std::os::unix::ffi::OsStrExt;
 use std::ffi::OsString;
 use std::str::FromStr;
+use crate::signing::signing_utils::generate_temp_path;
+use crate::las::profile::profile_config_path;
+
+// ========== ENVIRONMENT (for CI && containers, where the binary's saved path can't be rewritten)
+// SIGRS_CONFIG_PATH=/ci/config.sigrs sigrs list-all
+// SIGRS_PROFILE=work sigrs list-all
+// SIGRS_IDENTITY=deploy sigrs sign --working-dir
+// SIGRS_USERNAME="CI Bot" SIGRS_EMAIL=ci@corp.com sigrs sign --working-dir
+// == a flag always wins over its variable, a variable over .sigrs.toml, profiles.toml
+// == && the path saved in the binary. Empty variables are ignored
+
+pub const ENV_CONFIG_PATH: &str = "SIGRS_CONFIG_PATH";
+pub const ENV_PROFILE: &str = "SIGRS_PROFILE";
+pub const ENV_IDENTITY: &str = "SIGRS_IDENTITY";
+pub const ENV_USERNAME: &str = "SIGRS_USERNAME";
+pub const ENV_EMAIL: &str = "SIGRS_EMAIL";
+
+/// Value of the environment variable `name`, None if unset or empty
+pub fn env_value(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
 
-/// If `--config-path` flag passed, uses that value
-/// Else, reads config-path appended to binary (set at build time to default config dir)
-/// example default unix: `/home/alice/.config/sigrs/config.sigrs`
+/// Picks config.sigrs, first match wins:
+/// `--config-path` > `--profile` > `SIGRS_CONFIG_PATH` > `SIGRS_PROFILE` > the active
+/// profile (see `las/profile.rs`) > config-path appended to binary (set at build time
+/// to default config dir), example default unix: `/home/alice/.config/sigrs/config.sigrs`
 pub fn extract_config_path(args: &ArgMatches) -> Result<OsString, String> {
 
-    match args.get_one::<String>("config-path") {
-        Some(path) => {
-            let os = OsString::from_str(path.as_str()).map_err(|e| e.to_string())?;
-            Ok(os)
-        },
-        None => {
-            let path = get_config_path()?;
-            Ok(path)
+    // not every command takes --config-path
+    if let Some(path) = args.try_get_one::<String>("config-path").ok().flatten() {
+        return OsString::from_str(path.as_str()).map_err(|e| e.to_string());
+    }
+    let profile = args.try_get_one::<String>("profile").ok().flatten().cloned();
+    if profile.is_none() {
+        if let Some(path) = env_value(ENV_CONFIG_PATH) {
+            return Ok(OsString::from(path));
         }
     }
+    profile_config_path(profile.or_else(|| env_value(ENV_PROFILE)).as_deref())
 }
 
 /// Returns the config path appended to the end of this binary
@@ -111,3 +135,66 @@ pub fn print_bin_path() -> Result<(), String> {
         }
     }
 }
+
+/// Replaces the file at `path` with `content` without ever leaving it half written
+/// - write to a temp file in the same directory
+/// - fsync() the temp file
+/// - rename the temp file over `path`
+/// - fsync() the containing directory
+///
+/// A crash at any point leaves either the old or the new file, never neither.
+/// Permissions of an existing file at `path` are kept. A symlinked `path` (e.g. dotfiles)
+/// stays a link, the file it points to is replaced instead
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    // renaming over the link itself would swap it for a regular file
+    let resolved = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = resolved.as_path();
+    // bare file names have an empty parent
+    let parent_dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let base = parent_dir.join(path.file_name().ok_or_else(|| String::from("Invalid file name"))?);
+    let tmp_base = generate_temp_path(&base)?;
+
+    let mut tmp = None;
+    for attempt in 0..200u8 {
+        let mut tmp_path_try = tmp_base.clone().into_os_string();
+        tmp_path_try.push(format!(".{}", attempt));
+        let tmp_path_try = PathBuf::from(tmp_path_try);
+
+        match File::options().write(true).create_new(true).open(&tmp_path_try) {
+            Ok(f) => {
+                tmp = Some((f, tmp_path_try));
+                break;
+            },
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Problem creating temp file: {:?}", e)),
+        }
+    }
+    let Some((mut tmp_file, tmp_path)) = tmp else {
+        return Err(String::from("Cannot create temp file"));
+    };
+
+    let res = (|| {
+        if let Ok(meta) = fs::metadata(path) {
+            tmp_file.set_permissions(meta.permissions())
+                .map_err(|e| format!("Problem copying permissions: {:?}", e))?;
+        }
+        tmp_file.write_all(content)
+            .map_err(|e| format!("Problem writing temp file: {:?}", e))?;
+        tmp_file.sync_all()
+            .map_err(|e| format!("Problem syncing temp file: {:?}", e))?;
+        fs::rename(&tmp_path, path)
+            .map_err(|e| format!("Problem replacing {}: {:?}", path.display(), e))
+    })();
+
+    if res.is_err() {
+        // original is untouched, don't leave the temp file behind
+        let _ = fs::remove_file(&tmp_path);
+        return res;
+    }
+
+    let dir_file = File::open(&parent_dir).map_err(|e| e.to_string())?;
+    dir_file.sync_all().map_err(|e| e.to_string())
+}
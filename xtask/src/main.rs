@@ -91,7 +91,9 @@ fn build() -> Result<(), String> {
     Ok(())
 }
 
+// must match `las_header()` in core/src/sigrs_bin/las/version.rs
 const EMPTY_CONFIG: &'static str = r#"# File generated by signatu_rs. Do not manually edit this file. #
+#!las-version:3
 "#;
 
 fn create_default_config(config_dir_path: Arc<PathBuf>) -> thread::Result<()> {